@@ -28,6 +28,7 @@ import { useEffect, useState } from "react";
 import { Buffer } from "buffer";
 import { Program, AnchorProvider } from "@coral-xyz/anchor";
 import IDL from "../../invoice_claim.json";
+import { findOrgConfigByAuthority } from "../../lib/org";
 
 const PROGRAM_ID = new PublicKey(import.meta.env.VITE_PROGRAM_ID);
 const PINATA_GATEWAY =
@@ -51,6 +52,9 @@ const statusMap: Record<number, string> = {
   3: "InEscrowReadyToSettle",
   4: "Paid",
   5: "Refunded",
+  6: "RefundCompleted",
+  7: "NeedsReview",
+  8: "ReviewRejected",
 };
 
 const STATUS_CONFIG: Record<string, { bg: string; text: string; icon: any }> = {
@@ -72,6 +76,9 @@ const STATUS_CONFIG: Record<string, { bg: string; text: string; icon: any }> = {
   },
   Paid: { bg: "bg-green-100", text: "text-green-800", icon: CheckCircle2 },
   Refunded: { bg: "bg-red-100", text: "text-red-800", icon: AlertCircle },
+  RefundCompleted: { bg: "bg-gray-100", text: "text-gray-800", icon: CheckCircle2 },
+  NeedsReview: { bg: "bg-amber-100", text: "text-amber-800", icon: AlertCircle },
+  ReviewRejected: { bg: "bg-red-100", text: "text-red-800", icon: AlertCircle },
 };
 
 const StatusBadge = ({ status }: { status: string }) => {
@@ -255,11 +262,7 @@ export function AdminDashboard() {
 
       const authority = wallet.publicKey;
 
-      // Derive org config PDA
-      const [orgConfigPda] = PublicKey.findProgramAddressSync(
-          [Buffer.from("org_config"), authority.toBuffer()],
-          PROGRAM_ID
-      );
+      const orgConfigPda = await findOrgConfigByAuthority(connection, PROGRAM_ID, authority);
 
       console.log("Closing org config PDA:", orgConfigPda.toBase58());
 
@@ -99,6 +99,9 @@ export function AuditQueue() {
                         3: "InEscrowReadyToSettle",
                         4: "Paid",
                         5: "Refunded",
+                        6: "RefundCompleted",
+                        7: "NeedsReview",
+                        8: "ReviewRejected",
                     };
                     const status = statusMap[statusByte] || "Unknown";
 
@@ -158,18 +161,20 @@ export function AuditQueue() {
             });
             const program = new Program(IDL as any, provider);
 
-            // Derive required PDAs
+            // The invoice's org comes from its vendor, so any of the org's auditors can vote
             const reviewer = wallet.publicKey;
-            const [orgConfigPda] = PublicKey.findProgramAddressSync(
-                [Buffer.from("org_config"), reviewer.toBuffer()],
-                PROGRAM_ID
-            );
+            const vendorAccount = await (program.account as any).vendorAccount.fetch(new PublicKey(invoice.vendor));
+            const orgConfigPda: PublicKey = vendorAccount.org;
             // Use the actual invoice PDA from the row
             const invoicePda = new PublicKey(invoice.pubkey);
-            const [paymentQueuePda] = PublicKey.findProgramAddressSync(
-                [Buffer.from("payment_queue"), orgConfigPda.toBuffer()],
+
+            // Votes are collected on a ballot; its rent goes back to whoever opened it
+            const [auditBallotPda] = PublicKey.findProgramAddressSync(
+                [Buffer.from("audit_ballot"), invoicePda.toBuffer()],
                 PROGRAM_ID
             );
+            const ballot = await (program.account as any).auditBallot.fetchNullable(auditBallotPda);
+            const rentReceiver: PublicKey = ballot ? ballot.payer : reviewer;
 
             // Call on-chain method via Anchor (no manual discriminator needed)
             const txSig = await program.methods
@@ -178,12 +183,21 @@ export function AuditQueue() {
                     reviewer,
                     orgConfig: orgConfigPda,
                     invoiceAccount: invoicePda,
-                    paymentQueue: paymentQueuePda,
+                    vendorAccount: new PublicKey(invoice.vendor),
+                    auditBallot: auditBallotPda,
+                    rentReceiver,
                 })
                 .rpc();
 
             console.log("AuditDecide tx:", txSig);
-            alert(`Invoice ${approve ? "approved" : "rejected"} successfully!`);
+
+            // The ballot is closed once the quorum is reached
+            const open = await (program.account as any).auditBallot.fetchNullable(auditBallotPda);
+            alert(
+                open
+                    ? `Vote recorded: ${open.approvals.length} approve, ${open.rejections.length} reject, ${open.threshold} needed`
+                    : `Invoice ${approve ? "approved" : "rejected"} successfully!`
+            );
 
             // Refresh the list
             await fetchPendingInvoices();
@@ -63,6 +63,21 @@ const STATUS_CONFIG: Record<
     color: "bg-red-500/20 text-red-400 border-red-500/30",
     icon: <AlertCircle className="w-4 h-4" />,
   },
+  refundCompleted: {
+    label: "Refund Completed",
+    color: "bg-slate-500/20 text-slate-400 border-slate-500/30",
+    icon: <CheckCircle className="w-4 h-4" />,
+  },
+  needsReview: {
+    label: "Needs Review",
+    color: "bg-amber-500/20 text-amber-400 border-amber-500/30",
+    icon: <AlertCircle className="w-4 h-4" />,
+  },
+  reviewRejected: {
+    label: "Review Rejected",
+    color: "bg-red-500/20 text-red-400 border-red-500/30",
+    icon: <AlertCircle className="w-4 h-4" />,
+  },
 };
 
 export function InvoiceManagement() {
@@ -75,29 +75,36 @@ export function PaymentQueue() {
                     offset += 32;
                     console.log(`  Org: ${org.toBase58()}`);
 
-                    // pending_invoices: Vec<PendingPayment>
-                    const len = account.data.readUInt32LE(offset);
-                    offset += 4;
-                    console.log(`  Pending invoices count: ${len}`);
-
-                    for (let i = 0; i < len; i++) {
-                        // invoice_account: Pubkey (32 bytes)
-                        const invoiceAccount = new PublicKey(account.data.slice(offset, offset + 32));
-                        offset += 32;
-
-                        // vendor: Pubkey (32 bytes)
-                        const vendor = new PublicKey(account.data.slice(offset, offset + 32));
-                        offset += 32;
-
-                        // due_date: i64 (8 bytes)
-                        const dueDate = Number(account.data.readBigInt64LE(offset));
-                        offset += 8;
-
-                        // amount: u64 (8 bytes)
-                        const amount = Number(account.data.readBigUInt64LE(offset));
-                        offset += 8;
-
-                        console.log(`  Invoice ${i}: ${invoiceAccount.toBase58()}, Amount: ${amount}, Due: ${dueDate}`);
+                    // head: Option<Pubkey> -> first QueueEntry of the linked list
+                    const hasHead = account.data[offset] === 1;
+                    offset += 1;
+                    let next: PublicKey | null = hasHead
+                        ? new PublicKey(account.data.slice(offset, offset + 32))
+                        : null;
+
+                    let i = 0;
+                    while (next) {
+                        const entryInfo = await connection.getAccountInfo(next);
+                        if (!entryInfo) {
+                            console.warn(`  Queue entry ${next.toBase58()} not found, stopping walk`);
+                            break;
+                        }
+                        const data = entryInfo.data;
+
+                        // QueueEntry: 8 disc | 32 queue | 32 invoice | 32 vendor | 8 due | 8 amount | Option prev | Option next | ...
+                        let o = 8 + 32;
+                        const invoiceAccount = new PublicKey(data.slice(o, o + 32));
+                        o += 32;
+                        const vendor = new PublicKey(data.slice(o, o + 32));
+                        o += 32;
+                        const dueDate = Number(data.readBigInt64LE(o));
+                        o += 8;
+                        const amount = Number(data.readBigUInt64LE(o));
+                        o += 8;
+                        o += data[o] === 1 ? 33 : 1; // prev
+                        next = data[o] === 1 ? new PublicKey(data.slice(o + 1, o + 33)) : null;
+
+                        console.log(`  Invoice ${i++}: ${invoiceAccount.toBase58()}, Amount: ${amount}, Due: ${dueDate}`);
 
                         fetchedInvoices.push({
                             invoiceAccount: invoiceAccount.toBase58(),
@@ -102,7 +102,8 @@ export function SetupPage({ onComplete }: SetupPageProps) {
       // Authority is the wallet's public key
       const authority = wallet.publicKey;
 
-      // Derive org config PDA
+      // A new org's PDA is seeded by its creator, which is the initializing
+      // authority; later lookups go through findOrgConfigByAuthority instead
       const [orgConfigPda] = PublicKey.findProgramAddressSync(
           [Buffer.from("org_config"), authority.toBuffer()],
           PROGRAM_ID
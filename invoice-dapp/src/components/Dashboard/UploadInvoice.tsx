@@ -151,7 +151,8 @@ export function UploadInvoice() {
 
   const createInvoiceRequestOnChain = async (
       ipfsHash: string,
-      amount: number
+      amount: number,
+      orgConfig: PublicKey
   ): Promise<void> => {
     if (!wallet.publicKey) {
       throw new Error("Wallet not connected");
@@ -209,6 +210,7 @@ export function UploadInvoice() {
           .requestInvoiceExtraction(ipfsHash, new BN(amount), nonceBN)
           .accountsStrict({
             authority: authority,
+            orgConfig,
             invoiceRequest: invoiceRequestPda,
             systemProgram: SystemProgram.programId,
           })
@@ -413,7 +415,11 @@ export function UploadInvoice() {
       );
       console.log("Amount as number:", amountInLowestUnit);
 
-      await createInvoiceRequestOnChain(ipfsHash, amountInLowestUnit);
+      await createInvoiceRequestOnChain(
+          ipfsHash,
+          amountInLowestUnit,
+          new PublicKey(formData.organizationPubkey)
+      );
 
       setSuccess(
           `Invoice uploaded successfully! IPFS Hash: ${ipfsHash.slice(
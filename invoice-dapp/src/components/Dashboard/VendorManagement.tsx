@@ -18,6 +18,7 @@ import { Program, AnchorProvider } from "@coral-xyz/anchor";
 import { PublicKey, SystemProgram } from "@solana/web3.js";
 import { Buffer } from "buffer";
 import IDL from "../../invoice_claim.json";
+import { findOrgConfigByAuthority } from "../../lib/org";
 
 const PROGRAM_ID = new PublicKey(import.meta.env.VITE_PROGRAM_ID);
 
@@ -59,11 +60,7 @@ export function VendorManagement() {
 
       const authority = wallet.publicKey;
 
-      // Derive org config PDA
-      const [orgConfigPda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("org_config"), authority.toBuffer()],
-        PROGRAM_ID
-      );
+      const orgConfigPda = await findOrgConfigByAuthority(connection, PROGRAM_ID, authority);
 
       // Fetch all program accounts
       const allAccounts = await connection.getProgramAccounts(PROGRAM_ID);
@@ -149,10 +146,7 @@ export function VendorManagement() {
       const program = new Program(IDL, provider);
       const authority = wallet.publicKey!;
 
-      const [orgConfigPda] = PublicKey.findProgramAddressSync(
-          [Buffer.from("org_config"), authority.toBuffer()],
-          PROGRAM_ID
-      );
+      const orgConfigPda = await findOrgConfigByAuthority(connection, PROGRAM_ID, authority);
 
       const [vendorPda] = PublicKey.findProgramAddressSync(
           [
@@ -206,10 +200,7 @@ export function VendorManagement() {
       const program = new Program(IDL, provider);
       const authority = wallet.publicKey!;
 
-      const [orgConfigPda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("org_config"), authority.toBuffer()],
-        PROGRAM_ID
-      );
+      const orgConfigPda = await findOrgConfigByAuthority(connection, PROGRAM_ID, authority);
 
       const [vendorPda] = PublicKey.findProgramAddressSync(
         [
@@ -256,10 +247,7 @@ export function VendorManagement() {
       const program = new Program(IDL, provider);
       const authority = wallet.publicKey!;
 
-      const [orgConfigPda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("org_config"), authority.toBuffer()],
-        PROGRAM_ID
-      );
+      const orgConfigPda = await findOrgConfigByAuthority(connection, PROGRAM_ID, authority);
 
       const [vendorPda] = PublicKey.findProgramAddressSync(
         [
@@ -299,10 +287,7 @@ export function VendorManagement() {
       const program = new Program(IDL, provider);
       const authority = wallet.publicKey!;
 
-      const [orgConfigPda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("org_config"), authority.toBuffer()],
-        PROGRAM_ID
-      );
+      const orgConfigPda = await findOrgConfigByAuthority(connection, PROGRAM_ID, authority);
 
       const [vendorPda] = PublicKey.findProgramAddressSync(
         [
@@ -346,10 +331,7 @@ export function VendorManagement() {
       const program = new Program(IDL, provider);
       const authority = wallet.publicKey!;
 
-      const [orgConfigPda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("org_config"), authority.toBuffer()],
-        PROGRAM_ID
-      );
+      const orgConfigPda = await findOrgConfigByAuthority(connection, PROGRAM_ID, authority);
 
       const [vendorPda] = PublicKey.findProgramAddressSync(
         [
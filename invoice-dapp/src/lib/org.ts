@@ -0,0 +1,29 @@
+import { Connection, PublicKey } from "@solana/web3.js";
+import { Buffer } from "buffer";
+import bs58 from "bs58";
+import IDL from "../invoice_claim.json";
+
+const ORG_CONFIG_DISCRIMINATOR = Buffer.from(
+  IDL.accounts.find((account) => account.name === "OrgConfig")!.discriminator
+);
+
+// OrgConfig PDAs are seeded by the org's creator, which stays fixed across
+// authority transfers, so look the org up by its current `authority` field
+// (right after the discriminator) instead of deriving it from the wallet.
+export async function findOrgConfigByAuthority(
+  connection: Connection,
+  programId: PublicKey,
+  authority: PublicKey
+): Promise<PublicKey> {
+  const accounts = await connection.getProgramAccounts(programId, {
+    filters: [
+      { memcmp: { offset: 0, bytes: bs58.encode(ORG_CONFIG_DISCRIMINATOR) } },
+      { memcmp: { offset: 8, bytes: authority.toBase58() } },
+    ],
+  });
+
+  if (accounts.length === 0) {
+    throw new Error("No organization found with this wallet as authority");
+  }
+  return accounts[0].pubkey;
+}
@@ -0,0 +1,242 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+use std::fmt;
+
+use crate::discriminator;
+
+#[derive(Debug)]
+pub enum DecodeError {
+    TooShort(usize),
+    WrongDiscriminator { account: &'static str, found: [u8; 8] },
+    Borsh(std::io::Error),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::TooShort(len) => write!(f, "account data too short ({} bytes)", len),
+            DecodeError::WrongDiscriminator { account, found } => {
+                write!(f, "not a {} account (discriminator {:02x?})", account, found)
+            }
+            DecodeError::Borsh(e) => write!(f, "borsh decode failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// An Anchor `#[account]` owned by the invoice-claim program.
+pub trait ProgramAccount: BorshDeserialize + BorshSerialize {
+    /// Struct name in `state.rs`; the discriminator is derived from it.
+    const NAME: &'static str;
+
+    fn discriminator() -> [u8; 8] {
+        discriminator(&format!("account:{}", Self::NAME))
+    }
+
+    /// Decode raw account data, checking the discriminator. Trailing bytes
+    /// (unused `InitSpace` padding) are ignored.
+    fn try_from_account_data(data: &[u8]) -> Result<Self, DecodeError> {
+        if data.len() < 8 {
+            return Err(DecodeError::TooShort(data.len()));
+        }
+        let found: [u8; 8] = data[..8].try_into().unwrap();
+        if found != Self::discriminator() {
+            return Err(DecodeError::WrongDiscriminator { account: Self::NAME, found });
+        }
+        Self::deserialize(&mut &data[8..]).map_err(DecodeError::Borsh)
+    }
+
+    /// Discriminator followed by the Borsh body, as stored on chain.
+    fn to_account_data(&self) -> Vec<u8> {
+        let mut data = Self::discriminator().to_vec();
+        self.serialize(&mut data).expect("writing to a Vec cannot fail");
+        data
+    }
+}
+
+macro_rules! program_account {
+    ($($name:ident),* $(,)?) => {
+        $(impl ProgramAccount for $name {
+            const NAME: &'static str = stringify!($name);
+        })*
+    };
+}
+
+program_account!(
+    OrgConfig,
+    VendorAccount,
+    InvoiceRequest,
+    InvoiceAccount,
+    InvoiceRegistry,
+    PaymentQueue,
+    QueueEntry,
+    AuditBallot,
+);
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequestStatus {
+    Pending,
+    Completed,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvoiceStatus {
+    Validated,
+    InEscrowAwaitingVRF,
+    InEscrowAuditPending,
+    InEscrowReadyToSettle,
+    Paid,
+    Refunded,
+    RefundCompleted,
+    NeedsReview,
+    ReviewRejected,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct OrgConfig {
+    pub authority: Pubkey,
+    pub oracle_signer: Pubkey,
+    pub treasury_vault: Pubkey,
+    pub mint: Pubkey,
+    pub per_invoice_cap: u64,
+    pub daily_cap: u64,
+    pub daily_spent: u64,
+    pub last_reset_day: i64,
+    pub audit_rate_bps: u16,
+    pub paused: bool,
+    pub invoice_counter: u64,
+    pub version: u8,
+    pub bump: u8,
+    pub extra_oracle_signers: Vec<Pubkey>,
+    pub amount_tolerance: u64,
+    pub amount_tolerance_bps: u16,
+    pub creator: Pubkey,
+    pub pending_authority: Option<Pubkey>,
+    pub treasurer: Pubkey,
+    pub auditor: Pubkey,
+    pub vendor_manager: Pubkey,
+    pub auditors: Vec<Pubkey>,
+    pub audit_threshold: u8,
+}
+
+impl OrgConfig {
+    // Primary oracle signer or one of the extra authorized oracle keys
+    pub fn is_oracle(&self, key: &Pubkey) -> bool {
+        *key == self.oracle_signer || self.extra_oracle_signers.contains(key)
+    }
+
+    // Role checks mirror the program: the authority can act as treasurer and
+    // vendor manager, and as auditor only while no auditor set is configured
+    pub fn is_treasurer(&self, key: &Pubkey) -> bool {
+        *key == self.authority || *key == self.treasurer
+    }
+
+    pub fn is_auditor(&self, key: &Pubkey) -> bool {
+        if self.auditors.is_empty() {
+            *key == self.authority || *key == self.auditor
+        } else {
+            self.auditors.contains(key)
+        }
+    }
+
+    // Votes needed on either side to settle an audit
+    pub fn audit_quorum(&self) -> u8 {
+        if self.auditors.is_empty() { 1 } else { self.audit_threshold }
+    }
+
+    pub fn is_vendor_manager(&self, key: &Pubkey) -> bool {
+        *key == self.authority || *key == self.vendor_manager
+    }
+
+    // Mirrors the program's check in process_extraction_result
+    pub fn amount_within_tolerance(&self, declared: u64, extracted: u64) -> bool {
+        let diff = declared.abs_diff(extracted) as u128;
+        diff <= self.amount_tolerance as u128
+            || diff * 10_000 <= declared as u128 * self.amount_tolerance_bps as u128
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct VendorAccount {
+    pub org: Pubkey,
+    pub vendor_name: String,
+    pub wallet: Pubkey,
+    pub total_paid: u64,
+    pub last_payment: i64,
+    pub is_active: bool,
+    pub currency_preference: Pubkey,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InvoiceRequest {
+    pub authority: Pubkey,
+    pub ipfs_hash: String,
+    pub status: RequestStatus,
+    pub timestamp: i64,
+    pub amount: u64,
+    pub nonce: u64,
+    pub org: Pubkey,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InvoiceAccount {
+    pub authority: Pubkey,
+    pub vendor: Pubkey,                 // VendorAccount PDA
+    pub vendor_name: String,
+    pub amount: u64,
+    pub due_date: i64,
+    pub ipfs_hash: String,
+    pub status: InvoiceStatus,
+    pub timestamp: i64,
+    pub nonce: u64,
+    pub invoice_number: String,
+    pub escrowed_at: i64,
+}
+
+/// Uniqueness claim on a document or vendor invoice number; see
+/// `pda::document_registry` and `pda::number_registry`.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InvoiceRegistry {
+    pub org: Pubkey,                    // OrgConfig PDA
+    pub invoice: Pubkey,                // InvoiceAccount that claimed the key
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+/// Open audit vote on one invoice; see `pda::audit_ballot`. Closed once
+/// approvals or rejections reach `threshold`.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AuditBallot {
+    pub invoice: Pubkey,
+    pub org: Pubkey,
+    pub threshold: u8,
+    pub voters: u8,
+    pub approvals: Vec<Pubkey>,
+    pub rejections: Vec<Pubkey>,
+    pub payer: Pubkey,                  // Receives the rent back when the ballot closes
+    pub bump: u8,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PaymentQueue {
+    pub org: Pubkey,
+    pub head: Option<Pubkey>,
+    pub tail: Option<Pubkey>,
+    pub count: u64,
+    pub last_updated: i64,
+    pub bump: u8,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct QueueEntry {
+    pub queue: Pubkey,
+    pub invoice_account: Pubkey,
+    pub vendor: Pubkey,
+    pub due_date: i64,
+    pub amount: u64,
+    pub prev: Option<Pubkey>,
+    pub next: Option<Pubkey>,
+    pub payer: Pubkey,
+    pub bump: u8,
+}
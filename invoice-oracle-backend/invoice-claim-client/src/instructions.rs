@@ -0,0 +1,886 @@
+//! One builder per program instruction. Each takes the program id, an
+//! `*Accounts` struct with the caller-supplied keys (fixed program/sysvar
+//! addresses are filled in here) and, where the instruction has arguments,
+//! the argument struct that is Borsh-encoded after the discriminator.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::pubkey::Pubkey;
+use solana_program::{system_program, sysvar};
+
+use crate::{
+    discriminator, DEFAULT_VRF_QUEUE, DELEGATION_PROGRAM_ID, MAGIC_CONTEXT_ID, MAGIC_PROGRAM_ID,
+    TOKEN_PROGRAM_ID, VRF_PROGRAM_ID, VRF_PROGRAM_IDENTITY,
+};
+
+/// Instruction arguments, prefixed on the wire by the instruction
+/// discriminator (`sha256("global:<NAME>")[..8]` unless the handler sets one).
+pub trait InstructionData: BorshSerialize {
+    /// Handler name in `lib.rs`.
+    const NAME: &'static str;
+
+    fn discriminator() -> Vec<u8> {
+        discriminator(&format!("global:{}", Self::NAME)).to_vec()
+    }
+
+    fn data(&self) -> Vec<u8> {
+        let mut data = Self::discriminator();
+        self.serialize(&mut data).expect("writing to a Vec cannot fail");
+        data
+    }
+}
+
+macro_rules! instruction_data {
+    ($($ty:ident => $name:literal),* $(,)?) => {
+        $(impl InstructionData for $ty {
+            const NAME: &'static str = $name;
+        })*
+    };
+}
+
+instruction_data! {
+    RequestInvoiceExtraction => "request_invoice_extraction",
+    DelegateInvoiceExtraction => "delegate_invoice_extraction",
+    CommitInvoiceExtraction => "commit_invoice_extraction",
+    ProcessExtractionResult => "process_extraction_result",
+    DebugRequestPda => "debug_request_pda",
+    RequestInvoiceAuditVrf => "request_invoice_audit_vrf",
+    AuditDecide => "audit_decide",
+    ReviewInvoiceAmount => "review_invoice_amount",
+    OrgInit => "org_init",
+    UpdateOrgConfig => "update_org_config",
+    ProposeOrgAuthority => "propose_org_authority",
+    AcceptOrgAuthority => "accept_org_authority",
+    GetRemainingDailyBudget => "get_remaining_daily_budget",
+    RegisterVendor => "register_vendor",
+    DeactivateVendor => "deactivate_vendor",
+    ActivateVendor => "activate_vendor",
+    UpdateVendorWallet => "update_vendor_wallet",
+    FundEscrow => "fund_escrow",
+    SettleToVendor => "settle_to_vendor",
+    RefundEscrow => "refund_escrow",
+    InitPaymentQueue => "init_payment_queue",
+    AddToPaymentQueue => "add_to_payment_queue",
+    RemoveFromPaymentQueue => "remove_from_payment_queue",
+    ProcessInvoicePayment => "process_invoice_payment",
+    CloseInvoice => "close_invoice",
+    CloseRequest => "close_request",
+    CloseVendor => "close_vendor",
+    CloseOrg => "close_org",
+}
+
+pub const CALLBACK_INVOICE_VRF_DISCRIMINATOR: &[u8] = b"clbrand";
+
+fn build(program_id: &Pubkey, accounts: Vec<AccountMeta>, data: Vec<u8>) -> Instruction {
+    Instruction { program_id: *program_id, accounts, data }
+}
+
+/// Anchor expects the program id in place of an omitted optional account.
+pub fn optional_meta(key: Option<Pubkey>, program_id: &Pubkey) -> AccountMeta {
+    match key {
+        Some(k) => AccountMeta::new(k, false),
+        None => AccountMeta::new_readonly(*program_id, false),
+    }
+}
+
+// --- Invoice extraction ---
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RequestInvoiceExtraction {
+    pub ipfs_hash: String,
+    pub amount: u64,
+    pub nonce: u64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RequestInvoiceExtractionAccounts {
+    pub authority: Pubkey,
+    pub org_config: Pubkey,
+    pub invoice_request: Pubkey,
+}
+
+pub fn request_invoice_extraction(
+    program_id: &Pubkey,
+    a: &RequestInvoiceExtractionAccounts,
+    args: &RequestInvoiceExtraction,
+) -> Instruction {
+    build(
+        program_id,
+        vec![
+            AccountMeta::new(a.authority, true),
+            AccountMeta::new_readonly(a.org_config, false),
+            AccountMeta::new(a.invoice_request, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        args.data(),
+    )
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DelegateInvoiceExtraction;
+
+#[derive(Clone, Debug, Default)]
+pub struct DelegateInvoiceExtractionAccounts {
+    pub invoice_request: Pubkey,
+    pub authority: Pubkey,
+}
+
+/// The validator to delegate to, if any, goes in `remaining_accounts`.
+pub fn delegate_invoice_extraction(
+    program_id: &Pubkey,
+    a: &DelegateInvoiceExtractionAccounts,
+    validator: Option<Pubkey>,
+) -> Instruction {
+    let (buffer, record, metadata) = crate::pda::delegation_accounts(program_id, &a.invoice_request);
+    let mut accounts = vec![
+        AccountMeta::new(buffer, false),
+        AccountMeta::new(record, false),
+        AccountMeta::new(metadata, false),
+        AccountMeta::new(a.invoice_request, false),
+        AccountMeta::new(a.authority, true),
+        AccountMeta::new_readonly(system_program::ID, false),
+        AccountMeta::new_readonly(*program_id, false),
+        AccountMeta::new_readonly(DELEGATION_PROGRAM_ID, false),
+    ];
+    if let Some(v) = validator {
+        accounts.push(AccountMeta::new_readonly(v, false));
+    }
+    build(program_id, accounts, DelegateInvoiceExtraction.data())
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CommitInvoiceExtraction {
+    pub extracted_amount: u64,
+    pub extracted_vendor: Pubkey,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CommitInvoiceExtractionAccounts {
+    pub invoice_request: Pubkey,
+    pub org_config: Pubkey,
+    pub oracle: Pubkey,
+}
+
+pub fn commit_invoice_extraction(
+    program_id: &Pubkey,
+    a: &CommitInvoiceExtractionAccounts,
+    args: &CommitInvoiceExtraction,
+) -> Instruction {
+    build(
+        program_id,
+        vec![
+            AccountMeta::new(a.invoice_request, false),
+            AccountMeta::new_readonly(a.org_config, false),
+            AccountMeta::new_readonly(a.oracle, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(MAGIC_PROGRAM_ID, false),
+            AccountMeta::new(MAGIC_CONTEXT_ID, false),
+        ],
+        args.data(),
+    )
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProcessExtractionResult {
+    pub vendor_name: String,
+    pub amount: u64,
+    pub due_date: i64,
+    /// Empty when the invoice has none; at most 32 bytes.
+    pub invoice_number: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ProcessExtractionResultAccounts {
+    pub payer: Pubkey,
+    pub org_config: Pubkey,
+    pub vendor_account: Pubkey,
+    pub invoice_request: Pubkey,
+    pub invoice_account: Pubkey,
+    pub document_registry: Pubkey,
+    pub number_registry: Pubkey,
+}
+
+pub fn process_extraction_result(
+    program_id: &Pubkey,
+    a: &ProcessExtractionResultAccounts,
+    args: &ProcessExtractionResult,
+) -> Instruction {
+    build(
+        program_id,
+        vec![
+            AccountMeta::new(a.payer, true),
+            AccountMeta::new(a.org_config, false),
+            AccountMeta::new_readonly(a.vendor_account, false),
+            AccountMeta::new(a.invoice_request, false),
+            AccountMeta::new(a.invoice_account, false),
+            AccountMeta::new(a.document_registry, false),
+            AccountMeta::new(a.number_registry, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        args.data(),
+    )
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DebugRequestPda {
+    pub authority: Pubkey,
+    pub nonce: u64,
+}
+
+pub fn debug_request_pda(program_id: &Pubkey, args: &DebugRequestPda) -> Instruction {
+    build(
+        program_id,
+        vec![AccountMeta::new_readonly(system_program::ID, false)],
+        args.data(),
+    )
+}
+
+// --- VRF audit routing ---
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RequestInvoiceAuditVrf {
+    pub client_seed: u8,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RequestInvoiceAuditVrfAccounts {
+    pub payer: Pubkey,
+    pub org_config: Pubkey,
+    pub invoice_account: Pubkey,
+    pub vendor_account: Pubkey,
+}
+
+pub fn request_invoice_audit_vrf(
+    program_id: &Pubkey,
+    a: &RequestInvoiceAuditVrfAccounts,
+    args: &RequestInvoiceAuditVrf,
+) -> Instruction {
+    build(
+        program_id,
+        vec![
+            AccountMeta::new(a.payer, true),
+            AccountMeta::new(a.org_config, false),
+            AccountMeta::new(a.invoice_account, false),
+            AccountMeta::new_readonly(a.vendor_account, false),
+            AccountMeta::new(DEFAULT_VRF_QUEUE, false),
+            AccountMeta::new_readonly(crate::pda::program_identity(program_id), false),
+            AccountMeta::new_readonly(VRF_PROGRAM_ID, false),
+            AccountMeta::new_readonly(sysvar::slot_hashes::ID, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        args.data(),
+    )
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CallbackInvoiceVrf {
+    pub randomness: [u8; 32],
+}
+
+impl InstructionData for CallbackInvoiceVrf {
+    const NAME: &'static str = "callback_invoice_vrf";
+
+    // Set with `#[instruction(discriminator = ...)]`; the VRF request passes it
+    // as the callback discriminator
+    fn discriminator() -> Vec<u8> {
+        CALLBACK_INVOICE_VRF_DISCRIMINATOR.to_vec()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CallbackInvoiceVrfAccounts {
+    pub invoice_account: Pubkey,
+    pub org_config: Pubkey,
+    pub vendor_account: Pubkey,
+}
+
+/// Normally sent by the VRF program; built here for tests and tooling.
+pub fn callback_invoice_vrf(
+    program_id: &Pubkey,
+    a: &CallbackInvoiceVrfAccounts,
+    args: &CallbackInvoiceVrf,
+) -> Instruction {
+    build(
+        program_id,
+        vec![
+            AccountMeta::new_readonly(VRF_PROGRAM_IDENTITY, false),
+            AccountMeta::new(a.invoice_account, false),
+            AccountMeta::new_readonly(a.org_config, false),
+            AccountMeta::new_readonly(a.vendor_account, false),
+        ],
+        args.data(),
+    )
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AuditDecide {
+    pub approve: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct AuditDecideAccounts {
+    pub reviewer: Pubkey,
+    pub org_config: Pubkey,
+    pub invoice_account: Pubkey,
+    pub vendor_account: Pubkey,
+    pub audit_ballot: Pubkey,
+    /// The ballot's `payer`, or the reviewer when casting the first vote.
+    pub rent_receiver: Pubkey,
+}
+
+pub fn audit_decide(program_id: &Pubkey, a: &AuditDecideAccounts, args: &AuditDecide) -> Instruction {
+    build(
+        program_id,
+        vec![
+            AccountMeta::new(a.reviewer, true),
+            AccountMeta::new(a.org_config, false),
+            AccountMeta::new(a.invoice_account, false),
+            AccountMeta::new_readonly(a.vendor_account, false),
+            AccountMeta::new(a.audit_ballot, false),
+            AccountMeta::new(a.rent_receiver, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        args.data(),
+    )
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ReviewInvoiceAmount {
+    pub approve: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ReviewInvoiceAmountAccounts {
+    pub reviewer: Pubkey,
+    pub org_config: Pubkey,
+    pub invoice_account: Pubkey,
+    pub vendor_account: Pubkey,
+    pub document_registry: Pubkey,
+    pub number_registry: Pubkey,
+}
+
+pub fn review_invoice_amount(
+    program_id: &Pubkey,
+    a: &ReviewInvoiceAmountAccounts,
+    args: &ReviewInvoiceAmount,
+) -> Instruction {
+    build(
+        program_id,
+        vec![
+            AccountMeta::new_readonly(a.reviewer, true),
+            AccountMeta::new_readonly(a.org_config, false),
+            AccountMeta::new(a.invoice_account, false),
+            AccountMeta::new_readonly(a.vendor_account, false),
+            AccountMeta::new(a.document_registry, false),
+            AccountMeta::new(a.number_registry, false),
+        ],
+        args.data(),
+    )
+}
+
+// --- Org ---
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct OrgInit {
+    pub treasury_vault: Pubkey,
+    pub mint: Pubkey,
+    pub per_invoice_cap: u64,
+    pub daily_cap: u64,
+    pub audit_rate_bps: u16,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct OrgInitAccounts {
+    pub org_config: Pubkey,
+    pub authority: Pubkey,
+}
+
+pub fn org_init(program_id: &Pubkey, a: &OrgInitAccounts, args: &OrgInit) -> Instruction {
+    build(
+        program_id,
+        vec![
+            AccountMeta::new(a.org_config, false),
+            AccountMeta::new(a.authority, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        args.data(),
+    )
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct UpdateOrgConfigArgs {
+    pub per_invoice_cap: Option<u64>,
+    pub daily_cap: Option<u64>,
+    pub paused: Option<bool>,
+    pub oracle_signer: Option<Pubkey>,
+    pub mint: Option<Pubkey>,
+    pub extra_oracle_signers: Option<Vec<Pubkey>>,
+    pub amount_tolerance: Option<u64>,
+    pub amount_tolerance_bps: Option<u16>,
+    pub treasurer: Option<Pubkey>,
+    pub auditor: Option<Pubkey>,
+    pub vendor_manager: Option<Pubkey>,
+    pub auditors: Option<Vec<Pubkey>>,
+    pub audit_threshold: Option<u8>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UpdateOrgConfig {
+    pub update_args: UpdateOrgConfigArgs,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct UpdateOrgConfigAccounts {
+    pub authority: Pubkey,
+    pub org_config: Pubkey,
+}
+
+pub fn update_org_config(
+    program_id: &Pubkey,
+    a: &UpdateOrgConfigAccounts,
+    args: &UpdateOrgConfig,
+) -> Instruction {
+    build(
+        program_id,
+        vec![
+            AccountMeta::new_readonly(a.authority, true),
+            AccountMeta::new(a.org_config, false),
+        ],
+        args.data(),
+    )
+}
+
+/// `None` cancels a pending proposal.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProposeOrgAuthority {
+    pub new_authority: Option<Pubkey>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ProposeOrgAuthorityAccounts {
+    pub authority: Pubkey,
+    pub org_config: Pubkey,
+}
+
+pub fn propose_org_authority(
+    program_id: &Pubkey,
+    a: &ProposeOrgAuthorityAccounts,
+    args: &ProposeOrgAuthority,
+) -> Instruction {
+    build(
+        program_id,
+        vec![
+            AccountMeta::new_readonly(a.authority, true),
+            AccountMeta::new(a.org_config, false),
+        ],
+        args.data(),
+    )
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AcceptOrgAuthority;
+
+#[derive(Clone, Debug, Default)]
+pub struct AcceptOrgAuthorityAccounts {
+    pub new_authority: Pubkey,
+    pub org_config: Pubkey,
+}
+
+pub fn accept_org_authority(program_id: &Pubkey, a: &AcceptOrgAuthorityAccounts) -> Instruction {
+    build(
+        program_id,
+        vec![
+            AccountMeta::new_readonly(a.new_authority, true),
+            AccountMeta::new(a.org_config, false),
+        ],
+        AcceptOrgAuthority.data(),
+    )
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GetRemainingDailyBudget;
+
+pub fn get_remaining_daily_budget(program_id: &Pubkey, org_config: &Pubkey) -> Instruction {
+    build(
+        program_id,
+        vec![AccountMeta::new_readonly(*org_config, false)],
+        GetRemainingDailyBudget.data(),
+    )
+}
+
+// --- Vendors ---
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RegisterVendor {
+    pub vendor_name: String,
+    pub wallet: Pubkey,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RegisterVendorAccounts {
+    pub vendor_account: Pubkey,
+    pub org_config: Pubkey,
+    pub authority: Pubkey,
+}
+
+pub fn register_vendor(program_id: &Pubkey, a: &RegisterVendorAccounts, args: &RegisterVendor) -> Instruction {
+    build(
+        program_id,
+        vec![
+            AccountMeta::new(a.vendor_account, false),
+            AccountMeta::new_readonly(a.org_config, false),
+            AccountMeta::new(a.authority, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        args.data(),
+    )
+}
+
+/// Accounts shared by the vendor admin instructions.
+#[derive(Clone, Debug, Default)]
+pub struct VendorAdminAccounts {
+    pub vendor_account: Pubkey,
+    pub org_config: Pubkey,
+    pub authority: Pubkey,
+}
+
+impl VendorAdminAccounts {
+    fn metas(&self) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(self.vendor_account, false),
+            AccountMeta::new_readonly(self.org_config, false),
+            AccountMeta::new_readonly(self.authority, true),
+        ]
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DeactivateVendor;
+
+pub fn deactivate_vendor(program_id: &Pubkey, a: &VendorAdminAccounts) -> Instruction {
+    build(program_id, a.metas(), DeactivateVendor.data())
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ActivateVendor;
+
+pub fn activate_vendor(program_id: &Pubkey, a: &VendorAdminAccounts) -> Instruction {
+    build(program_id, a.metas(), ActivateVendor.data())
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UpdateVendorWallet {
+    pub new_wallet: Pubkey,
+}
+
+pub fn update_vendor_wallet(
+    program_id: &Pubkey,
+    a: &VendorAdminAccounts,
+    args: &UpdateVendorWallet,
+) -> Instruction {
+    build(program_id, a.metas(), args.data())
+}
+
+// --- Escrow ---
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FundEscrow;
+
+#[derive(Clone, Debug, Default)]
+pub struct FundEscrowAccounts {
+    pub org_config: Pubkey,
+    pub invoice_account: Pubkey,
+    pub vendor_account: Pubkey,
+    pub escrow_authority: Pubkey,
+    pub payer: Pubkey,
+    pub authority: Pubkey,
+    pub payer_ata: Pubkey,
+    pub escrow_ata: Pubkey,
+    pub mint: Pubkey,
+}
+
+pub fn fund_escrow(program_id: &Pubkey, a: &FundEscrowAccounts) -> Instruction {
+    build(
+        program_id,
+        vec![
+            AccountMeta::new(a.org_config, false),
+            AccountMeta::new(a.invoice_account, false),
+            AccountMeta::new_readonly(a.vendor_account, false),
+            AccountMeta::new_readonly(a.escrow_authority, false),
+            AccountMeta::new(a.payer, true),
+            AccountMeta::new_readonly(a.authority, false),
+            AccountMeta::new(a.payer_ata, false),
+            AccountMeta::new(a.escrow_ata, false),
+            AccountMeta::new_readonly(a.mint, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+        ],
+        FundEscrow.data(),
+    )
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SettleToVendor;
+
+#[derive(Clone, Debug, Default)]
+pub struct SettleToVendorAccounts {
+    pub org_config: Pubkey,
+    pub invoice_account: Pubkey,
+    pub vendor_account: Pubkey,
+    pub payment_queue: Pubkey,
+    pub queue_entry: Pubkey,
+    pub prev_entry: Option<Pubkey>,
+    pub next_entry: Option<Pubkey>,
+    pub rent_receiver: Pubkey,
+    pub escrow_authority: Pubkey,
+    pub vendor_ata: Pubkey,
+    pub escrow_ata: Pubkey,
+    pub mint: Pubkey,
+    pub signer: Pubkey,
+}
+
+pub fn settle_to_vendor(program_id: &Pubkey, a: &SettleToVendorAccounts) -> Instruction {
+    build(
+        program_id,
+        vec![
+            AccountMeta::new(a.org_config, false),
+            AccountMeta::new(a.invoice_account, false),
+            AccountMeta::new(a.vendor_account, false),
+            AccountMeta::new(a.payment_queue, false),
+            AccountMeta::new(a.queue_entry, false),
+            optional_meta(a.prev_entry, program_id),
+            optional_meta(a.next_entry, program_id),
+            AccountMeta::new(a.rent_receiver, false),
+            AccountMeta::new_readonly(a.escrow_authority, false),
+            AccountMeta::new(a.vendor_ata, false),
+            AccountMeta::new(a.escrow_ata, false),
+            AccountMeta::new_readonly(a.mint, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+            AccountMeta::new_readonly(a.signer, true),
+        ],
+        SettleToVendor.data(),
+    )
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RefundEscrow;
+
+#[derive(Clone, Debug, Default)]
+pub struct RefundEscrowAccounts {
+    pub org_config: Pubkey,
+    pub invoice_account: Pubkey,
+    pub vendor_account: Pubkey,
+    pub document_registry: Pubkey,
+    pub number_registry: Pubkey,
+    pub escrow_authority: Pubkey,
+    pub escrow_ata: Pubkey,
+    pub refund_ata: Pubkey,
+    pub audit_ballot: Pubkey,
+    pub rent_receiver: Pubkey,
+    pub signer: Pubkey,
+}
+
+pub fn refund_escrow(program_id: &Pubkey, a: &RefundEscrowAccounts) -> Instruction {
+    build(
+        program_id,
+        vec![
+            AccountMeta::new(a.org_config, false),
+            AccountMeta::new(a.invoice_account, false),
+            AccountMeta::new_readonly(a.vendor_account, false),
+            AccountMeta::new(a.document_registry, false),
+            AccountMeta::new(a.number_registry, false),
+            AccountMeta::new_readonly(a.escrow_authority, false),
+            AccountMeta::new(a.escrow_ata, false),
+            AccountMeta::new(a.refund_ata, false),
+            AccountMeta::new(a.audit_ballot, false),
+            AccountMeta::new(a.rent_receiver, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+            AccountMeta::new_readonly(a.signer, true),
+        ],
+        RefundEscrow.data(),
+    )
+}
+
+// --- Payment queue ---
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InitPaymentQueue;
+
+#[derive(Clone, Debug, Default)]
+pub struct InitPaymentQueueAccounts {
+    pub authority: Pubkey,
+    pub org_config: Pubkey,
+    pub payment_queue: Pubkey,
+}
+
+pub fn init_payment_queue(program_id: &Pubkey, a: &InitPaymentQueueAccounts) -> Instruction {
+    build(
+        program_id,
+        vec![
+            AccountMeta::new(a.authority, true),
+            AccountMeta::new_readonly(a.org_config, false),
+            AccountMeta::new(a.payment_queue, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        InitPaymentQueue.data(),
+    )
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AddToPaymentQueue;
+
+#[derive(Clone, Debug, Default)]
+pub struct AddToPaymentQueueAccounts {
+    pub signer: Pubkey,
+    pub org_config: Pubkey,
+    pub invoice_account: Pubkey,
+    pub vendor_account: Pubkey,
+    pub payment_queue: Pubkey,
+    pub queue_entry: Pubkey,
+    pub prev_entry: Option<Pubkey>,
+    pub next_entry: Option<Pubkey>,
+}
+
+pub fn add_to_payment_queue(program_id: &Pubkey, a: &AddToPaymentQueueAccounts) -> Instruction {
+    build(
+        program_id,
+        vec![
+            AccountMeta::new(a.signer, true),
+            AccountMeta::new_readonly(a.org_config, false),
+            AccountMeta::new_readonly(a.invoice_account, false),
+            AccountMeta::new_readonly(a.vendor_account, false),
+            AccountMeta::new(a.payment_queue, false),
+            AccountMeta::new(a.queue_entry, false),
+            optional_meta(a.prev_entry, program_id),
+            optional_meta(a.next_entry, program_id),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        AddToPaymentQueue.data(),
+    )
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RemoveFromPaymentQueue {
+    pub invoice_key: Pubkey,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RemoveFromPaymentQueueAccounts {
+    pub signer: Pubkey,
+    pub org_config: Pubkey,
+    pub payment_queue: Pubkey,
+    pub queue_entry: Pubkey,
+    pub prev_entry: Option<Pubkey>,
+    pub next_entry: Option<Pubkey>,
+    pub rent_receiver: Pubkey,
+}
+
+pub fn remove_from_payment_queue(
+    program_id: &Pubkey,
+    a: &RemoveFromPaymentQueueAccounts,
+    args: &RemoveFromPaymentQueue,
+) -> Instruction {
+    build(
+        program_id,
+        vec![
+            AccountMeta::new_readonly(a.signer, true),
+            AccountMeta::new_readonly(a.org_config, false),
+            AccountMeta::new(a.payment_queue, false),
+            AccountMeta::new(a.queue_entry, false),
+            optional_meta(a.prev_entry, program_id),
+            optional_meta(a.next_entry, program_id),
+            AccountMeta::new(a.rent_receiver, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        args.data(),
+    )
+}
+
+// --- Legacy payment path and account cleanup ---
+
+/// Accounts shared by instructions that act on an invoice as its authority.
+#[derive(Clone, Debug, Default)]
+pub struct InvoiceAuthorityAccounts {
+    pub invoice_account: Pubkey,
+    pub authority: Pubkey,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProcessInvoicePayment;
+
+pub fn process_invoice_payment(program_id: &Pubkey, a: &InvoiceAuthorityAccounts) -> Instruction {
+    build(
+        program_id,
+        vec![
+            AccountMeta::new(a.invoice_account, false),
+            AccountMeta::new_readonly(a.authority, true),
+        ],
+        ProcessInvoicePayment.data(),
+    )
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CloseInvoice;
+
+#[derive(Clone, Debug, Default)]
+pub struct CloseInvoiceAccounts {
+    pub invoice_account: Pubkey,
+    pub document_registry: Pubkey,
+    pub number_registry: Pubkey,
+    pub authority: Pubkey,
+}
+
+pub fn close_invoice(program_id: &Pubkey, a: &CloseInvoiceAccounts) -> Instruction {
+    build(
+        program_id,
+        vec![
+            AccountMeta::new(a.invoice_account, false),
+            AccountMeta::new(a.document_registry, false),
+            AccountMeta::new(a.number_registry, false),
+            AccountMeta::new(a.authority, true),
+        ],
+        CloseInvoice.data(),
+    )
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CloseRequest;
+
+pub fn close_request(program_id: &Pubkey, invoice_request: &Pubkey, authority: &Pubkey) -> Instruction {
+    build(
+        program_id,
+        vec![
+            AccountMeta::new(*invoice_request, false),
+            AccountMeta::new(*authority, true),
+        ],
+        CloseRequest.data(),
+    )
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CloseVendor;
+
+pub fn close_vendor(program_id: &Pubkey, a: &VendorAdminAccounts) -> Instruction {
+    build(
+        program_id,
+        vec![
+            AccountMeta::new(a.vendor_account, false),
+            AccountMeta::new_readonly(a.org_config, false),
+            AccountMeta::new(a.authority, true),
+        ],
+        CloseVendor.data(),
+    )
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CloseOrg;
+
+pub fn close_org(program_id: &Pubkey, org_config: &Pubkey, authority: &Pubkey) -> Instruction {
+    build(
+        program_id,
+        vec![
+            AccountMeta::new(*org_config, false),
+            AccountMeta::new(*authority, true),
+        ],
+        CloseOrg.data(),
+    )
+}
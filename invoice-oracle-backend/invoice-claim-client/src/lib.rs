@@ -0,0 +1,51 @@
+//! Typed client for the invoice-claim program.
+//!
+//! Account layouts mirror `programs/invoice-claim/src/state.rs`; the tests in
+//! `tests/idl.rs` round-trip them against the program IDL so a field change on
+//! chain fails here instead of corrupting the backend. Instruction builders in
+//! `instructions` are checked against the same IDL.
+
+pub mod accounts;
+pub mod instructions;
+pub mod pda;
+
+pub use accounts::*;
+pub use solana_program::pubkey::Pubkey;
+
+use solana_program::pubkey;
+
+/// Deployed invoice-claim program (`declare_id!` in the program's lib.rs).
+pub const ID: Pubkey = pubkey!("HQ5y6ZMwNHSrRvma4bDHtay4UDW5qBM63A5mvyGi4MkH");
+
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+// MagicBlock ephemeral rollups
+pub const DELEGATION_PROGRAM_ID: Pubkey = pubkey!("DELeGGvXpWV2fqJUhqcF5ZSYMS4JTLjteaAMARRSaeSh");
+pub const MAGIC_PROGRAM_ID: Pubkey = pubkey!("Magic11111111111111111111111111111111111111");
+pub const MAGIC_CONTEXT_ID: Pubkey = pubkey!("MagicContext1111111111111111111111111111111");
+
+// MagicBlock VRF
+pub const VRF_PROGRAM_ID: Pubkey = pubkey!("Vrf1RNUjXmQGjmQrQLvJHs9SNkvDJEsRVFPkfSQUwGz");
+pub const VRF_PROGRAM_IDENTITY: Pubkey = pubkey!("9irBy75QS2BN81FUgXuHcjqceJJRuc9oDkAe8TKVvvAw");
+pub const DEFAULT_VRF_QUEUE: Pubkey = pubkey!("Cuj97ggrhhidhbu39TijNVqE74xvKJ69gDervRUXAxGh");
+
+// Escrow still waiting on VRF/audit this long after due_date can be refunded
+// (`ESCROW_EXPIRY_SECS` in the program's state.rs)
+pub const ESCROW_EXPIRY_SECS: i64 = 30 * 86_400;
+
+// Longest `InvoiceAccount::invoice_number` the program accepts, in bytes
+// (`MAX_INVOICE_NUMBER_LEN` in the program's state.rs)
+pub const MAX_INVOICE_NUMBER_LEN: usize = 32;
+
+// `InvoiceError::AlreadyQueued`; Anchor numbers custom errors from 6000
+pub const ERROR_ALREADY_QUEUED: u32 = 6018;
+
+use sha2::{Digest, Sha256};
+
+/// First 8 bytes of `sha256(preimage)`, as Anchor derives discriminators
+/// (`"account:<Name>"`, `"global:<ix_name>"`, `"event:<Name>"`).
+pub fn discriminator(preimage: &str) -> [u8; 8] {
+    let mut h = Sha256::new();
+    h.update(preimage.as_bytes());
+    h.finalize()[..8].try_into().unwrap()
+}
@@ -0,0 +1,95 @@
+use sha2::{Digest, Sha256};
+use solana_program::pubkey::Pubkey;
+
+use crate::DELEGATION_PROGRAM_ID;
+
+// Seeds match the `#[account(seeds = ...)]` constraints in the program.
+
+// Keyed by the org's creator, which stays fixed when the authority is transferred
+pub fn org_config(program_id: &Pubkey, creator: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"org_config", creator.as_ref()], program_id).0
+}
+
+pub fn vendor_account(program_id: &Pubkey, org_config: &Pubkey, vendor_name: &str) -> Pubkey {
+    Pubkey::find_program_address(&[b"vendor", org_config.as_ref(), vendor_name.as_bytes()], program_id).0
+}
+
+pub fn invoice_request(program_id: &Pubkey, authority: &Pubkey, nonce: u64) -> Pubkey {
+    Pubkey::find_program_address(&[b"request", authority.as_ref(), &nonce.to_le_bytes()], program_id).0
+}
+
+pub fn invoice_account(program_id: &Pubkey, authority: &Pubkey, nonce: u64) -> Pubkey {
+    Pubkey::find_program_address(&[b"invoice", authority.as_ref(), &nonce.to_le_bytes()], program_id).0
+}
+
+/// Registry key for a document: `sha256(ipfs_hash)`.
+pub fn document_key(ipfs_hash: &str) -> [u8; 32] {
+    Sha256::digest(ipfs_hash.as_bytes()).into()
+}
+
+/// Invoice number as the program compares it: ASCII letters and digits only,
+/// upper-cased, so `INV-0042` and `inv 0042` are the same number.
+pub fn normalize_invoice_number(invoice_number: &str) -> String {
+    invoice_number.chars().filter(char::is_ascii_alphanumeric).map(|c| c.to_ascii_uppercase()).collect()
+}
+
+/// Registry key for a vendor invoice number, falling back to the document key
+/// when the invoice has no number.
+pub fn invoice_number_key(invoice_number: &str, ipfs_hash: &str) -> [u8; 32] {
+    let normalized = normalize_invoice_number(invoice_number);
+    if normalized.is_empty() {
+        return document_key(ipfs_hash);
+    }
+    Sha256::digest(normalized.as_bytes()).into()
+}
+
+pub fn document_registry(program_id: &Pubkey, org_config: &Pubkey, ipfs_hash: &str) -> Pubkey {
+    Pubkey::find_program_address(&[b"invoice_doc", org_config.as_ref(), &document_key(ipfs_hash)], program_id).0
+}
+
+pub fn number_registry(program_id: &Pubkey, vendor_account: &Pubkey, invoice_number: &str, ipfs_hash: &str) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"invoice_number", vendor_account.as_ref(), &invoice_number_key(invoice_number, ipfs_hash)],
+        program_id,
+    )
+    .0
+}
+
+pub fn escrow_authority(program_id: &Pubkey, invoice_account: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"escrow_auth", invoice_account.as_ref()], program_id).0
+}
+
+pub fn audit_ballot(program_id: &Pubkey, invoice_account: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"audit_ballot", invoice_account.as_ref()], program_id).0
+}
+
+pub fn payment_queue(program_id: &Pubkey, org_config: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"payment_queue", org_config.as_ref()], program_id).0
+}
+
+pub fn queue_entry(program_id: &Pubkey, payment_queue: &Pubkey, invoice_account: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"queue_entry", payment_queue.as_ref(), invoice_account.as_ref()],
+        program_id,
+    )
+    .0
+}
+
+/// Program identity that signs VRF requests.
+pub fn program_identity(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"identity"], program_id).0
+}
+
+/// Ephemeral rollup delegation accounts for `invoice_request`:
+/// (buffer, delegation record, delegation metadata).
+pub fn delegation_accounts(program_id: &Pubkey, invoice_request: &Pubkey) -> (Pubkey, Pubkey, Pubkey) {
+    let buffer = Pubkey::find_program_address(&[b"buffer", invoice_request.as_ref()], program_id).0;
+    let record =
+        Pubkey::find_program_address(&[b"delegation", invoice_request.as_ref()], &DELEGATION_PROGRAM_ID).0;
+    let metadata = Pubkey::find_program_address(
+        &[b"delegation-metadata", invoice_request.as_ref()],
+        &DELEGATION_PROGRAM_ID,
+    )
+    .0;
+    (buffer, record, metadata)
+}
@@ -0,0 +1,73 @@
+// IDL helpers shared by the integration tests. The IDL is the copy the dapp
+// ships, regenerated from the program on every build.
+
+use serde_json::Value;
+
+const IDL: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/../../invoice-dapp/src/invoice_claim.json"));
+
+pub fn idl() -> Value {
+    serde_json::from_str(IDL).expect("IDL is valid JSON")
+}
+
+pub fn idl_type<'a>(idl: &'a Value, name: &str) -> &'a Value {
+    idl["types"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|t| t["name"] == name)
+        .unwrap_or_else(|| panic!("type {} missing from IDL", name))
+}
+
+// Append a sample value of IDL type `ty`, varying with `seed` so fields differ
+pub fn encode(idl: &Value, ty: &Value, seed: &mut u8, out: &mut Vec<u8>) {
+    *seed = seed.wrapping_add(1);
+    match ty {
+        Value::String(s) => match s.as_str() {
+            "pubkey" => out.extend_from_slice(&[*seed; 32]),
+            "u8" => out.push(*seed),
+            "bool" => out.push(*seed % 2),
+            "u16" => out.extend_from_slice(&(*seed as u16).to_le_bytes()),
+            "u32" => out.extend_from_slice(&(*seed as u32).to_le_bytes()),
+            "u64" => out.extend_from_slice(&(*seed as u64).to_le_bytes()),
+            "i64" => out.extend_from_slice(&(*seed as i64).to_le_bytes()),
+            "string" => {
+                let s = format!("field-{}", seed);
+                out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+                out.extend_from_slice(s.as_bytes());
+            }
+            other => panic!("unhandled IDL type {}", other),
+        },
+        Value::Object(o) if o.contains_key("array") => {
+            let (inner, len) = (&o["array"][0], o["array"][1].as_u64().unwrap());
+            for _ in 0..len {
+                encode(idl, inner, seed, out);
+            }
+        }
+        Value::Object(o) if o.contains_key("option") => {
+            out.push(1);
+            encode(idl, &o["option"], seed, out);
+        }
+        Value::Object(o) if o.contains_key("vec") => {
+            out.extend_from_slice(&2u32.to_le_bytes());
+            encode(idl, &o["vec"], seed, out);
+            encode(idl, &o["vec"], seed, out);
+        }
+        Value::Object(o) if o.contains_key("defined") => {
+            let def = idl_type(idl, o["defined"]["name"].as_str().unwrap());
+            match def["type"]["kind"].as_str().unwrap() {
+                "struct" => {
+                    for f in def["type"]["fields"].as_array().unwrap() {
+                        encode(idl, &f["type"], seed, out);
+                    }
+                }
+                "enum" => {
+                    // Last variant, so a shorter Rust enum fails to decode
+                    let n = def["type"]["variants"].as_array().unwrap().len();
+                    out.push((n - 1) as u8);
+                }
+                k => panic!("unhandled type kind {}", k),
+            }
+        }
+        other => panic!("unhandled IDL type {}", other),
+    }
+}
@@ -0,0 +1,120 @@
+// Checks the hand-written layouts against the program IDL. Sample account data
+// is encoded field by field from the IDL, decoded with the typed struct and
+// re-encoded; any added, removed, reordered or retyped field breaks the round trip.
+
+use borsh::BorshDeserialize;
+use invoice_claim_client::*;
+use std::fmt::Debug;
+
+mod common;
+use common::{encode, idl, idl_type};
+
+fn check_account<T: ProgramAccount + Debug>() {
+    let idl = idl();
+    let account = idl["accounts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|a| a["name"] == T::NAME)
+        .unwrap_or_else(|| panic!("account {} missing from IDL", T::NAME));
+    let idl_disc: Vec<u8> = serde_json::from_value(account["discriminator"].clone()).unwrap();
+    assert_eq!(idl_disc, T::discriminator(), "{} discriminator", T::NAME);
+
+    let mut data = T::discriminator().to_vec();
+    let mut seed = 0;
+    encode(&idl, &serde_json::json!({ "defined": { "name": T::NAME } }), &mut seed, &mut data);
+
+    let decoded = T::try_from_account_data(&data)
+        .unwrap_or_else(|e| panic!("{} failed to decode IDL layout: {}", T::NAME, e));
+    assert_eq!(decoded.to_account_data(), data, "{} layout differs from IDL: {:?}", T::NAME, decoded);
+
+    // Accounts are allocated with InitSpace padding past the last field
+    let mut padded = data.clone();
+    padded.extend_from_slice(&[0; 64]);
+    assert!(T::try_from_account_data(&padded).is_ok(), "{} rejects padded data", T::NAME);
+}
+
+fn check_enum<T: BorshDeserialize + Debug>(name: &str) {
+    let idl = idl();
+    let variants = idl_type(&idl, name)["type"]["variants"].as_array().unwrap().clone();
+    for (i, v) in variants.iter().enumerate() {
+        let decoded = T::try_from_slice(&[i as u8])
+            .unwrap_or_else(|e| panic!("{} variant {} missing: {}", name, i, e));
+        assert_eq!(format!("{:?}", decoded), v["name"].as_str().unwrap(), "{} variant {}", name, i);
+    }
+    assert!(
+        T::try_from_slice(&[variants.len() as u8]).is_err(),
+        "{} has variants the IDL does not",
+        name
+    );
+}
+
+#[test]
+fn org_config_matches_idl() {
+    check_account::<OrgConfig>();
+}
+
+#[test]
+fn vendor_account_matches_idl() {
+    check_account::<VendorAccount>();
+}
+
+#[test]
+fn invoice_request_matches_idl() {
+    check_account::<InvoiceRequest>();
+}
+
+#[test]
+fn invoice_account_matches_idl() {
+    check_account::<InvoiceAccount>();
+}
+
+#[test]
+fn invoice_registry_matches_idl() {
+    check_account::<InvoiceRegistry>();
+}
+
+#[test]
+fn payment_queue_matches_idl() {
+    check_account::<PaymentQueue>();
+}
+
+#[test]
+fn queue_entry_matches_idl() {
+    check_account::<QueueEntry>();
+}
+
+#[test]
+fn audit_ballot_matches_idl() {
+    check_account::<AuditBallot>();
+}
+
+#[test]
+fn status_enums_match_idl() {
+    check_enum::<RequestStatus>("RequestStatus");
+    check_enum::<InvoiceStatus>("InvoiceStatus");
+}
+
+#[test]
+fn error_codes_match_idl() {
+    let idl = idl();
+    let code = |name: &str| {
+        idl["errors"].as_array().unwrap().iter().find(|e| e["name"] == name).unwrap()["code"].as_u64().unwrap()
+    };
+    assert_eq!(code("AlreadyQueued"), ERROR_ALREADY_QUEUED as u64);
+}
+
+#[test]
+fn rejects_other_account_types() {
+    let queue = PaymentQueue {
+        org: Pubkey::new_unique(),
+        head: None,
+        tail: None,
+        count: 0,
+        last_updated: 0,
+        bump: 255,
+    };
+    let err = QueueEntry::try_from_account_data(&queue.to_account_data()).unwrap_err();
+    assert!(matches!(err, DecodeError::WrongDiscriminator { account: "QueueEntry", .. }));
+    assert!(matches!(OrgConfig::try_from_account_data(&[0; 4]), Err(DecodeError::TooShort(4))));
+}
@@ -0,0 +1,153 @@
+// Checks every instruction builder against the program IDL: discriminator,
+// Borsh-encoded arguments, and account order, signer/writable flags and fixed
+// addresses.
+
+use borsh::BorshDeserialize;
+use invoice_claim_client::instructions::*;
+use invoice_claim_client::{Pubkey, ID};
+use serde_json::Value;
+use solana_program::instruction::Instruction;
+use std::fmt::Debug;
+use std::str::FromStr;
+
+mod common;
+use common::{encode, idl};
+
+fn idl_instruction<'a>(idl: &'a Value, name: &str) -> &'a Value {
+    idl["instructions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|i| i["name"] == name)
+        .unwrap_or_else(|| panic!("instruction {} missing from IDL", name))
+}
+
+// Encode sample arguments from the IDL, decode them with `D` and build the
+// instruction; the data must be exactly discriminator + those bytes.
+fn check<D>(checked: &mut Vec<String>, build: impl Fn(&D) -> Instruction)
+where
+    D: InstructionData + BorshDeserialize + Debug,
+{
+    let idl = idl();
+    let ix_idl = idl_instruction(&idl, D::NAME);
+
+    let idl_disc: Vec<u8> = serde_json::from_value(ix_idl["discriminator"].clone()).unwrap();
+    assert_eq!(idl_disc, D::discriminator(), "{} discriminator", D::NAME);
+
+    let mut args = Vec::new();
+    let mut seed = 0;
+    for arg in ix_idl["args"].as_array().unwrap() {
+        encode(&idl, &arg["type"], &mut seed, &mut args);
+    }
+    let decoded = D::try_from_slice(&args)
+        .unwrap_or_else(|e| panic!("{} args differ from IDL: {}", D::NAME, e));
+
+    let ix = build(&decoded);
+    assert_eq!(ix.program_id, ID);
+    let disc_len = idl_disc.len();
+    assert_eq!(ix.data[..disc_len], idl_disc[..], "{} data discriminator", D::NAME);
+    assert_eq!(ix.data[disc_len..], args[..], "{} args layout", D::NAME);
+
+    let accounts = ix_idl["accounts"].as_array().unwrap();
+    assert_eq!(ix.accounts.len(), accounts.len(), "{} account count", D::NAME);
+    for (meta, acc) in ix.accounts.iter().zip(accounts) {
+        let name = acc["name"].as_str().unwrap();
+        let flag = |k: &str| acc[k].as_bool().unwrap_or(false);
+
+        if flag("optional") && meta.pubkey == ID {
+            // Omitted optional account
+            assert!(!meta.is_signer && !meta.is_writable, "{}.{} placeholder", D::NAME, name);
+            continue;
+        }
+        assert_eq!(meta.is_signer, flag("signer"), "{}.{} signer", D::NAME, name);
+        assert_eq!(meta.is_writable, flag("writable"), "{}.{} writable", D::NAME, name);
+
+        if let Some(address) = acc["address"].as_str() {
+            assert_eq!(meta.pubkey, Pubkey::from_str(address).unwrap(), "{}.{} address", D::NAME, name);
+        }
+        // PDAs with constant-only seeds can be checked without account data
+        if let Some(seeds) = acc["pda"]["seeds"].as_array() {
+            if acc["pda"].get("program").is_none() && seeds.iter().all(|s| s["kind"] == "const") {
+                let seeds: Vec<Vec<u8>> = seeds
+                    .iter()
+                    .map(|s| serde_json::from_value(s["value"].clone()).unwrap())
+                    .collect();
+                let refs: Vec<&[u8]> = seeds.iter().map(|s| s.as_slice()).collect();
+                let expected = Pubkey::find_program_address(&refs, &ID).0;
+                assert_eq!(meta.pubkey, expected, "{}.{} pda", D::NAME, name);
+            }
+        }
+    }
+
+    checked.push(D::NAME.to_string());
+}
+
+#[test]
+fn every_instruction_matches_idl() {
+    let pid = &ID;
+    let mut checked = Vec::new();
+    let c = &mut checked;
+
+    check(c, |d: &RequestInvoiceExtraction| request_invoice_extraction(pid, &Default::default(), d));
+    check(c, |_: &DelegateInvoiceExtraction| delegate_invoice_extraction(pid, &Default::default(), None));
+    check(c, |d: &CommitInvoiceExtraction| commit_invoice_extraction(pid, &Default::default(), d));
+    check(c, |d: &ProcessExtractionResult| process_extraction_result(pid, &Default::default(), d));
+    check(c, |d: &DebugRequestPda| debug_request_pda(pid, d));
+    check(c, |d: &RequestInvoiceAuditVrf| request_invoice_audit_vrf(pid, &Default::default(), d));
+    check(c, |d: &CallbackInvoiceVrf| callback_invoice_vrf(pid, &Default::default(), d));
+    check(c, |d: &AuditDecide| audit_decide(pid, &Default::default(), d));
+    check(c, |d: &ReviewInvoiceAmount| review_invoice_amount(pid, &Default::default(), d));
+    check(c, |d: &OrgInit| org_init(pid, &Default::default(), d));
+    check(c, |d: &UpdateOrgConfig| update_org_config(pid, &Default::default(), d));
+    check(c, |d: &ProposeOrgAuthority| propose_org_authority(pid, &Default::default(), d));
+    check(c, |_: &AcceptOrgAuthority| accept_org_authority(pid, &Default::default()));
+    check(c, |_: &GetRemainingDailyBudget| get_remaining_daily_budget(pid, &Pubkey::new_unique()));
+    check(c, |d: &RegisterVendor| register_vendor(pid, &Default::default(), d));
+    check(c, |_: &DeactivateVendor| deactivate_vendor(pid, &Default::default()));
+    check(c, |_: &ActivateVendor| activate_vendor(pid, &Default::default()));
+    check(c, |d: &UpdateVendorWallet| update_vendor_wallet(pid, &Default::default(), d));
+    check(c, |_: &FundEscrow| fund_escrow(pid, &Default::default()));
+    check(c, |_: &RefundEscrow| refund_escrow(pid, &Default::default()));
+    check(c, |_: &InitPaymentQueue| init_payment_queue(pid, &Default::default()));
+    check(c, |d: &RemoveFromPaymentQueue| remove_from_payment_queue(pid, &Default::default(), d));
+    check(c, |_: &ProcessInvoicePayment| process_invoice_payment(pid, &Default::default()));
+    check(c, |_: &CloseInvoice| close_invoice(pid, &Default::default()));
+    check(c, |_: &CloseRequest| close_request(pid, &Pubkey::new_unique(), &Pubkey::new_unique()));
+    check(c, |_: &CloseVendor| close_vendor(pid, &Default::default()));
+    check(c, |_: &CloseOrg| close_org(pid, &Pubkey::new_unique(), &Pubkey::new_unique()));
+
+    // Exercise both the present and omitted forms of the optional queue neighbours
+    check(c, |_: &SettleToVendor| {
+        settle_to_vendor(pid, &SettleToVendorAccounts { prev_entry: Some(Pubkey::new_unique()), ..Default::default() })
+    });
+    check(c, |_: &AddToPaymentQueue| {
+        add_to_payment_queue(pid, &AddToPaymentQueueAccounts { next_entry: Some(Pubkey::new_unique()), ..Default::default() })
+    });
+
+    let idl = idl();
+    let mut expected: Vec<String> = idl["instructions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|i| i["name"].as_str().unwrap().to_string())
+        .collect();
+    expected.sort();
+    checked.sort();
+    assert_eq!(checked, expected, "instructions without a checked builder");
+}
+
+#[test]
+fn delegation_accounts_match_idl_seeds() {
+    let request = Pubkey::new_unique();
+    let ix = delegate_invoice_extraction(
+        &ID,
+        &DelegateInvoiceExtractionAccounts { invoice_request: request, authority: Pubkey::new_unique() },
+        Some(Pubkey::new_unique()),
+    );
+    let (buffer, record, metadata) = invoice_claim_client::pda::delegation_accounts(&ID, &request);
+    assert_eq!(ix.accounts[0].pubkey, buffer);
+    assert_eq!(ix.accounts[1].pubkey, record);
+    assert_eq!(ix.accounts[2].pubkey, metadata);
+    // Validator rides in remaining_accounts
+    assert_eq!(ix.accounts.len(), 9);
+}
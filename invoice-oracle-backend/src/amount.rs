@@ -0,0 +1,361 @@
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use spl_token_2022::extension::StateWithExtensions;
+use spl_token_2022::state::Mint;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::extraction::CURRENCY_CODES;
+
+const CURRENCY_SYMBOLS: &[char] = &['$', '€', '£', '¥'];
+// USDC on mainnet and devnet
+const USDC_MINTS: &[&str] = &["EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v", "4zMMC9srt5Ri5X14GAgXhaHii3GnPAEERYPJgZJDncDU"];
+// Grouping characters seen in printed amounts besides ',' and '.'
+const GROUP_SEPARATORS: &[char] = &['\'', ' ', '\u{a0}', '\u{202f}'];
+
+/// An exact decimal amount as printed on an invoice: `digits / 10^scale`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Amount {
+    digits: u128,
+    scale: u32,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AmountError {
+    Empty,
+    Invalid(String),
+    Negative(String),
+    /// `1,250` / `1.250`: grouped thousands or three decimal places
+    Ambiguous(String),
+    /// More fractional digits than the mint can represent
+    PrecisionLoss { amount: String, decimals: u8 },
+    Overflow(String),
+}
+
+impl fmt::Display for AmountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AmountError::Empty => write!(f, "empty amount"),
+            AmountError::Invalid(s) => write!(f, "invalid amount '{}'", s),
+            AmountError::Negative(s) => write!(f, "negative amount '{}'", s),
+            AmountError::Ambiguous(s) => {
+                write!(f, "amount '{}' could be grouped thousands or have three decimal places", s)
+            }
+            AmountError::PrecisionLoss { amount, decimals } => {
+                write!(f, "amount {} has more than {} decimal places", amount, decimals)
+            }
+            AmountError::Overflow(s) => write!(f, "amount '{}' is too large", s),
+        }
+    }
+}
+
+impl std::error::Error for AmountError {}
+
+impl FromStr for Amount {
+    type Err = AmountError;
+
+    /// Accepts `1234.56`, `1,234.56`, `1.234,56`, `1'234.56`, `1 234,56` with
+    /// an optional currency symbol or code on either side (`$12`, `12 €`,
+    /// `USD 12`, `12.00 EUR`). `1,250` on its own is ambiguous; see
+    /// [`parse_base_units`].
+    fn from_str(raw: &str) -> Result<Self, AmountError> {
+        Amount::parse(raw, None, None)
+    }
+}
+
+impl Amount {
+    fn parse(raw: &str, decimal_mark: Option<char>, decimals: Option<u8>) -> Result<Self, AmountError> {
+        let invalid = || AmountError::Invalid(raw.to_string());
+
+        let mut s = raw.trim();
+        s = s.trim_start_matches(CURRENCY_SYMBOLS).trim_end_matches(CURRENCY_SYMBOLS).trim();
+        for code in CURRENCY_CODES {
+            s = s.strip_prefix(code).or_else(|| s.strip_suffix(code)).unwrap_or(s).trim();
+        }
+        s = s.trim_start_matches(CURRENCY_SYMBOLS).trim_end_matches(CURRENCY_SYMBOLS).trim();
+
+        if s.is_empty() {
+            return Err(AmountError::Empty);
+        }
+        if s.starts_with('-') || (s.starts_with('(') && s.ends_with(')')) {
+            return Err(AmountError::Negative(raw.to_string()));
+        }
+        if !s.chars().all(|c| c.is_ascii_digit() || c == '.' || c == ',' || GROUP_SEPARATORS.contains(&c)) {
+            return Err(invalid());
+        }
+
+        let decimal_sep = match decimal_separator(s).ok_or_else(invalid)? {
+            Separator::Decimal(i) => Some(i),
+            Separator::None | Separator::Grouping(_) => None,
+            Separator::Ambiguous(i) => {
+                let three_places = match (decimal_mark, decimals) {
+                    (Some(mark), _) => s[i..].starts_with(mark),
+                    // Three decimal places would not fit the mint anyway
+                    (None, Some(decimals)) if decimals < 3 => false,
+                    _ => return Err(AmountError::Ambiguous(raw.to_string())),
+                };
+                three_places.then_some(i)
+            }
+        };
+        let (int_part, frac_part) = match decimal_sep {
+            Some(i) => (&s[..i], &s[i + 1..]),
+            None => (s, ""),
+        };
+        if (frac_part.is_empty() && decimal_sep.is_some()) || !frac_part.chars().all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        // Keeps 10^scale within u128
+        if frac_part.len() > 38 {
+            return Err(AmountError::Overflow(raw.to_string()));
+        }
+
+        let int_digits = ungroup(int_part).ok_or_else(invalid)?;
+        let mut digits: u128 = 0;
+        for c in int_digits.chars().chain(frac_part.chars()) {
+            digits = digits
+                .checked_mul(10)
+                .and_then(|d| d.checked_add(c.to_digit(10).unwrap() as u128))
+                .ok_or_else(|| AmountError::Overflow(raw.to_string()))?;
+        }
+        Ok(Amount { digits, scale: frac_part.len() as u32 })
+    }
+}
+
+enum Separator {
+    /// A whole number
+    None,
+    /// Whole number grouped with a repeated `,` or `.`
+    Grouping(char),
+    /// Byte index of the decimal separator
+    Decimal(usize),
+    /// A lone `,` or `.` followed by exactly three digits
+    Ambiguous(usize),
+}
+
+// None when the separators are inconsistent.
+fn decimal_separator(s: &str) -> Option<Separator> {
+    let dots = s.matches('.').count();
+    let commas = s.matches(',').count();
+    match (dots, commas) {
+        (0, 0) => Some(Separator::None),
+        // Both present: the last one is the decimal point and must be unique
+        (_, _) if dots > 0 && commas > 0 => {
+            let (d, c) = (s.rfind('.').unwrap(), s.rfind(',').unwrap());
+            let (i, count) = if d > c { (d, dots) } else { (c, commas) };
+            (count == 1).then_some(Separator::Decimal(i))
+        }
+        // Repeated separator: thousands grouping
+        (n, 0) | (0, n) if n > 1 => Some(Separator::Grouping(if dots > 0 { '.' } else { ',' })),
+        _ => {
+            let i = s.find(['.', ',']).unwrap();
+            let int_part = &s[..i];
+            let frac_len = s.len() - i - 1;
+            // "1,234" / "1.234" may be grouped thousands; "0.125" and "1 234.567" are not
+            let groupable = frac_len == 3
+                && (1..=3).contains(&int_part.len())
+                && int_part.chars().all(|c| c.is_ascii_digit())
+                && !int_part.starts_with('0');
+            Some(if groupable { Separator::Ambiguous(i) } else { Separator::Decimal(i) })
+        }
+    }
+}
+
+// Strip thousands separators from the integer part, checking the groups are
+// 1-3 digits followed by groups of exactly 3.
+fn ungroup(int_part: &str) -> Option<String> {
+    let groups: Vec<&str> = int_part.split(|c: char| !c.is_ascii_digit()).collect();
+    if groups.len() > 1 {
+        let seps: Vec<char> = int_part.chars().filter(|c| !c.is_ascii_digit()).collect();
+        if seps.iter().any(|c| *c != seps[0]) {
+            return None;
+        }
+        if !(1..=3).contains(&groups[0].len()) || groups[1..].iter().any(|g| g.len() != 3) {
+            return None;
+        }
+    }
+    let digits: String = groups.concat();
+    if digits.is_empty() {
+        // ".50" style amounts
+        return (groups.len() == 1).then(|| "0".to_string());
+    }
+    Some(digits)
+}
+
+/// Parse a printed amount into base units of a mint with `decimals`. A lone
+/// `,` or `.` before three digits is read with `decimal_mark`, the separator
+/// the rest of the document uses for decimals; without one it is grouping
+/// only when the mint has fewer than three decimals, and ambiguous otherwise.
+pub fn parse_base_units(raw: &str, decimal_mark: Option<char>, decimals: u8) -> Result<u64, AmountError> {
+    Amount::parse(raw, decimal_mark, Some(decimals))?.to_base_units(decimals)
+}
+
+/// The decimal separator `raw` shows unambiguously, if any, e.g. `.` for
+/// `1,234.56`, `12.00` or `1,234,567`.
+pub fn decimal_mark(raw: &str) -> Option<char> {
+    let s: String = raw
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '.' || *c == ',' || GROUP_SEPARATORS.contains(c))
+        .collect();
+    let s = s.trim();
+    match decimal_separator(s)? {
+        Separator::Decimal(i) => s[i..].chars().next(),
+        Separator::Grouping(c) => Some(if c == '.' { ',' } else { '.' }),
+        Separator::None | Separator::Ambiguous(_) => None,
+    }
+}
+
+/// Whether an invoice in `invoice` can be settled in a mint denominated in
+/// `mint`; USD invoices are paid in USD stablecoins.
+pub fn currency_matches(invoice: &str, mint: &str) -> bool {
+    let dollars = |c: &str| c.eq_ignore_ascii_case("USD") || c.eq_ignore_ascii_case("USDC");
+    invoice.eq_ignore_ascii_case(mint) || (dollars(invoice) && dollars(mint))
+}
+
+impl Amount {
+    /// Convert to token base units for a mint with `decimals`, failing rather
+    /// than rounding if the amount has more precision than the mint.
+    pub fn to_base_units(self, decimals: u8) -> Result<u64, AmountError> {
+        let decimals_u32 = decimals as u32;
+        let overflow = || AmountError::Overflow(self.to_string());
+        let units = if self.scale <= decimals_u32 {
+            10u128
+                .checked_pow(decimals_u32 - self.scale)
+                .and_then(|m| self.digits.checked_mul(m))
+                .ok_or_else(overflow)?
+        } else {
+            let divisor = 10u128.checked_pow(self.scale - decimals_u32).ok_or_else(overflow)?;
+            if !self.digits.is_multiple_of(divisor) {
+                return Err(AmountError::PrecisionLoss { amount: self.to_string(), decimals });
+            }
+            self.digits / divisor
+        };
+        u64::try_from(units).map_err(|_| overflow())
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let denom = 10u128.pow(self.scale);
+        if self.scale == 0 {
+            write!(f, "{}", self.digits)
+        } else {
+            write!(f, "{}.{:0width$}", self.digits / denom, self.digits % denom, width = self.scale as usize)
+        }
+    }
+}
+
+/// Render base units as a decimal string, e.g. 1_050_000 with 6 decimals as "1.050000".
+pub fn format_base_units(amount: u64, decimals: u8) -> String {
+    Amount { digits: amount as u128, scale: decimals as u32 }.to_string()
+}
+
+/// Currency code of a well-known stablecoin mint.
+pub fn mint_currency(mint: &Pubkey) -> Option<&'static str> {
+    USDC_MINTS.contains(&mint.to_string().as_str()).then_some("USDC")
+}
+
+/// Decimals of an SPL Token or Token-2022 mint.
+pub fn fetch_mint_decimals(rpc_client: &RpcClient, mint: &Pubkey) -> Result<u8, Box<dyn std::error::Error>> {
+    let account = rpc_client.get_account(mint)?;
+    Ok(StateWithExtensions::<Mint>::unpack(&account.data)?.base.decimals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn units(s: &str, decimals: u8) -> Result<u64, AmountError> {
+        parse_base_units(s, None, decimals)
+    }
+
+    #[test]
+    fn parses_regional_formats() {
+        for s in [
+            "1234.56", "1,234.56", "1.234,56", "1'234.56", "1 234,56", "1\u{a0}234,56", "$1,234.56", "€1.234,56",
+            "1.234,56 €", "USD 1,234.56", "1,234.56 USD", "1234,56EUR",
+        ] {
+            assert_eq!(units(s, 6), Ok(1_234_560_000), "{}", s);
+        }
+        assert_eq!(units("1,234,567", 2), Ok(123_456_700));
+        assert_eq!(units("1.234.567,8", 2), Ok(123_456_780));
+        assert_eq!(units("1,234", 0), Ok(1234));
+        assert_eq!(units("0.125", 3), Ok(125));
+        assert_eq!(units("12,5", 2), Ok(1250));
+        assert_eq!(units(".50", 2), Ok(50));
+        assert_eq!(units("950", 6), Ok(950_000_000));
+    }
+
+    #[test]
+    fn reads_three_digit_fractions_by_context() {
+        // Mints with fewer than three decimals can only mean grouping
+        assert_eq!(units("1.250", 2), Ok(125_000));
+        assert_eq!(units("1,250", 0), Ok(1250));
+        for s in ["1.250", "1,250", "$1,250", "1.250 €"] {
+            assert_eq!(units(s, 6), Err(AmountError::Ambiguous(s.to_string())), "{}", s);
+        }
+        assert_eq!(parse_base_units("1.250", Some(','), 6), Ok(1_250_000_000));
+        assert_eq!(parse_base_units("1.250", Some('.'), 6), Ok(1_250_000));
+        assert_eq!(parse_base_units("1,250", Some(','), 6), Ok(1_250_000));
+        assert_eq!(parse_base_units("1,250", Some('.'), 6), Ok(1_250_000_000));
+        // Only the lone three-digit case looks at the context
+        assert_eq!(parse_base_units("1,250.50", Some(','), 6), Ok(1_250_500_000));
+        assert_eq!(units("1 250,500", 6), Ok(1_250_500_000));
+        assert_eq!(units("1234.567", 6), Ok(1_234_567_000));
+    }
+
+    #[test]
+    fn finds_the_decimal_mark() {
+        for (s, mark) in [
+            ("1,234.56", Some('.')),
+            ("1.234,56", Some(',')),
+            ("1 234,5", Some(',')),
+            ("12.00", Some('.')),
+            ("1,234,567", Some('.')),
+            ("1.250", None),
+            ("$1,250", None),
+            ("1.250,00 €", Some(',')),
+            ("1250", None),
+            ("1.234.567,891", Some(',')),
+        ] {
+            assert_eq!(decimal_mark(s), mark, "{}", s);
+        }
+    }
+
+    #[test]
+    fn matches_currencies() {
+        assert!(currency_matches("EUR", "EUR"));
+        assert!(currency_matches("USD", "USDC"));
+        assert!(currency_matches("usdc", "USD"));
+        assert!(!currency_matches("EUR", "USDC"));
+        assert!(!currency_matches("GBP", "USD"));
+
+        let usdc = Pubkey::from_str("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v").unwrap();
+        assert_eq!(mint_currency(&usdc), Some("USDC"));
+        assert_eq!(mint_currency(&Pubkey::new_unique()), None);
+    }
+
+    #[test]
+    fn rejects_precision_loss_and_overflow() {
+        assert!(matches!(units("1.2345", 2), Err(AmountError::PrecisionLoss { decimals: 2, .. })));
+        // Trailing zeros beyond the mint's decimals are exact
+        assert_eq!(units("1.2300", 2), Ok(123));
+        assert!(matches!(units("18446744073709.551616", 6), Err(AmountError::Overflow(_))));
+        assert_eq!(units("18446744073709.551615", 6), Ok(u64::MAX));
+    }
+
+    #[test]
+    fn rejects_malformed_amounts() {
+        for s in ["", "$", "abc", "1,23,456.00", "1.234.56", "1,234.5.6", "12.", "1,234'567.00", "1a.00", "GBP"] {
+            assert!(s.parse::<Amount>().is_err(), "{}", s);
+        }
+        assert!(matches!("-5.00".parse::<Amount>(), Err(AmountError::Negative(_))));
+        assert!(matches!("($5.00)".parse::<Amount>(), Err(AmountError::Negative(_))));
+    }
+
+    #[test]
+    fn formats_base_units() {
+        assert_eq!(format_base_units(1_050_000, 6), "1.050000");
+        assert_eq!(format_base_units(42, 0), "42");
+        assert_eq!(format_base_units(5, 2), "0.05");
+    }
+}
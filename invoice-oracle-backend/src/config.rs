@@ -0,0 +1,369 @@
+//! Runtime configuration: a TOML file (`--config`, default `oracle.toml` when
+//! present) overridden by command-line flags, validated once at startup so a
+//! bad value fails before the oracle starts rather than mid-loop. See
+//! `oracle.example.toml` for the file format.
+
+use clap::{value_parser, Arg, ArgMatches, Command};
+use serde::Deserialize;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::extraction::CURRENCY_CODES;
+
+const DEFAULT_CONFIG_PATH: &str = "oracle.toml";
+const DEFAULT_CLUSTER_URL: &str = "https://api.devnet.solana.com";
+const DEFAULT_PROGRAM_ID: &str = "HQ5y6ZMwNHSrRvma4bDHtay4UDW5qBM63A5mvyGi4MkH";
+const DEFAULT_KEYPAIR: &str = "phantom-keypair.json";
+
+// Full sweep interval; catches anything the subscriptions missed and cranks due payments
+const DEFAULT_POLL_INTERVAL_SECS: u64 = 30;
+
+pub type ConfigResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+/// The file as written; every field is optional so flags and defaults can fill gaps.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    pub cluster_url: Option<String>,
+    pub ws_url: Option<String>,
+    pub commitment: Option<String>,
+    pub program_id: Option<String>,
+    pub keypair: Option<String>,
+    pub poll_interval_secs: Option<u64>,
+    #[serde(default)]
+    pub orgs: Vec<OrgFile>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OrgFile {
+    pub authority: String,
+    pub name: Option<String>,
+    pub currency: Option<String>,
+}
+
+/// An organization the oracle serves, identified by the authority that created
+/// its OrgConfig (the PDA seed, which survives authority transfers).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Org {
+    pub name: Option<String>,
+    pub authority: Pubkey,
+    pub config: Pubkey,
+    /// Currency code the org's mint is denominated in; invoices in any other
+    /// currency are not submitted.
+    pub currency: Option<String>,
+}
+
+/// Where the oracle's signing key comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeypairSource {
+    /// A Solana CLI keypair file (JSON byte array).
+    File(PathBuf),
+    /// An environment variable holding the same JSON byte array (`env:NAME`).
+    Env(String),
+}
+
+impl FromStr for KeypairSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.strip_prefix("env:") {
+            Some("") => Err("keypair 'env:' needs a variable name".to_string()),
+            Some(var) => Ok(KeypairSource::Env(var.to_string())),
+            None if s.is_empty() => Err("keypair path is empty".to_string()),
+            None => Ok(KeypairSource::File(PathBuf::from(s))),
+        }
+    }
+}
+
+impl KeypairSource {
+    pub fn load(&self) -> ConfigResult<Keypair> {
+        match self {
+            KeypairSource::File(path) => read_keypair_file(path)
+                .map_err(|e| format!("could not read keypair file {}: {}", path.display(), e).into()),
+            KeypairSource::Env(var) => {
+                let json = std::env::var(var).map_err(|_| format!("keypair variable {} is not set", var))?;
+                let bytes: Vec<u8> = serde_json::from_str(&json)
+                    .map_err(|e| format!("keypair variable {} is not a JSON byte array: {}", var, e))?;
+                Keypair::from_bytes(&bytes)
+                    .map_err(|e| format!("keypair variable {} is not a valid keypair: {}", var, e).into())
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Config {
+    pub cluster_url: String,
+    pub ws_url: String,
+    pub commitment: CommitmentConfig,
+    pub program_id: Pubkey,
+    pub keypair: KeypairSource,
+    pub poll_interval: Duration,
+    pub orgs: Vec<Org>,
+}
+
+impl Config {
+    /// Read the config file named by `--config` and apply flag overrides.
+    pub fn load(matches: &ArgMatches) -> ConfigResult<Config> {
+        let file = match matches.get_one::<String>("config") {
+            Some(path) => ConfigFile::read(Path::new(path))?,
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => ConfigFile::read(Path::new(DEFAULT_CONFIG_PATH))?,
+            None => ConfigFile::default(),
+        };
+        Config::resolve(file, matches)
+    }
+
+    /// Merge `file` with the flags in `matches` (flags win) and validate the result.
+    pub fn resolve(file: ConfigFile, matches: &ArgMatches) -> ConfigResult<Config> {
+        let flag = |name: &str| matches.get_one::<String>(name).cloned();
+
+        let cluster_url =
+            flag("url").or(file.cluster_url).unwrap_or_else(|| DEFAULT_CLUSTER_URL.to_string());
+        check_url("cluster_url", &cluster_url, &["http", "https"])?;
+
+        let ws_url = match flag("ws-url").or(file.ws_url) {
+            Some(url) => url,
+            None => websocket_url(&cluster_url)?,
+        };
+        check_url("ws_url", &ws_url, &["ws", "wss"])?;
+
+        let commitment = match flag("commitment").or(file.commitment) {
+            Some(level) => parse_commitment(&level)?,
+            None => CommitmentConfig::confirmed(),
+        };
+
+        let program_id = flag("program-id").or(file.program_id).unwrap_or_else(|| DEFAULT_PROGRAM_ID.to_string());
+        let program_id = parse_pubkey("program_id", &program_id)?;
+
+        let keypair = flag("keypair").or(file.keypair).unwrap_or_else(|| DEFAULT_KEYPAIR.to_string());
+        let keypair = KeypairSource::from_str(&keypair)?;
+
+        // RECONCILE_INTERVAL_SECS is kept for older .env setups
+        let poll_interval_secs = match matches.get_one::<u64>("poll-interval").copied().or(file.poll_interval_secs) {
+            Some(secs) => secs,
+            None => match std::env::var("RECONCILE_INTERVAL_SECS") {
+                Ok(secs) => secs
+                    .parse()
+                    .map_err(|_| format!("RECONCILE_INTERVAL_SECS {:?} is not a number of seconds", secs))?,
+                Err(_) => DEFAULT_POLL_INTERVAL_SECS,
+            },
+        };
+        if poll_interval_secs == 0 {
+            return Err("poll_interval_secs must be at least 1".into());
+        }
+
+        // --org replaces the file's list; ORG_AUTHORITY_PUBKEY is kept for older .env setups
+        let org_files = match matches.get_many::<String>("org") {
+            Some(authorities) => authorities.map(|a| OrgFile { authority: a.clone(), name: None, currency: None }).collect(),
+            None if file.orgs.is_empty() => std::env::var("ORG_AUTHORITY_PUBKEY")
+                .map(|authority| vec![OrgFile { authority, name: None, currency: None }])
+                .unwrap_or_default(),
+            None => file.orgs,
+        };
+        let orgs = resolve_orgs(&program_id, org_files)?;
+
+        Ok(Config {
+            cluster_url,
+            ws_url,
+            commitment,
+            program_id,
+            keypair,
+            poll_interval: Duration::from_secs(poll_interval_secs),
+            orgs,
+        })
+    }
+
+    /// The configured org whose OrgConfig PDA is `org_config`.
+    pub fn org_by_config(&self, org_config: &Pubkey) -> Option<&Org> {
+        self.orgs.iter().find(|org| org.config == *org_config)
+    }
+}
+
+impl ConfigFile {
+    pub fn read(path: &Path) -> ConfigResult<ConfigFile> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("could not read config file {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("invalid config file {}: {}", path.display(), e).into())
+    }
+}
+
+/// Command-line interface: oracle flags plus the `jobs` operator commands.
+pub fn cli() -> Command<'static> {
+    let value = |name: &'static str, value_name: &'static str, help: &'static str| {
+        Arg::new(name).long(name).value_name(value_name).takes_value(true).help(help)
+    };
+
+    Command::new("invoice-oracle-backend")
+        .about("Extracts submitted invoices and drives escrow, VRF and settlement")
+        .arg(
+            value("config", "PATH", "TOML config file [default: oracle.toml if present]")
+                .short('c')
+                .env("ORACLE_CONFIG"),
+        )
+        .arg(value("url", "URL", "JSON RPC URL of the cluster").short('u'))
+        .arg(value("ws-url", "URL", "Websocket URL [default: derived from --url]"))
+        .arg(value("commitment", "LEVEL", "processed, confirmed or finalized"))
+        .arg(value("program-id", "PUBKEY", "invoice_claim program id"))
+        .arg(value("keypair", "SOURCE", "Oracle keypair file, or env:VAR holding its JSON bytes").short('k'))
+        .arg(value("poll-interval", "SECS", "Seconds between reconciliation sweeps").value_parser(value_parser!(u64)))
+        .arg(
+            value("org", "AUTHORITY", "OrgConfig authority to serve; repeat for several (replaces the config's orgs)")
+                .multiple_occurrences(true),
+        )
+        .subcommand(
+            Command::new("jobs")
+                .about("Inspect the job store and requeue dead-lettered requests")
+                .subcommand(Command::new("list").about("All jobs, most recently updated first"))
+                .subcommand(Command::new("dead").about("Dead-lettered jobs"))
+                .subcommand(Command::new("show").about("One job and its transactions").arg(Arg::new("request").required(true)))
+                .subcommand(Command::new("requeue").about("Retry a job from its current stage").arg(Arg::new("request").required(true))),
+        )
+}
+
+fn resolve_orgs(program_id: &Pubkey, files: Vec<OrgFile>) -> ConfigResult<Vec<Org>> {
+    if files.is_empty() {
+        return Err("no organizations configured: add [[orgs]] to the config file or pass --org".into());
+    }
+    let mut seen = HashSet::new();
+    files
+        .into_iter()
+        .map(|file| {
+            let authority = parse_pubkey("orgs.authority", &file.authority)?;
+            if !seen.insert(authority) {
+                return Err(format!("org {} is configured twice", authority).into());
+            }
+            let config = invoice_claim_client::pda::org_config(program_id, &authority);
+            let currency = match file.currency {
+                Some(code) if CURRENCY_CODES.contains(&code.to_ascii_uppercase().as_str()) => {
+                    Some(code.to_ascii_uppercase())
+                }
+                Some(code) => {
+                    return Err(format!("orgs.currency {:?} is not one of {}", code, CURRENCY_CODES.join(", ")).into())
+                }
+                None => None,
+            };
+            Ok(Org { name: file.name, authority, config, currency })
+        })
+        .collect()
+}
+
+fn parse_pubkey(field: &str, value: &str) -> ConfigResult<Pubkey> {
+    Pubkey::from_str(value).map_err(|_| format!("{} {:?} is not a valid public key", field, value).into())
+}
+
+fn parse_commitment(level: &str) -> ConfigResult<CommitmentConfig> {
+    match level {
+        "processed" => Ok(CommitmentConfig::processed()),
+        "confirmed" => Ok(CommitmentConfig::confirmed()),
+        "finalized" => Ok(CommitmentConfig::finalized()),
+        other => Err(format!("commitment {:?} must be processed, confirmed or finalized", other).into()),
+    }
+}
+
+fn check_url(field: &str, url: &str, schemes: &[&str]) -> ConfigResult<()> {
+    match url.split_once("://") {
+        Some((scheme, rest)) if schemes.contains(&scheme) && !rest.is_empty() => Ok(()),
+        _ => Err(format!("{} {:?} must be a {} URL", field, url, schemes.join("/")).into()),
+    }
+}
+
+// The websocket endpoint for an RPC URL, following the Solana CLI: same host
+// with ws(s), and the next port up when one is given (8899 -> 8900 on localnet)
+fn websocket_url(cluster_url: &str) -> ConfigResult<String> {
+    let (scheme, rest) = match cluster_url.split_once("://") {
+        Some(("https", rest)) => ("wss", rest),
+        Some(("http", rest)) => ("ws", rest),
+        _ => return Err(format!("cluster_url {:?} must be an http/https URL", cluster_url).into()),
+    };
+    let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let authority = match authority.rsplit_once(':').map(|(host, port)| (host, port.parse::<u16>())) {
+        Some((host, Ok(port))) => format!("{}:{}", host, port.saturating_add(1)),
+        _ => authority.to_string(),
+    };
+    Ok(format!("{}://{}{}", scheme, authority, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORG: &str = "BytFyQcJjBVSH6gARHCixGFa4wca1K3zERKGf3ZGCQVt";
+
+    fn resolve(toml: &str, args: &[&str]) -> ConfigResult<Config> {
+        let matches = cli().try_get_matches_from(std::iter::once("oracle").chain(args.iter().copied()))?;
+        Config::resolve(toml::from_str(toml)?, &matches)
+    }
+
+    #[test]
+    fn reads_the_file_and_lets_flags_override_it() {
+        let toml = format!(
+            r#"
+            cluster_url = "http://127.0.0.1:8899"
+            commitment = "finalized"
+            keypair = "env:ORACLE_KEYPAIR"
+            poll_interval_secs = 5
+
+            [[orgs]]
+            name = "Acme"
+            authority = "{ORG}"
+            currency = "eur"
+            "#
+        );
+
+        let config = resolve(&toml, &[]).unwrap();
+        assert_eq!(config.ws_url, "ws://127.0.0.1:8900");
+        assert_eq!(config.commitment, CommitmentConfig::finalized());
+        assert_eq!(config.keypair, KeypairSource::Env("ORACLE_KEYPAIR".to_string()));
+        assert_eq!(config.poll_interval, Duration::from_secs(5));
+        assert_eq!(config.program_id.to_string(), DEFAULT_PROGRAM_ID);
+        assert_eq!(config.orgs[0].name.as_deref(), Some("Acme"));
+        assert_eq!(config.orgs[0].currency.as_deref(), Some("EUR"));
+        let org_config = invoice_claim_client::pda::org_config(&config.program_id, &Pubkey::from_str(ORG).unwrap());
+        assert_eq!(config.org_by_config(&org_config).map(|org| org.config), Some(org_config));
+
+        let other = Pubkey::new_unique().to_string();
+        let config =
+            resolve(&toml, &["--url", "https://api.mainnet-beta.solana.com", "--org", &other, "--poll-interval", "60"])
+                .unwrap();
+        assert_eq!(config.ws_url, "wss://api.mainnet-beta.solana.com");
+        assert_eq!(config.poll_interval, Duration::from_secs(60));
+        assert_eq!(config.orgs.len(), 1);
+        assert_eq!(config.orgs[0].authority.to_string(), other);
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        let org = format!("[[orgs]]\nauthority = \"{ORG}\"\n");
+        let err = |toml: &str| resolve(toml, &[]).unwrap_err().to_string();
+
+        assert!(err(&format!("commitment = \"max\"\n{org}")).contains("commitment"));
+        assert!(err(&format!("cluster_url = \"api.devnet.solana.com\"\n{org}")).contains("cluster_url"));
+        assert!(err(&format!("ws_url = \"https://x\"\n{org}")).contains("ws_url"));
+        assert!(err(&format!("program_id = \"nope\"\n{org}")).contains("program_id"));
+        assert!(err(&format!("poll_interval_secs = 0\n{org}")).contains("poll_interval_secs"));
+        assert!(err(&format!("{org}{org}")).contains("configured twice"));
+        assert!(err(&format!("{org}currency = \"DOGE\"\n")).contains("orgs.currency"));
+        assert!(resolve(&format!("rpc_url = \"x\"\n{org}"), &[]).is_err());
+    }
+
+    #[test]
+    fn derives_websocket_urls() {
+        assert_eq!(websocket_url("https://api.devnet.solana.com").unwrap(), "wss://api.devnet.solana.com");
+        assert_eq!(websocket_url("http://localhost:8899").unwrap(), "ws://localhost:8900");
+        assert_eq!(websocket_url("https://rpc.example.com/key/abc").unwrap(), "wss://rpc.example.com/key/abc");
+        assert!(websocket_url("ftp://example.com").is_err());
+    }
+
+    #[test]
+    fn parses_keypair_sources() {
+        assert_eq!("id.json".parse(), Ok(KeypairSource::File(PathBuf::from("id.json"))));
+        assert_eq!("env:KEY".parse(), Ok(KeypairSource::Env("KEY".to_string())));
+        assert!("env:".parse::<KeypairSource>().is_err());
+    }
+}
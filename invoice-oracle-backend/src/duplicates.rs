@@ -0,0 +1,167 @@
+//! Duplicate checks run before an extraction result is submitted. The program
+//! rejects exact repeats itself through its document and invoice-number
+//! registries; checking them here first gives a clearer message than a failed
+//! transaction, and the near-duplicate pass catches re-submissions the
+//! registries can't, such as a re-scan whose invoice number was misread.
+
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::fmt;
+
+use invoice_claim_client::{pda, InvoiceAccount, InvoiceRegistry, ProgramAccount};
+
+// Same vendor and amount with due dates this close is treated as a likely repeat
+const NEAR_DUE_DATE_WINDOW_SECS: i64 = 7 * 86_400;
+
+/// An invoice already holding the document or invoice number being submitted.
+#[derive(Debug, PartialEq)]
+pub enum Duplicate {
+    Document { invoice: Pubkey },
+    InvoiceNumber { invoice: Pubkey },
+}
+
+impl fmt::Display for Duplicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Duplicate::Document { invoice } => write!(f, "document already processed as invoice {}", invoice),
+            Duplicate::InvoiceNumber { invoice } => {
+                write!(f, "vendor invoice number already processed as invoice {}", invoice)
+            }
+        }
+    }
+}
+
+/// What is being submitted, for comparison against existing invoices.
+pub struct Candidate<'a> {
+    pub vendor_account: Pubkey,
+    pub invoice_number: &'a str,
+    pub amount: u64,
+    pub due_date: i64,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct NearDuplicate {
+    pub invoice: Pubkey,
+    pub reason: String,
+}
+
+/// Look up the registries the program will try to claim.
+pub fn find_exact(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    org_config: &Pubkey,
+    vendor_account: &Pubkey,
+    invoice_number: &str,
+    ipfs_hash: &str,
+) -> Result<Option<Duplicate>, Box<dyn std::error::Error>> {
+    let keys = [
+        pda::document_registry(program_id, org_config, ipfs_hash),
+        pda::number_registry(program_id, vendor_account, invoice_number, ipfs_hash),
+    ];
+    let accounts = rpc_client.get_multiple_accounts(&keys)?;
+
+    for (i, account) in accounts.into_iter().enumerate() {
+        let Some(account) = account else { continue };
+        let invoice = InvoiceRegistry::try_from_account_data(&account.data)?.invoice;
+        return Ok(Some(if i == 0 {
+            Duplicate::Document { invoice }
+        } else {
+            Duplicate::InvoiceNumber { invoice }
+        }));
+    }
+    Ok(None)
+}
+
+/// Existing invoices from the same vendor that look like `candidate`: an
+/// invoice number one edit away, or the same amount falling due within a week.
+pub fn find_near(candidate: &Candidate, existing: &[(Pubkey, InvoiceAccount)]) -> Vec<NearDuplicate> {
+    let number = pda::normalize_invoice_number(candidate.invoice_number);
+
+    existing
+        .iter()
+        .filter(|(_, inv)| inv.vendor == candidate.vendor_account)
+        .filter_map(|(key, inv)| {
+            let other = pda::normalize_invoice_number(&inv.invoice_number);
+            let reason = if !number.is_empty() && !other.is_empty() && edit_distance(&number, &other) <= 1 {
+                format!("invoice number {:?} is close to {:?}", candidate.invoice_number, inv.invoice_number)
+            } else if inv.amount == candidate.amount
+                && (inv.due_date - candidate.due_date).abs() <= NEAR_DUE_DATE_WINDOW_SECS
+            {
+                format!("same amount {} due within a week ({} vs {})", inv.amount, inv.due_date, candidate.due_date)
+            } else {
+                return None;
+            };
+            Some(NearDuplicate { invoice: *key, reason })
+        })
+        .collect()
+}
+
+// Levenshtein distance over bytes; invoice numbers are normalised to ASCII
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            cur[j + 1] = (prev[j] + (ca != cb) as usize).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use invoice_claim_client::InvoiceStatus;
+
+    fn invoice(vendor: Pubkey, number: &str, amount: u64, due_date: i64) -> InvoiceAccount {
+        InvoiceAccount {
+            authority: Pubkey::new_unique(),
+            vendor,
+            vendor_name: "Acme".to_string(),
+            amount,
+            due_date,
+            ipfs_hash: "Qm".to_string(),
+            status: InvoiceStatus::Validated,
+            timestamp: 0,
+            nonce: 0,
+            invoice_number: number.to_string(),
+            escrowed_at: 0,
+        }
+    }
+
+    #[test]
+    fn measures_edit_distance() {
+        assert_eq!(edit_distance("INV0042", "INV0042"), 0);
+        assert_eq!(edit_distance("INV0042", "INV0043"), 1);
+        assert_eq!(edit_distance("INV0042", "INV042"), 1);
+        assert_eq!(edit_distance("INV0042", "INV0024"), 2);
+        assert_eq!(edit_distance("", "AB"), 2);
+    }
+
+    #[test]
+    fn flags_similar_numbers_and_amounts_from_the_same_vendor() {
+        let vendor = Pubkey::new_unique();
+        let day = 86_400;
+        let existing = vec![
+            (Pubkey::new_unique(), invoice(vendor, "INV-0042", 100, 30 * day)),
+            (Pubkey::new_unique(), invoice(vendor, "INV-0099", 500, 10 * day)),
+            (Pubkey::new_unique(), invoice(vendor, "INV-7000", 700, 90 * day)),
+            // Another vendor's invoice never matches
+            (Pubkey::new_unique(), invoice(Pubkey::new_unique(), "INV-0042", 500, 10 * day)),
+        ];
+        let candidate = Candidate { vendor_account: vendor, invoice_number: "inv 0043", amount: 500, due_date: 14 * day };
+
+        let found: Vec<Pubkey> = find_near(&candidate, &existing).into_iter().map(|n| n.invoice).collect();
+        assert_eq!(found, vec![existing[0].0, existing[1].0]);
+    }
+
+    #[test]
+    fn ignores_missing_numbers() {
+        let vendor = Pubkey::new_unique();
+        let existing = vec![(Pubkey::new_unique(), invoice(vendor, "", 100, 0))];
+        let candidate = Candidate { vendor_account: vendor, invoice_number: "7", amount: 200, due_date: 0 };
+        assert!(find_near(&candidate, &existing).is_empty());
+    }
+}
@@ -0,0 +1,677 @@
+use chrono::{Days, NaiveDate, NaiveTime};
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+use crate::amount::decimal_mark;
+
+// Confidence scores: fields pulled by a vendor template beat labelled generic
+// matches, which beat positional guesses.
+const TEMPLATE: f32 = 0.95;
+const LABELLED: f32 = 0.9;
+const UNLABELLED: f32 = 0.7;
+const GUESS: f32 = 0.5;
+
+// Groups may be separated by a single (no-break) space, as in "1 250,00"
+const MONEY: &str =
+    r"[$€£¥]?\s?(\d{1,3}(?:[,.' \x{a0}\x{202f}]\d{3})+(?:[.,]\d{1,2})?|\d+(?:[.,]\d{1,2})?)(?:\s?[€£¥])?";
+const DATE: &str = r"(\d{4}-\d{2}-\d{2}|\d{1,2}[/.\-]\d{1,2}[/.\-]\d{2,4}|[A-Za-z]{3,9}\.?\s+\d{1,2}(?:st|nd|rd|th)?,?\s+\d{4}|\d{1,2}(?:st|nd|rd|th)?\s+[A-Za-z]{3,9}\.?,?\s+\d{4})";
+pub(crate) const CURRENCY_CODES: &[&str] = &["USD", "EUR", "GBP", "JPY", "CAD", "AUD", "CHF", "INR", "SGD", "USDC"];
+
+/// A value pulled from OCR text with a confidence in (0, 1].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field<T> {
+    pub value: T,
+    pub confidence: f32,
+}
+
+fn field<T>(value: T, confidence: f32) -> Field<T> {
+    Field { value, confidence }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineItem {
+    pub description: String,
+    pub quantity: Option<String>,
+    pub unit_price: Option<String>,
+    pub amount: String,
+}
+
+/// Fields extracted from one invoice. Amounts are kept as printed (digits and
+/// separators, no currency symbol) so no precision is lost before conversion
+/// to token base units.
+#[derive(Debug, Default)]
+pub struct ExtractedInvoice {
+    pub vendor_name: Option<Field<String>>,
+    pub invoice_number: Option<Field<String>>,
+    pub issue_date: Option<Field<NaiveDate>>,
+    pub due_date: Option<Field<NaiveDate>>,
+    /// Days from issue to due date, from "Net 30" style payment terms
+    pub payment_terms_days: Option<Field<u32>>,
+    pub currency: Option<Field<String>>,
+    pub subtotal: Option<Field<String>>,
+    pub tax: Option<Field<String>>,
+    pub total: Option<Field<String>>,
+    pub line_items: Option<Field<Vec<LineItem>>>,
+    /// Name of the vendor template that matched, if any
+    pub template: Option<String>,
+}
+
+/// Where the submitted due date came from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DueDateSource {
+    Printed,
+    /// Issue date plus the invoice's own payment terms
+    InvoiceTerms,
+    /// Issue date (or processing date when none was found) plus the configured default terms
+    DefaultTerms,
+}
+
+impl ExtractedInvoice {
+    /// Unix timestamp the invoice is due: the printed due date if there is
+    /// one, otherwise the issue date plus net terms. Dates are taken as the
+    /// end of that day in UTC so an invoice is never due before its date ends.
+    pub fn due_timestamp(&self, default_net_days: u32, now: i64) -> (i64, DueDateSource) {
+        let end_of_day = |d: NaiveDate| d.and_time(NaiveTime::from_hms_opt(23, 59, 59).unwrap()).and_utc().timestamp();
+
+        if let Some(due) = &self.due_date {
+            return (end_of_day(due.value), DueDateSource::Printed);
+        }
+        let (days, source) = match &self.payment_terms_days {
+            Some(terms) => (terms.value, DueDateSource::InvoiceTerms),
+            None => (default_net_days, DueDateSource::DefaultTerms),
+        };
+        let issued = self
+            .issue_date
+            .as_ref()
+            .map(|f| f.value)
+            .or_else(|| chrono::DateTime::from_timestamp(now, 0).map(|t| t.date_naive()))
+            .unwrap_or_default();
+        let due = issued.checked_add_days(Days::new(days.into())).unwrap_or(NaiveDate::MAX);
+        (end_of_day(due), source)
+    }
+
+    /// Decimal separator the extracted amounts agree on, for reading a lone
+    /// `1.250` elsewhere on the invoice; None when none shows it or they conflict.
+    pub fn decimal_mark(&self) -> Option<char> {
+        let items = self.line_items.iter().flat_map(|f| &f.value);
+        let amounts = [&self.subtotal, &self.tax, &self.total]
+            .into_iter()
+            .flatten()
+            .map(|f| f.value.as_str())
+            .chain(items.flat_map(|item| item.unit_price.as_deref().into_iter().chain([item.amount.as_str()])));
+        let mut marks = amounts.filter_map(decimal_mark);
+        let mark = marks.next()?;
+        marks.all(|m| m == mark).then_some(mark)
+    }
+
+    pub fn log(&self) {
+        fn show<T: fmt::Debug>(name: &str, f: &Option<Field<T>>) {
+            match f {
+                Some(f) => println!("  {:<15} {:?} ({:.2})", name, f.value, f.confidence),
+                None => println!("  {:<15} -", name),
+            }
+        }
+        println!("\n===== EXTRACTED INVOICE FIELDS =====");
+        if let Some(t) = &self.template {
+            println!("  template        {}", t);
+        }
+        show("vendor_name", &self.vendor_name);
+        show("invoice_number", &self.invoice_number);
+        show("issue_date", &self.issue_date);
+        show("due_date", &self.due_date);
+        show("payment_terms", &self.payment_terms_days);
+        show("currency", &self.currency);
+        show("subtotal", &self.subtotal);
+        show("tax", &self.tax);
+        show("total", &self.total);
+        match &self.line_items {
+            Some(items) => println!("  {:<15} {} ({:.2})", "line_items", items.value.len(), items.confidence),
+            None => println!("  {:<15} -", "line_items"),
+        }
+        println!("====================================\n");
+    }
+}
+
+/// Per-vendor extraction rules, loaded from JSON:
+///
+/// ```json
+/// [{
+///   "vendor": "Muller Burobedarf",
+///   "match_any": ["Müller Bürobedarf GmbH"],
+///   "date_format": "%d.%m.%Y",
+///   "currency": "EUR",
+///   "line_items_header": "Pos\\.\\s+Bezeichnung",
+///   "fields": { "invoice_number": "Rechnungsnummer:\\s*(\\S+)" }
+/// }]
+/// ```
+///
+/// `vendor` is the name the vendor was registered with on-chain. `fields`
+/// maps a field name to a regex whose first capture group is the value; any
+/// field without a rule falls back to the generic heuristics. Line items are
+/// read from the row after `line_items_header` up to the first totals line.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TemplateSpec {
+    pub vendor: String,
+    pub match_any: Vec<String>,
+    #[serde(default)]
+    pub date_format: Option<String>,
+    #[serde(default)]
+    pub currency: Option<String>,
+    #[serde(default)]
+    pub line_items_header: Option<String>,
+    #[serde(default)]
+    pub fields: HashMap<String, String>,
+}
+
+const TEMPLATE_FIELDS: &[&str] =
+    &["invoice_number", "issue_date", "due_date", "payment_terms_days", "subtotal", "tax", "total"];
+
+struct Template {
+    spec: TemplateSpec,
+    fields: HashMap<String, Regex>,
+    item_header: Option<Regex>,
+}
+
+impl Template {
+    fn compile(spec: TemplateSpec) -> Result<Self, Box<dyn Error>> {
+        let mut fields = HashMap::new();
+        for (name, pattern) in &spec.fields {
+            if !TEMPLATE_FIELDS.contains(&name.as_str()) {
+                return Err(format!("template '{}': unknown field '{}'", spec.vendor, name).into());
+            }
+            let re = Regex::new(pattern).map_err(|e| format!("template '{}' field '{}': {}", spec.vendor, name, e))?;
+            if re.captures_len() < 2 {
+                return Err(format!("template '{}' field '{}' has no capture group", spec.vendor, name).into());
+            }
+            fields.insert(name.clone(), re);
+        }
+        let item_header = match &spec.line_items_header {
+            Some(p) => Some(Regex::new(p).map_err(|e| format!("template '{}' line_items_header: {}", spec.vendor, e))?),
+            None => None,
+        };
+        Ok(Template { spec, fields, item_header })
+    }
+
+    fn matches(&self, lower_text: &str) -> bool {
+        self.spec.match_any.iter().any(|m| lower_text.contains(&m.to_lowercase()))
+    }
+
+    fn capture(&self, name: &str, text: &str) -> Option<String> {
+        let c = self.fields.get(name)?.captures(text)?;
+        Some(c.get(1)?.as_str().trim().to_string())
+    }
+}
+
+/// Pulls structured fields out of OCR text using vendor templates where one
+/// matches and generic label-based heuristics otherwise.
+pub struct Extractor {
+    templates: Vec<Template>,
+    money: Regex,
+    invoice_number: Regex,
+    issue_label: Regex,
+    due_label: Regex,
+    payment_terms: Regex,
+    amount_due_suffix: Regex,
+    vendor_label: Regex,
+    skip_line: Regex,
+    item_header: Regex,
+    item_line: Regex,
+}
+
+impl Extractor {
+    pub fn new(templates: Vec<TemplateSpec>) -> Result<Self, Box<dyn Error>> {
+        let templates = templates.into_iter().map(Template::compile).collect::<Result<_, _>>()?;
+        Ok(Extractor {
+            templates,
+            money: Regex::new(MONEY).unwrap(),
+            invoice_number: Regex::new(
+                r"(?i)\binvoice\s*(?:no\.?|number|num\.?|#)\s*[:#]?\s*([A-Z0-9][A-Z0-9\-/_.]*[A-Z0-9])",
+            )
+            .unwrap(),
+            issue_label: Regex::new(&format!(
+                r"(?i)\b(?:invoice\s+date|date\s+of\s+issue|issue\s+date|issued(?:\s+on)?|date)\s*[:\-]?\s*{}",
+                DATE
+            ))
+            .unwrap(),
+            due_label: Regex::new(&format!(
+                r"(?i)\b(?:due\s+date|date\s+due|payment\s+due|due\s+by|due\s+on|due)\s*[:\-]?\s*{}",
+                DATE
+            ))
+            .unwrap(),
+            payment_terms: Regex::new(
+                r"(?i)\b(?:net\s*-?\s*(\d{1,3})\b|(?:payable|due)\s+within\s+(\d{1,3})\s+days|terms\s*:?\s*(\d{1,3})\s+days)",
+            )
+            .unwrap(),
+            amount_due_suffix: Regex::new(&format!(r"(?i){}\s+due\b", MONEY)).unwrap(),
+            vendor_label: Regex::new(r"(?i)^\s*(?:from|vendor|seller|supplier|issued\s+by|pay\s+to|remit\s+to)\s*:\s*(.*)$")
+                .unwrap(),
+            skip_line: Regex::new(
+                r"(?i)^\s*(?:tax\s+)?(?:invoice|receipt|statement|bill\s+to|ship\s+to|date|due|page|total|sub\s*total|tax|vat|amount|balance|description|qty|terms|po\b|order)",
+            )
+            .unwrap(),
+            item_header: Regex::new(r"(?i)^\s*(?:description|item|service|product)s?\b").unwrap(),
+            item_line: Regex::new(&format!(
+                // Columns are two or more spaces apart; one space may be digit grouping
+                r"^\s*(\S.*?\S)(?:\s{{2,}}|\t)\s*(?:(\d+(?:\.\d+)?)(?:\s{{2,}}|\t)\s*)?(?:{m}(?:\s{{2,}}|\t)\s*)?{m}\s*$",
+                m = MONEY
+            ))
+            .unwrap(),
+        })
+    }
+
+    /// Load templates from a JSON file (see [`TemplateSpec`]).
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let raw = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let specs: Vec<TemplateSpec> =
+            serde_json::from_str(&raw).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::new(specs)
+    }
+
+    pub fn extract(&self, text: &str) -> ExtractedInvoice {
+        let lower = text.to_lowercase();
+        let template = self.templates.iter().find(|t| t.matches(&lower));
+        let date_format = template.and_then(|t| t.spec.date_format.as_deref());
+
+        let mut out = ExtractedInvoice {
+            template: template.map(|t| t.spec.vendor.clone()),
+            ..Default::default()
+        };
+
+        out.vendor_name = match template {
+            Some(t) => Some(field(t.spec.vendor.clone(), 1.0)),
+            None => self.vendor_name(text),
+        };
+
+        out.invoice_number = template
+            .and_then(|t| t.capture("invoice_number", text))
+            .map(|v| field(v, TEMPLATE))
+            .or_else(|| self.invoice_number.captures(text).map(|c| field(c[1].to_string(), LABELLED)));
+
+        let template_date = |name: &str| {
+            let raw = template?.capture(name, text)?;
+            parse_date(&raw, date_format).map(|(d, _)| field(d, TEMPLATE))
+        };
+        out.issue_date = template_date("issue_date").or_else(|| self.labelled_date(&self.issue_label, text, date_format));
+        out.due_date = template_date("due_date").or_else(|| self.labelled_date(&self.due_label, text, date_format));
+        out.payment_terms_days = template
+            .and_then(|t| t.capture("payment_terms_days", text))
+            .and_then(|v| v.parse().ok())
+            .map(|v| field(v, TEMPLATE))
+            .or_else(|| {
+                let c = self.payment_terms.captures(text)?;
+                let days = c.iter().skip(1).flatten().next()?.as_str().parse().ok()?;
+                Some(field(days, LABELLED))
+            });
+
+        out.currency = match template.and_then(|t| t.spec.currency.clone()) {
+            Some(c) => Some(field(c, 1.0)),
+            None => detect_currency(text),
+        };
+
+        let template_amount = |name: &str| {
+            let raw = template?.capture(name, text)?;
+            let c = self.money.captures(&raw)?;
+            Some(field(c[1].to_string(), TEMPLATE))
+        };
+        out.subtotal = template_amount("subtotal").or_else(|| self.labelled_amount(text, &["sub total", "subtotal"], &[]));
+        out.tax = template_amount("tax").or_else(|| {
+            self.labelled_amount(text, &["sales tax", "tax", "vat", "gst"], &["tax invoice", "tax id", "vat no", "vat number", "vat reg"])
+        });
+        out.total = template_amount("total")
+            .or_else(|| self.labelled_amount(text, &["amount due", "balance due", "total due"], &[]))
+            .or_else(|| self.labelled_amount(text, &["grand total", "total amount", "total"], &["total tax", "total vat"]))
+            .or_else(|| {
+                self.amount_due_suffix
+                    .captures(text)
+                    .map(|c| field(c[1].to_string(), UNLABELLED))
+            });
+
+        out.line_items = self.line_items(text, template);
+        out
+    }
+
+    fn vendor_name(&self, text: &str) -> Option<Field<String>> {
+        let lines: Vec<&str> = text.lines().collect();
+        for (i, line) in lines.iter().enumerate() {
+            if let Some(c) = self.vendor_label.captures(line) {
+                let value = c[1].trim();
+                let value = if value.is_empty() {
+                    lines[i + 1..].iter().map(|l| l.trim()).find(|l| !l.is_empty())?
+                } else {
+                    value
+                };
+                return Some(field(value.to_string(), LABELLED));
+            }
+        }
+
+        // Otherwise the issuer is usually the first plain line of the header.
+        // Everything after "Bill to" is the payer, so stop there.
+        for line in lines.iter().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+            if line.to_lowercase().starts_with("bill to") {
+                break;
+            }
+            let letters = line.chars().filter(|c| c.is_alphabetic()).count();
+            if self.skip_line.is_match(line) || line.contains('@') || letters * 2 < line.chars().count() {
+                continue;
+            }
+            return Some(field(line.to_string(), GUESS));
+        }
+        None
+    }
+
+    fn labelled_date(&self, label: &Regex, text: &str, format: Option<&str>) -> Option<Field<NaiveDate>> {
+        label.captures_iter(text).find_map(|c| {
+            let (date, ambiguous) = parse_date(&c[1], format)?;
+            Some(field(date, if ambiguous { GUESS } else { LABELLED }))
+        })
+    }
+
+    // Last amount on the first line starting with one of `labels`. Amounts in
+    // table layouts sometimes land on the next line, so that is tried too.
+    fn labelled_amount(&self, text: &str, labels: &[&str], exclude: &[&str]) -> Option<Field<String>> {
+        let lines: Vec<&str> = text.lines().collect();
+        for label in labels {
+            for (i, line) in lines.iter().enumerate() {
+                let lower = line.trim().to_lowercase();
+                if !lower.starts_with(label) || exclude.iter().any(|x| lower.starts_with(x)) {
+                    continue;
+                }
+                // "total" must not match "totals" or similar
+                if lower[label.len()..].starts_with(|c: char| c.is_alphabetic()) {
+                    continue;
+                }
+                let rest = &line.trim()[label.len()..];
+                let found = self
+                    .money
+                    .captures_iter(rest)
+                    .last()
+                    .or_else(|| lines.get(i + 1).and_then(|next| self.money.captures_iter(next.trim()).last()));
+                if let Some(c) = found {
+                    return Some(field(c[1].to_string(), LABELLED));
+                }
+            }
+        }
+        None
+    }
+
+    fn line_items(&self, text: &str, template: Option<&Template>) -> Option<Field<Vec<LineItem>>> {
+        let lines: Vec<&str> = text.lines().collect();
+        let header = template.and_then(|t| t.item_header.as_ref()).unwrap_or(&self.item_header);
+        // With a header row only the lines between it and the totals count
+        let (start, confidence) = match lines.iter().position(|l| header.is_match(l)) {
+            Some(i) => (i + 1, UNLABELLED),
+            None => (0, GUESS),
+        };
+        let is_totals = |line: &str| {
+            let lower = line.trim().to_lowercase();
+            ["subtotal", "sub total", "total"].iter().any(|p| lower.starts_with(p))
+                || template.is_some_and(|t| {
+                    ["subtotal", "tax", "total"]
+                        .iter()
+                        .filter_map(|f| t.fields.get(*f))
+                        .any(|re| re.is_match(line))
+                })
+        };
+
+        let mut items = Vec::new();
+        for line in &lines[start..] {
+            if start > 0 && is_totals(line) {
+                break;
+            }
+            if self.skip_line.is_match(line) {
+                continue;
+            }
+            let Some(c) = self.item_line.captures(line) else { continue };
+            let description = c[1].trim().to_string();
+            if !description.chars().any(|ch| ch.is_alphabetic()) {
+                continue;
+            }
+            items.push(LineItem {
+                description,
+                quantity: c.get(2).map(|m| m.as_str().to_string()),
+                unit_price: c.get(3).map(|m| m.as_str().to_string()),
+                amount: c[4].to_string(),
+            });
+        }
+        (!items.is_empty()).then(|| field(items, confidence))
+    }
+}
+
+/// Parse a date as printed on an invoice. Returns the date and whether it was
+/// ambiguous (e.g. 03/04/2025 is valid as both M/D and D/M).
+pub fn parse_date(raw: &str, format: Option<&str>) -> Option<(NaiveDate, bool)> {
+    let raw = raw.trim();
+    if let Some(f) = format {
+        if let Ok(d) = NaiveDate::parse_from_str(raw, f) {
+            return Some((d, false));
+        }
+    }
+
+    // Normalise "March 3rd, 2025" / "3 Mar. 2025" to "March 3 2025" / "3 Mar 2025"
+    let cleaned: String = raw
+        .split_whitespace()
+        .map(|w| {
+            let w = w.trim_end_matches([',', '.']);
+            for suffix in ["st", "nd", "rd", "th"] {
+                if let Some(n) = w.strip_suffix(suffix) {
+                    if !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()) {
+                        return n.to_string();
+                    }
+                }
+            }
+            w.to_string()
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    for f in ["%Y-%m-%d", "%B %d %Y", "%b %d %Y", "%d %B %Y", "%d %b %Y", "%d.%m.%Y", "%d-%m-%Y"] {
+        if let Ok(d) = NaiveDate::parse_from_str(&cleaned, f) {
+            return Some((d, false));
+        }
+    }
+
+    // Slashed dates default to US order; flag them when D/M would also parse
+    for (us, eu) in [("%m/%d/%Y", "%d/%m/%Y"), ("%m/%d/%y", "%d/%m/%y")] {
+        match (NaiveDate::parse_from_str(&cleaned, us), NaiveDate::parse_from_str(&cleaned, eu)) {
+            (Ok(a), Ok(b)) => return Some((a, a != b)),
+            (Ok(a), Err(_)) | (Err(_), Ok(a)) => return Some((a, false)),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn detect_currency(text: &str) -> Option<Field<String>> {
+    for code in CURRENCY_CODES {
+        let found = text.match_indices(code).any(|(i, _)| {
+            let before = text[..i].chars().next_back();
+            let after = text[i + code.len()..].chars().next();
+            !before.is_some_and(|c| c.is_alphanumeric()) && !after.is_some_and(|c| c.is_alphanumeric())
+        });
+        if found {
+            return Some(field(code.to_string(), LABELLED));
+        }
+    }
+    for (symbol, code, confidence) in [('€', "EUR", 0.85), ('£', "GBP", 0.85), ('¥', "JPY", UNLABELLED), ('$', "USD", 0.6)] {
+        if text.contains(symbol) {
+            return Some(field(code.to_string(), confidence));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::parse_base_units;
+    use serde_json::Value;
+    use std::path::PathBuf;
+
+    fn corpus_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/invoices")
+    }
+
+    fn check_str(name: &str, field: &str, got: Option<String>, want: &Value) {
+        match want {
+            Value::Null => assert_eq!(got, None, "{}: {}", name, field),
+            Value::String(s) => assert_eq!(got.as_deref(), Some(s.as_str()), "{}: {}", name, field),
+            _ => panic!("{}: bad expectation for {}", name, field),
+        }
+    }
+
+    // Every fixtures/invoices/<name>.txt has a <name>.json with the expected
+    // values; null means the field must not be extracted.
+    #[test]
+    fn corpus_matches_expected_fields() {
+        let extractor = Extractor::from_file(&corpus_dir().join("templates.json")).unwrap();
+        let mut checked = 0;
+
+        for entry in std::fs::read_dir(corpus_dir()).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|e| e != "txt") {
+                continue;
+            }
+            let name = path.file_stem().unwrap().to_string_lossy().to_string();
+            let text = std::fs::read_to_string(&path).unwrap();
+            let want: Value =
+                serde_json::from_str(&std::fs::read_to_string(path.with_extension("json")).unwrap()).unwrap();
+            let got = extractor.extract(&text);
+
+            let value = |f: &Option<Field<String>>| f.as_ref().map(|f| f.value.clone());
+            let date = |f: &Option<Field<NaiveDate>>| f.as_ref().map(|f| f.value.to_string());
+            check_str(&name, "vendor_name", value(&got.vendor_name), &want["vendor_name"]);
+            check_str(&name, "invoice_number", value(&got.invoice_number), &want["invoice_number"]);
+            check_str(&name, "issue_date", date(&got.issue_date), &want["issue_date"]);
+            check_str(&name, "due_date", date(&got.due_date), &want["due_date"]);
+            assert_eq!(
+                got.payment_terms_days.as_ref().map(|f| f.value as u64),
+                want["payment_terms_days"].as_u64(),
+                "{}: payment_terms_days",
+                name
+            );
+            check_str(&name, "currency", value(&got.currency), &want["currency"]);
+            check_str(&name, "subtotal", value(&got.subtotal), &want["subtotal"]);
+            check_str(&name, "tax", value(&got.tax), &want["tax"]);
+            check_str(&name, "total", value(&got.total), &want["total"]);
+            check_str(&name, "template", got.template.clone(), &want["template"]);
+            for amount in [&got.subtotal, &got.tax, &got.total].into_iter().flatten() {
+                let units = parse_base_units(&amount.value, got.decimal_mark(), 6);
+                assert!(units.is_ok(), "{}: unparseable {} ({:?})", name, amount.value, units);
+            }
+
+            let items = got.line_items.as_ref().map(|f| f.value.clone()).unwrap_or_default();
+            let want_items = want["line_items"].as_array().unwrap();
+            assert_eq!(items.len(), want_items.len(), "{}: line item count {:?}", name, items);
+            for (item, w) in items.iter().zip(want_items) {
+                assert_eq!(item.description, w["description"].as_str().unwrap(), "{}: line item", name);
+                assert_eq!(item.amount, w["amount"].as_str().unwrap(), "{}: line item amount", name);
+            }
+
+            for c in [&got.vendor_name, &got.invoice_number, &got.currency, &got.subtotal, &got.tax, &got.total]
+                .into_iter()
+                .flatten()
+                .map(|f| f.confidence)
+                .chain(got.issue_date.iter().chain(&got.due_date).map(|f| f.confidence))
+            {
+                assert!(c > 0.0 && c <= 1.0, "{}: confidence {} out of range", name, c);
+            }
+            checked += 1;
+        }
+        assert!(checked >= 5, "corpus has only {} samples", checked);
+    }
+
+    #[test]
+    fn template_fields_outrank_heuristics() {
+        let text = std::fs::read_to_string(corpus_dir().join("muller_de.txt")).unwrap();
+        let with = Extractor::from_file(&corpus_dir().join("templates.json")).unwrap().extract(&text);
+        let without = Extractor::new(vec![]).unwrap().extract(&text);
+        assert_eq!(with.vendor_name.unwrap().confidence, 1.0);
+        assert!(with.total.unwrap().confidence > without.total.map(|f| f.confidence).unwrap_or(0.0));
+    }
+
+    #[test]
+    fn reads_space_grouped_amounts() {
+        let extractor = Extractor::new(vec![]).unwrap();
+        for (text, total) in [
+            ("Total: 1 250,00 €", "1 250,00"),
+            ("Total: 1\u{a0}250,00 €", "1\u{a0}250,00"),
+            ("Total: 12 345 678,90", "12 345 678,90"),
+            ("Total: 3   250,00", "250,00"),
+        ] {
+            let got = extractor.extract(text);
+            assert_eq!(got.total.map(|f| f.value).as_deref(), Some(total), "{}", text);
+        }
+    }
+
+    #[test]
+    fn decimal_mark_comes_from_the_other_amounts() {
+        let extractor = Extractor::new(vec![]).unwrap();
+        let mark = |text: &str| extractor.extract(text).decimal_mark();
+        assert_eq!(mark("Subtotal: 1.000,50\nTax: 249,50\nTotal: 1.250"), Some(','));
+        assert_eq!(mark("Subtotal: 1,000.50\nTax: 249.50\nTotal: 1,250"), Some('.'));
+        assert_eq!(mark("Total: 1.250"), None);
+        assert_eq!(mark("Subtotal: 1.000,50\nTax: 249.50\nTotal: 1.250"), None);
+
+        let got = extractor.extract("Subtotal: 1.000,00\nTax: 250,00\nTotal: 1.250");
+        let total = got.total.as_ref().unwrap().value.as_str();
+        assert_eq!(parse_base_units(total, got.decimal_mark(), 6), Ok(1_250_000_000));
+    }
+
+    #[test]
+    fn due_date_falls_back_to_net_terms() {
+        let date = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        let ts = |s: &str| date(s).and_hms_opt(23, 59, 59).unwrap().and_utc().timestamp();
+        let now = ts("2025-01-01");
+
+        let mut inv = ExtractedInvoice {
+            issue_date: Some(field(date("2025-01-20"), LABELLED)),
+            payment_terms_days: Some(field(14, LABELLED)),
+            due_date: Some(field(date("2025-02-01"), LABELLED)),
+            ..Default::default()
+        };
+        assert_eq!(inv.due_timestamp(30, now), (ts("2025-02-01"), DueDateSource::Printed));
+
+        inv.due_date = None;
+        assert_eq!(inv.due_timestamp(30, now), (ts("2025-02-03"), DueDateSource::InvoiceTerms));
+
+        inv.payment_terms_days = None;
+        assert_eq!(inv.due_timestamp(30, now), (ts("2025-02-19"), DueDateSource::DefaultTerms));
+
+        inv.issue_date = None;
+        assert_eq!(inv.due_timestamp(30, now), (ts("2025-01-31"), DueDateSource::DefaultTerms));
+    }
+
+    #[test]
+    fn parses_common_date_formats() {
+        let d = |s| parse_date(s, None).map(|(d, amb)| (d.to_string(), amb));
+        assert_eq!(d("2025-03-04"), Some(("2025-03-04".into(), false)));
+        assert_eq!(d("March 4th, 2025"), Some(("2025-03-04".into(), false)));
+        assert_eq!(d("4 Mar. 2025"), Some(("2025-03-04".into(), false)));
+        assert_eq!(d("04.03.2025"), Some(("2025-03-04".into(), false)));
+        assert_eq!(d("03/04/2025"), Some(("2025-03-04".into(), true)));
+        assert_eq!(d("03/24/2025"), Some(("2025-03-24".into(), false)));
+        assert_eq!(d("24/03/2025"), Some(("2025-03-24".into(), false)));
+        assert_eq!(parse_date("03/04/2025", Some("%d/%m/%Y")).unwrap().0.to_string(), "2025-04-03");
+        assert_eq!(d("soon"), None);
+    }
+
+    #[test]
+    fn rejects_bad_templates() {
+        let spec = |fields: &[(&str, &str)]| TemplateSpec {
+            vendor: "X".into(),
+            match_any: vec!["x".into()],
+            date_format: None,
+            currency: None,
+            line_items_header: None,
+            fields: fields.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        };
+        assert!(Extractor::new(vec![spec(&[("vendor", "(.*)")])]).is_err());
+        assert!(Extractor::new(vec![spec(&[("total", "Total .*")])]).is_err());
+        assert!(Extractor::new(vec![spec(&[("total", "Total (")])]).is_err());
+        assert!(Extractor::new(vec![spec(&[("total", "Total (.*)")])]).is_ok());
+    }
+}
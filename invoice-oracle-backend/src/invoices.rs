@@ -0,0 +1,122 @@
+//! On-chain invoice lookups shared by the settlement passes and the
+//! submission checks.
+
+use solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig};
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType};
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use std::collections::{HashMap, HashSet};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use invoice_claim_client::{InvoiceAccount, ProgramAccount, VendorAccount};
+
+/// Decoded InvoiceAccounts of each org, keyed by OrgConfig PDA.
+pub(crate) type OrgInvoices = HashMap<Pubkey, Vec<(Pubkey, InvoiceAccount)>>;
+
+// `VendorAccount::org` follows the discriminator
+const VENDOR_ORG_OFFSET: usize = 8;
+
+// `InvoiceAccount::vendor` follows the discriminator and the authority
+const INVOICE_VENDOR_OFFSET: usize = 8 + 32;
+
+/// OrgConfig PDA a VendorAccount belongs to.
+pub(crate) fn read_vendor_org(
+    rpc_client: &RpcClient,
+    vendor_account_pda: &Pubkey,
+) -> Result<Pubkey, Box<dyn std::error::Error>> {
+    Ok(VendorAccount::try_from_account_data(&rpc_client.get_account(vendor_account_pda)?.data)?.org)
+}
+
+/// Fetch every InvoiceAccount once and group those issued by the vendors of
+/// `orgs` by org.
+///
+/// Invoices don't record their org, so a second call maps each vendor to its
+/// org, reading only `VendorAccount::org`. Orgs without invoices are absent.
+pub(crate) fn get_org_invoices(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    orgs: &HashSet<Pubkey>,
+) -> Result<OrgInvoices, Box<dyn std::error::Error>> {
+    let vendors = get_program_accounts(
+        rpc_client,
+        program_id,
+        vec![discriminator_filter(&VendorAccount::discriminator())],
+        Some(UiDataSliceConfig { offset: VENDOR_ORG_OFFSET, length: 32 }),
+    )?;
+
+    let mut vendor_orgs = HashMap::new();
+    for (vendor, account) in vendors {
+        let org = Pubkey::try_from(account.data.as_slice())?;
+        if orgs.contains(&org) {
+            vendor_orgs.insert(vendor, org);
+        }
+    }
+
+    let mut grouped = OrgInvoices::new();
+    let invoices = get_program_accounts(
+        rpc_client,
+        program_id,
+        vec![discriminator_filter(&InvoiceAccount::discriminator())],
+        None,
+    )?;
+    for (pubkey, invoice) in decode_invoices(invoices) {
+        if let Some(org) = vendor_orgs.get(&invoice.vendor) {
+            grouped.entry(*org).or_default().push((pubkey, invoice));
+        }
+    }
+    Ok(grouped)
+}
+
+/// Fetch and decode the InvoiceAccounts issued by one VendorAccount.
+pub(crate) fn get_vendor_invoices(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    vendor_account_pda: &Pubkey,
+) -> Result<Vec<(Pubkey, InvoiceAccount)>, Box<dyn std::error::Error>> {
+    let filters = vec![
+        discriminator_filter(&InvoiceAccount::discriminator()),
+        RpcFilterType::Memcmp(Memcmp::new(
+            INVOICE_VENDOR_OFFSET,
+            MemcmpEncodedBytes::Base64(STANDARD.encode(vendor_account_pda)),
+        )),
+    ];
+    let invoices = get_program_accounts(rpc_client, program_id, filters, None)?;
+    Ok(decode_invoices(invoices))
+}
+
+fn discriminator_filter(discriminator: &[u8; 8]) -> RpcFilterType {
+    RpcFilterType::Memcmp(Memcmp::new(0, MemcmpEncodedBytes::Base64(STANDARD.encode(discriminator))))
+}
+
+fn get_program_accounts(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    filters: Vec<RpcFilterType>,
+    data_slice: Option<UiDataSliceConfig>,
+) -> Result<Vec<(Pubkey, Account)>, Box<dyn std::error::Error>> {
+    Ok(rpc_client.get_program_accounts_with_config(
+        program_id,
+        RpcProgramAccountsConfig {
+            filters: Some(filters),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                data_slice,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )?)
+}
+
+fn decode_invoices(accounts: Vec<(Pubkey, Account)>) -> Vec<(Pubkey, InvoiceAccount)> {
+    let mut decoded = Vec::with_capacity(accounts.len());
+    for (pubkey, account) in accounts {
+        match InvoiceAccount::try_from_account_data(&account.data) {
+            Ok(inv) => decoded.push((pubkey, inv)),
+            Err(e) => println!("[WARN] Could not parse invoice {}: {}", pubkey, e),
+        }
+    }
+    decoded
+}
@@ -0,0 +1,554 @@
+//! Trustless IPFS retrieval. Documents are fetched block by block as raw IPLD
+//! blocks (`?format=raw`), each block is checked against the multihash in its
+//! CID, and UnixFS files are reassembled from the verified blocks, so a
+//! gateway can't substitute content for a CID. Verified documents are cached
+//! on disk by CID.
+
+use async_trait::async_trait;
+use data_encoding::BASE32_NOPAD;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+pub type IpfsError = Box<dyn Error + Send + Sync>;
+
+const DEFAULT_GATEWAYS: &str =
+    "https://emerald-abundant-baboon-978.mypinata.cloud/ipfs,https://ipfs.io/ipfs,https://gateway.pinata.cloud/ipfs";
+const DEFAULT_CACHE_DIR: &str = ".cache/ipfs";
+const DEFAULT_MAX_DOCUMENT_BYTES: usize = 20 * 1024 * 1024;
+// 20 MiB in 256 KiB chunks is 80 leaves; leave room for smaller chunkers
+const DEFAULT_MAX_DOCUMENT_BLOCKS: usize = 1024;
+// The IPFS spec caps blocks at 2 MiB; anything larger is not a block
+const MAX_BLOCK_BYTES: usize = 2 * 1024 * 1024;
+
+const CODEC_RAW: u64 = 0x55;
+const CODEC_DAG_PB: u64 = 0x70;
+const HASH_IDENTITY: u64 = 0x00;
+const HASH_SHA2_256: u64 = 0x12;
+
+// UnixFS node types that carry file content
+const UNIXFS_RAW: u64 = 0;
+const UNIXFS_FILE: u64 = 2;
+
+/// A content identifier. CIDv0 (`Qm...`) is read as its CIDv1 equivalent.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Cid {
+    codec: u64,
+    hash_code: u64,
+    digest: Vec<u8>,
+}
+
+impl Cid {
+    /// Decode a binary CID, returning it and the number of bytes read.
+    fn read(bytes: &[u8]) -> Result<(Cid, usize), IpfsError> {
+        let mut buf = bytes;
+        // CIDv0 is a bare sha2-256 multihash of a dag-pb block
+        if buf.len() >= 34 && buf[0] == HASH_SHA2_256 as u8 && buf[1] == 32 {
+            let cid = Cid { codec: CODEC_DAG_PB, hash_code: HASH_SHA2_256, digest: buf[2..34].to_vec() };
+            return Ok((cid, 34));
+        }
+        let version = read_varint(&mut buf).ok_or("truncated CID")?;
+        if version != 1 {
+            return Err(format!("unsupported CID version {}", version).into());
+        }
+        let codec = read_varint(&mut buf).ok_or("truncated CID")?;
+        let hash_code = read_varint(&mut buf).ok_or("truncated CID")?;
+        let len = read_varint(&mut buf).ok_or("truncated CID")? as usize;
+        if buf.len() < len {
+            return Err("truncated CID digest".into());
+        }
+        let cid = Cid { codec, hash_code, digest: buf[..len].to_vec() };
+        Ok((cid, bytes.len() - buf.len() + len))
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for v in [1, self.codec, self.hash_code, self.digest.len() as u64] {
+            write_varint(v, &mut out);
+        }
+        out.extend_from_slice(&self.digest);
+        out
+    }
+
+    /// Whether `block` hashes to this CID's digest.
+    fn verify(&self, block: &[u8]) -> Result<(), IpfsError> {
+        let matches = match self.hash_code {
+            HASH_SHA2_256 => Sha256::digest(block)[..] == self.digest[..],
+            HASH_IDENTITY => block == self.digest.as_slice(),
+            other => return Err(format!("unsupported multihash 0x{:x} in {}", other, self).into()),
+        };
+        if !matches {
+            return Err(format!("block content does not match {}", self).into());
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Cid {
+    type Err = IpfsError;
+
+    /// Accepts CIDv0 (`Qm...`) and base32 (`b...`) or base58btc (`z...`) CIDv1.
+    fn from_str(s: &str) -> Result<Self, IpfsError> {
+        let bytes = if s.len() == 46 && s.starts_with("Qm") {
+            bs58::decode(s).into_vec()?
+        } else if let Some(rest) = s.strip_prefix('b') {
+            BASE32_NOPAD.decode(rest.to_ascii_uppercase().as_bytes())?
+        } else if let Some(rest) = s.strip_prefix('z') {
+            bs58::decode(rest).into_vec()?
+        } else {
+            return Err(format!("unsupported CID encoding: {}", s).into());
+        };
+        let (cid, len) = Cid::read(&bytes)?;
+        if len != bytes.len() {
+            return Err(format!("trailing bytes after CID: {}", s).into());
+        }
+        Ok(cid)
+    }
+}
+
+impl fmt::Display for Cid {
+    /// Always base32 CIDv1, which is also safe to use as a file name.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "b{}", BASE32_NOPAD.encode(&self.to_bytes()).to_ascii_lowercase())
+    }
+}
+
+/// Somewhere raw blocks can be fetched from. Results are not trusted;
+/// `read_file` verifies every block.
+#[async_trait]
+pub trait BlockSource: Send + Sync {
+    async fn get_block(&self, cid: &Cid) -> Result<Vec<u8>, IpfsError>;
+}
+
+/// HTTP gateway speaking the trustless gateway protocol.
+struct Gateway {
+    base: String,
+    client: reqwest::Client,
+}
+
+#[async_trait]
+impl BlockSource for Gateway {
+    async fn get_block(&self, cid: &Cid) -> Result<Vec<u8>, IpfsError> {
+        let resp = self
+            .client
+            .get(format!("{}/{}?format=raw", self.base, cid))
+            .header("Accept", "application/vnd.ipld.raw")
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(resp.bytes().await?.to_vec())
+    }
+}
+
+/// A local Kubo node's RPC API (`/api/v0/block/get`).
+struct KuboApi {
+    base: String,
+    client: reqwest::Client,
+}
+
+#[async_trait]
+impl BlockSource for KuboApi {
+    async fn get_block(&self, cid: &Cid) -> Result<Vec<u8>, IpfsError> {
+        let resp = self
+            .client
+            .post(format!("{}/api/v0/block/get", self.base))
+            .query(&[("arg", cid.to_string())])
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(resp.bytes().await?.to_vec())
+    }
+}
+
+/// Tries each source in turn until one returns a block that verifies.
+struct Sources(Vec<(String, Box<dyn BlockSource>)>);
+
+#[async_trait]
+impl BlockSource for Sources {
+    async fn get_block(&self, cid: &Cid) -> Result<Vec<u8>, IpfsError> {
+        let mut last_err: Option<String> = None;
+        for (name, source) in &self.0 {
+            match source.get_block(cid).await {
+                Ok(block) if block.len() > MAX_BLOCK_BYTES => {
+                    last_err = Some(format!("{}: block {} is {} bytes", name, cid, block.len()))
+                }
+                Ok(block) => match cid.verify(&block) {
+                    Ok(()) => return Ok(block),
+                    Err(e) => {
+                        eprintln!("⚠️  {} served bad content: {}", name, e);
+                        last_err = Some(format!("{}: {}", name, e));
+                    }
+                },
+                Err(e) => last_err = Some(format!("{}: {}", name, e)),
+            }
+        }
+        Err(format!(
+            "failed to fetch block {}: {}",
+            cid,
+            last_err.unwrap_or_else(|| "no IPFS sources configured".to_string())
+        )
+        .into())
+    }
+}
+
+/// Fetches verified documents, reading through an on-disk cache.
+pub struct IpfsClient {
+    sources: Sources,
+    cache_dir: PathBuf,
+    max_bytes: usize,
+    max_blocks: usize,
+}
+
+impl IpfsClient {
+    /// Sources come from `IPFS_API_URL` (a local Kubo node, tried first) and
+    /// `IPFS_GATEWAYS` (comma separated); the cache lives in `IPFS_CACHE_DIR`.
+    /// Documents are capped by `IPFS_MAX_DOCUMENT_BYTES` and `IPFS_MAX_DOCUMENT_BLOCKS`.
+    pub fn from_env() -> Self {
+        let client = reqwest::Client::new();
+        let mut sources: Vec<(String, Box<dyn BlockSource>)> = Vec::new();
+
+        if let Ok(api) = env::var("IPFS_API_URL") {
+            let base = api.trim_end_matches('/').to_string();
+            sources.push((base.clone(), Box::new(KuboApi { base, client: client.clone() })));
+        }
+        let gateways = env::var("IPFS_GATEWAYS").unwrap_or_else(|_| DEFAULT_GATEWAYS.to_string());
+        for gw in gateways.split(',').map(str::trim).filter(|gw| !gw.is_empty()) {
+            let base = gw.trim_end_matches('/').to_string();
+            sources.push((base.clone(), Box::new(Gateway { base, client: client.clone() })));
+        }
+
+        IpfsClient {
+            sources: Sources(sources),
+            cache_dir: env::var("IPFS_CACHE_DIR").unwrap_or_else(|_| DEFAULT_CACHE_DIR.to_string()).into(),
+            max_bytes: env::var("IPFS_MAX_DOCUMENT_BYTES")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(DEFAULT_MAX_DOCUMENT_BYTES),
+            max_blocks: env::var("IPFS_MAX_DOCUMENT_BLOCKS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(DEFAULT_MAX_DOCUMENT_BLOCKS),
+        }
+    }
+
+    /// The document behind `cid`, verified against it.
+    pub async fn fetch(&self, cid: &str) -> Result<Vec<u8>, IpfsError> {
+        let cid: Cid = cid.parse()?;
+        // Named by the canonical CID, never the on-chain string
+        let path = self.cache_dir.join(cid.to_string());
+        if let Ok(bytes) = tokio::fs::read(&path).await {
+            println!("IPFS cache hit: {}", path.display());
+            return Ok(bytes);
+        }
+
+        let bytes = read_file(&self.sources, &cid, self.max_bytes, self.max_blocks).await?;
+        println!("Fetched and verified {} ({} bytes)", cid, bytes.len());
+
+        // Write then rename so a crash can't leave a truncated entry
+        tokio::fs::create_dir_all(&self.cache_dir).await?;
+        let tmp = path.with_extension("partial");
+        tokio::fs::write(&tmp, &bytes).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        Ok(bytes)
+    }
+}
+
+/// Reassemble the file rooted at `root`, verifying every block. Raw blocks are
+/// file content as-is; dag-pb blocks are UnixFS nodes whose own data comes
+/// before their children's.
+///
+/// A block linked more than once is fetched only the first time, and the walk
+/// fails once the file would span more than `max_blocks` blocks or
+/// `max_bytes` of content, so a small DAG can't fan out into unbounded fetches.
+pub async fn read_file(
+    source: &dyn BlockSource,
+    root: &Cid,
+    max_bytes: usize,
+    max_blocks: usize,
+) -> Result<Vec<u8>, IpfsError> {
+    let mut out = Vec::new();
+    let mut stack = vec![root.clone()];
+    let mut fetched: HashMap<Cid, Vec<u8>> = HashMap::new();
+    let mut visited = 0;
+
+    while let Some(cid) = stack.pop() {
+        visited += 1;
+        if cid.hash_code != HASH_IDENTITY && !fetched.contains_key(&cid) {
+            let block = source.get_block(&cid).await?;
+            cid.verify(&block)?;
+            fetched.insert(cid.clone(), block);
+        }
+        let block = match cid.hash_code {
+            // Inline CID: the digest is the block
+            HASH_IDENTITY => &cid.digest,
+            _ => &fetched[&cid],
+        };
+
+        match cid.codec {
+            CODEC_RAW => out.extend_from_slice(block),
+            CODEC_DAG_PB => {
+                let node = PbNode::decode(block)?;
+                let unixfs = UnixFs::decode(node.data.unwrap_or_default())?;
+                if unixfs.kind != UNIXFS_FILE && unixfs.kind != UNIXFS_RAW {
+                    return Err(format!("{} is not a file (UnixFS type {})", cid, unixfs.kind).into());
+                }
+                out.extend_from_slice(unixfs.data);
+                // Every queued link is visited, so count them before queueing
+                if visited + stack.len() + node.links.len() > max_blocks {
+                    return Err(format!("document {} spans more than {} blocks", root, max_blocks).into());
+                }
+                stack.extend(node.links.into_iter().rev());
+            }
+            other => return Err(format!("unsupported codec 0x{:x} in {}", other, cid).into()),
+        }
+        if out.len() > max_bytes {
+            return Err(format!("document {} is larger than {} bytes", root, max_bytes).into());
+        }
+    }
+    Ok(out)
+}
+
+// --- Minimal protobuf decoding for dag-pb and UnixFS ---
+
+fn read_varint(buf: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = buf.split_first()?;
+        *buf = rest;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn write_varint(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+}
+
+// Next (field number, value), skipping fixed-width fields
+fn next_field<'a>(buf: &mut &'a [u8]) -> Result<Option<(u64, Value<'a>)>, IpfsError> {
+    loop {
+        if buf.is_empty() {
+            return Ok(None);
+        }
+        let key = read_varint(buf).ok_or("truncated protobuf key")?;
+        let value = match key & 7 {
+            0 => Value::Varint(read_varint(buf).ok_or("truncated protobuf varint")?),
+            2 => {
+                let len = read_varint(buf).ok_or("truncated protobuf length")? as usize;
+                if buf.len() < len {
+                    return Err("truncated protobuf bytes".into());
+                }
+                let (bytes, rest) = buf.split_at(len);
+                *buf = rest;
+                Value::Bytes(bytes)
+            }
+            wire @ (1 | 5) => {
+                let width = if wire == 1 { 8 } else { 4 };
+                *buf = buf.get(width..).ok_or("truncated protobuf fixed field")?;
+                continue;
+            }
+            other => return Err(format!("unsupported protobuf wire type {}", other).into()),
+        };
+        return Ok(Some((key >> 3, value)));
+    }
+}
+
+struct PbNode<'a> {
+    links: Vec<Cid>,
+    data: Option<&'a [u8]>,
+}
+
+impl<'a> PbNode<'a> {
+    // PBNode { bytes Data = 1; repeated PBLink Links = 2 }, PBLink { bytes Hash = 1; ... }
+    fn decode(mut buf: &'a [u8]) -> Result<Self, IpfsError> {
+        let mut node = PbNode { links: Vec::new(), data: None };
+        while let Some((field, value)) = next_field(&mut buf)? {
+            match (field, value) {
+                (1, Value::Bytes(data)) => node.data = Some(data),
+                (2, Value::Bytes(mut link)) => {
+                    let mut hash = None;
+                    while let Some((field, value)) = next_field(&mut link)? {
+                        if let (1, Value::Bytes(h)) = (field, value) {
+                            hash = Some(h);
+                        }
+                    }
+                    let hash = hash.ok_or("dag-pb link without a hash")?;
+                    let (cid, len) = Cid::read(hash)?;
+                    if len != hash.len() {
+                        return Err("trailing bytes after link CID".into());
+                    }
+                    node.links.push(cid);
+                }
+                _ => {}
+            }
+        }
+        Ok(node)
+    }
+}
+
+struct UnixFs<'a> {
+    kind: u64,
+    data: &'a [u8],
+}
+
+impl<'a> UnixFs<'a> {
+    // Data { DataType Type = 1; bytes Data = 2; ... }
+    fn decode(mut buf: &'a [u8]) -> Result<Self, IpfsError> {
+        let mut out = UnixFs { kind: u64::MAX, data: &[] };
+        while let Some((field, value)) = next_field(&mut buf)? {
+            match (field, value) {
+                (1, Value::Varint(kind)) => out.kind = kind,
+                (2, Value::Bytes(data)) => out.data = data,
+                _ => {}
+            }
+        }
+        if out.kind == u64::MAX {
+            return Err("UnixFS node without a type".into());
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    struct MemoryStore(HashMap<Vec<u8>, Vec<u8>>);
+
+    #[async_trait]
+    impl BlockSource for MemoryStore {
+        async fn get_block(&self, cid: &Cid) -> Result<Vec<u8>, IpfsError> {
+            self.0.get(&cid.to_bytes()).cloned().ok_or_else(|| format!("missing {}", cid).into())
+        }
+    }
+
+    fn sha256_cid(codec: u64, block: &[u8]) -> Cid {
+        Cid { codec, hash_code: HASH_SHA2_256, digest: Sha256::digest(block).to_vec() }
+    }
+
+    fn field(num: u64, bytes: &[u8], out: &mut Vec<u8>) {
+        write_varint(num << 3 | 2, out);
+        write_varint(bytes.len() as u64, out);
+        out.extend_from_slice(bytes);
+    }
+
+    // dag-pb node for a UnixFS file chunk with the given children
+    fn file_node(data: &[u8], links: &[&Cid]) -> Vec<u8> {
+        let mut unixfs = vec![0x08, UNIXFS_FILE as u8];
+        field(2, data, &mut unixfs);
+        let mut node = Vec::new();
+        for link in links {
+            let mut pb_link = Vec::new();
+            field(1, &link.to_bytes(), &mut pb_link);
+            field(2, &pb_link, &mut node);
+        }
+        field(1, &unixfs, &mut node);
+        node
+    }
+
+    #[test]
+    fn parses_and_prints_cids() {
+        let raw: Cid = "bafkreibjntqp7vaggmvtlgs2sptrjhiwywmrqwlcdbdoi2ub2medwdqomm".parse().unwrap();
+        assert_eq!((raw.codec, raw.hash_code, raw.digest.len()), (CODEC_RAW, HASH_SHA2_256, 32));
+        assert_eq!(raw.to_string(), "bafkreibjntqp7vaggmvtlgs2sptrjhiwywmrqwlcdbdoi2ub2medwdqomm");
+
+        let v0: Cid = "QmT78zSuBmuS4z925WZfrqQ1qHaJ56DQaTfyMUF7F8ff5o".parse().unwrap();
+        assert_eq!((v0.codec, v0.hash_code), (CODEC_DAG_PB, HASH_SHA2_256));
+        assert!(v0.to_string().starts_with("bafybei"));
+        assert_eq!(v0.to_string().parse::<Cid>().unwrap(), v0);
+
+        for bad in ["", "Qm", "bafkrei", "hello", "../../etc/passwd"] {
+            assert!(bad.parse::<Cid>().is_err(), "{}", bad);
+        }
+    }
+
+    #[tokio::test]
+    async fn reassembles_a_chunked_file() {
+        let (a, b, c) = (b"%PDF-1.4 ".to_vec(), b"invoice ".to_vec(), b"body".to_vec());
+        let (ca, cb) = (sha256_cid(CODEC_RAW, &a), sha256_cid(CODEC_RAW, &b));
+        let inner = file_node(&[], &[&ca, &cb]);
+        let c_inner = sha256_cid(CODEC_DAG_PB, &inner);
+        let cc = sha256_cid(CODEC_RAW, &c);
+        let root = file_node(&[], &[&c_inner, &cc]);
+        let c_root = sha256_cid(CODEC_DAG_PB, &root);
+
+        let mut blocks = HashMap::new();
+        for (cid, block) in [(&ca, &a), (&cb, &b), (&cc, &c), (&c_inner, &inner), (&c_root, &root)] {
+            blocks.insert(cid.to_bytes(), block.clone());
+        }
+        let mut store = MemoryStore(blocks);
+
+        let file = read_file(&store, &c_root, 1024, 16).await.unwrap();
+        assert_eq!(file, b"%PDF-1.4 invoice body");
+
+        assert!(read_file(&store, &c_root, 10, 16).await.is_err(), "size limit");
+
+        // A source serving different bytes for a CID is rejected
+        store.0.insert(cb.to_bytes(), b"tampered".to_vec());
+        let err = read_file(&store, &c_root, 1024, 16).await.unwrap_err();
+        assert!(err.to_string().contains("does not match"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn fetches_repeated_blocks_once_and_caps_the_block_count() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct CountingStore(MemoryStore, AtomicUsize);
+
+        #[async_trait]
+        impl BlockSource for CountingStore {
+            async fn get_block(&self, cid: &Cid) -> Result<Vec<u8>, IpfsError> {
+                self.1.fetch_add(1, Ordering::SeqCst);
+                self.0.get_block(cid).await
+            }
+        }
+
+        // One chunk linked eight times from the root
+        let chunk = b"0000".to_vec();
+        let c_chunk = sha256_cid(CODEC_RAW, &chunk);
+        let root = file_node(&[], &[&c_chunk; 8]);
+        let c_root = sha256_cid(CODEC_DAG_PB, &root);
+        let store = CountingStore(
+            MemoryStore(HashMap::from([(c_chunk.to_bytes(), chunk), (c_root.to_bytes(), root)])),
+            AtomicUsize::new(0),
+        );
+
+        let file = read_file(&store, &c_root, 1024, 16).await.unwrap();
+        assert_eq!(file, b"0000".repeat(8));
+        assert_eq!(store.1.load(Ordering::SeqCst), 2, "each block fetched once");
+
+        // The root and its eight links are nine blocks
+        let err = read_file(&store, &c_root, 1024, 8).await.unwrap_err();
+        assert!(err.to_string().contains("more than 8 blocks"), "{}", err);
+        assert!(read_file(&store, &c_root, 1024, 9).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn reads_inline_and_single_block_files() {
+        let inline = Cid { codec: CODEC_RAW, hash_code: HASH_IDENTITY, digest: b"tiny".to_vec() };
+        let empty = MemoryStore(HashMap::new());
+        assert_eq!(read_file(&empty, &inline, 1024, 16).await.unwrap(), b"tiny");
+
+        let node = file_node(b"single block", &[]);
+        let cid = sha256_cid(CODEC_DAG_PB, &node);
+        let store = MemoryStore(HashMap::from([(cid.to_bytes(), node)]));
+        assert_eq!(read_file(&store, &cid, 1024, 16).await.unwrap(), b"single block");
+    }
+}
@@ -0,0 +1,431 @@
+//! Persistent record of what the oracle has attempted for each InvoiceRequest,
+//! so failures are retried with backoff across restarts instead of being
+//! printed and forgotten. Jobs that keep failing are dead-lettered until an
+//! operator requeues them (`invoice-oracle-backend jobs requeue <request>`).
+
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use invoice_claim_client::InvoiceStatus;
+use solana_sdk::pubkey::Pubkey;
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 8;
+const DEFAULT_RETRY_BASE_SECS: i64 = 30;
+const MAX_RETRY_DELAY_SECS: i64 = 3600;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS jobs (
+    request         TEXT PRIMARY KEY,   -- InvoiceRequest pubkey
+    invoice         TEXT,               -- InvoiceAccount PDA
+    stage           TEXT NOT NULL,      -- next stage to run
+    state           TEXT NOT NULL,      -- pending | done | dead
+    attempts        INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at INTEGER NOT NULL DEFAULT 0,
+    last_error      TEXT,
+    created_at      INTEGER NOT NULL,
+    updated_at      INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS jobs_due ON jobs (state, next_attempt_at);
+CREATE INDEX IF NOT EXISTS jobs_invoice ON jobs (invoice);
+CREATE TABLE IF NOT EXISTS job_signatures (
+    request    TEXT NOT NULL,
+    stage      TEXT NOT NULL,
+    signature  TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
+";
+
+/// Pipeline stages, in order. `Enqueue` waits on the VRF callback or an
+/// audit, so it is driven by the settlement pass rather than the job runner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Extract,
+    FundEscrow,
+    RequestVrf,
+    Enqueue,
+}
+
+impl Stage {
+    fn as_str(self) -> &'static str {
+        match self {
+            Stage::Extract => "extract",
+            Stage::FundEscrow => "fund_escrow",
+            Stage::RequestVrf => "request_vrf",
+            Stage::Enqueue => "enqueue",
+        }
+    }
+}
+
+impl FromStr for Stage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "extract" => Ok(Stage::Extract),
+            "fund_escrow" => Ok(Stage::FundEscrow),
+            "request_vrf" => Ok(Stage::RequestVrf),
+            "enqueue" => Ok(Stage::Enqueue),
+            other => Err(format!("unknown job stage '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The stage an invoice in `status` still needs from the oracle, or None when
+/// there is nothing left to do (held for review or audit, settled, refunded,
+/// or escrow left for the org to fund). Deciding from on-chain state means a
+/// stage that landed just before a restart is not repeated. An audited invoice
+/// the auditors approve is queued by the settlement pass without a job.
+pub fn next_stage(status: InvoiceStatus, auto_fund_escrow: bool, auto_request_vrf: bool) -> Option<Stage> {
+    match status {
+        InvoiceStatus::Validated if auto_fund_escrow => Some(Stage::FundEscrow),
+        InvoiceStatus::InEscrowAwaitingVRF if auto_request_vrf => Some(Stage::RequestVrf),
+        InvoiceStatus::InEscrowAwaitingVRF | InvoiceStatus::InEscrowReadyToSettle => Some(Stage::Enqueue),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobState {
+    Pending,
+    Done,
+    Dead,
+}
+
+impl JobState {
+    fn as_str(self) -> &'static str {
+        match self {
+            JobState::Pending => "pending",
+            JobState::Done => "done",
+            JobState::Dead => "dead",
+        }
+    }
+}
+
+impl FromStr for JobState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "pending" => Ok(JobState::Pending),
+            "done" => Ok(JobState::Done),
+            "dead" => Ok(JobState::Dead),
+            other => Err(format!("unknown job state '{}'", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Job {
+    pub request: Pubkey,
+    pub invoice: Option<Pubkey>,
+    pub stage: Stage,
+    pub state: JobState,
+    pub attempts: u32,
+    pub next_attempt_at: i64,
+    pub last_error: Option<String>,
+    pub updated_at: i64,
+}
+
+impl fmt::Display for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:<7} stage={:<11} attempts={} next_attempt_at={}",
+            self.request,
+            self.state.as_str(),
+            self.stage,
+            self.attempts,
+            self.next_attempt_at
+        )?;
+        if let Some(invoice) = self.invoice {
+            write!(f, " invoice={}", invoice)?;
+        }
+        if let Some(err) = &self.last_error {
+            write!(f, "\n    last error: {}", err)?;
+        }
+        Ok(())
+    }
+}
+
+pub type JobResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+pub struct JobStore {
+    conn: Connection,
+    max_attempts: u32,
+    retry_base_secs: i64,
+}
+
+impl JobStore {
+    /// Open (creating if needed) the store at `JOB_STORE_PATH`, with retry
+    /// policy from `JOB_MAX_ATTEMPTS` and `JOB_RETRY_BASE_SECS`.
+    pub fn from_env() -> JobResult<Self> {
+        let path = std::env::var("JOB_STORE_PATH").unwrap_or_else(|_| "oracle-jobs.sqlite3".to_string());
+        let mut store = JobStore::open(Path::new(&path))?;
+        if let Some(n) = std::env::var("JOB_MAX_ATTEMPTS").ok().and_then(|s| s.parse().ok()) {
+            store.max_attempts = n;
+        }
+        if let Some(n) = std::env::var("JOB_RETRY_BASE_SECS").ok().and_then(|s| s.parse().ok()) {
+            store.retry_base_secs = n;
+        }
+        Ok(store)
+    }
+
+    pub fn open(path: &Path) -> JobResult<Self> {
+        let conn = Connection::open(path)?;
+        // The `jobs` command may open the store while the oracle is running
+        conn.busy_timeout(Duration::from_secs(5))?;
+        JobStore::with_connection(conn)
+    }
+
+    fn with_connection(conn: Connection) -> JobResult<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(JobStore { conn, max_attempts: DEFAULT_MAX_ATTEMPTS, retry_base_secs: DEFAULT_RETRY_BASE_SECS })
+    }
+
+    /// Start tracking `request` at the Extract stage; a no-op if it is known.
+    pub fn ensure(&self, request: &Pubkey, now: i64) -> JobResult<()> {
+        self.conn.execute(
+            "INSERT OR IGNORE INTO jobs (request, stage, state, created_at, updated_at)
+             VALUES (?1, ?2, 'pending', ?3, ?3)",
+            params![request.to_string(), Stage::Extract.as_str(), now],
+        )?;
+        Ok(())
+    }
+
+    pub fn get(&self, request: &Pubkey) -> JobResult<Option<Job>> {
+        Ok(self
+            .conn
+            .query_row("SELECT * FROM jobs WHERE request = ?1", [request.to_string()], row_to_job)
+            .optional()?)
+    }
+
+    /// Pending jobs whose backoff has elapsed, oldest first. Jobs waiting on
+    /// the settlement pass (`Stage::Enqueue`) are left out.
+    pub fn due(&self, now: i64) -> JobResult<Vec<Job>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT * FROM jobs WHERE state = 'pending' AND stage != ?1 AND next_attempt_at <= ?2
+             ORDER BY created_at",
+        )?;
+        let jobs = stmt
+            .query_map(params![Stage::Enqueue.as_str(), now], row_to_job)?
+            .collect::<Result<_, _>>()?;
+        Ok(jobs)
+    }
+
+    /// Unfinished (pending or dead) jobs at the Enqueue stage, keyed by invoice.
+    pub fn awaiting_enqueue(&self) -> JobResult<HashMap<Pubkey, Job>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT * FROM jobs WHERE stage = ?1 AND state != 'done' AND invoice IS NOT NULL")?;
+        let jobs = stmt
+            .query_map([Stage::Enqueue.as_str()], row_to_job)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(jobs.into_iter().filter_map(|job| Some((job.invoice?, job))).collect())
+    }
+
+    /// All jobs in `state` (every job when None), most recently updated first.
+    pub fn list(&self, state: Option<JobState>) -> JobResult<Vec<Job>> {
+        let mut stmt =
+            self.conn.prepare("SELECT * FROM jobs WHERE ?1 IS NULL OR state = ?1 ORDER BY updated_at DESC")?;
+        let jobs = stmt.query_map([state.map(JobState::as_str)], row_to_job)?.collect::<Result<_, _>>()?;
+        Ok(jobs)
+    }
+
+    /// Transaction signatures recorded for `request`, as (stage, signature).
+    pub fn signatures(&self, request: &Pubkey) -> JobResult<Vec<(String, String)>> {
+        let mut stmt =
+            self.conn.prepare("SELECT stage, signature FROM job_signatures WHERE request = ?1 ORDER BY rowid")?;
+        let sigs = stmt
+            .query_map([request.to_string()], |r| Ok((r.get(0)?, r.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+        Ok(sigs)
+    }
+
+    pub fn set_invoice(&self, request: &Pubkey, invoice: &Pubkey, now: i64) -> JobResult<()> {
+        self.conn.execute(
+            "UPDATE jobs SET invoice = ?2, updated_at = ?3 WHERE request = ?1",
+            params![request.to_string(), invoice.to_string(), now],
+        )?;
+        Ok(())
+    }
+
+    pub fn record_signature(&self, request: &Pubkey, stage: Stage, signature: &str, now: i64) -> JobResult<()> {
+        self.conn.execute(
+            "INSERT INTO job_signatures (request, stage, signature, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![request.to_string(), stage.as_str(), signature, now],
+        )?;
+        Ok(())
+    }
+
+    /// Move on to `next` (or finish when None), resetting the attempt count.
+    pub fn advance(&self, request: &Pubkey, next: Option<Stage>, now: i64) -> JobResult<()> {
+        let (stage, state) = match next {
+            Some(stage) => (Some(stage.as_str()), JobState::Pending),
+            None => (None, JobState::Done),
+        };
+        self.conn.execute(
+            "UPDATE jobs SET stage = COALESCE(?2, stage), state = ?3, attempts = 0, next_attempt_at = 0,
+                 last_error = NULL, updated_at = ?4
+             WHERE request = ?1",
+            params![request.to_string(), stage, state.as_str(), now],
+        )?;
+        Ok(())
+    }
+
+    /// Record a failed attempt and schedule the retry, dead-lettering the job
+    /// once it has used up its attempts. Returns the job's new state.
+    pub fn fail(&self, request: &Pubkey, error: &str, now: i64) -> JobResult<JobState> {
+        let attempts = self.get(request)?.map_or(0, |j| j.attempts) + 1;
+        let state = if attempts >= self.max_attempts { JobState::Dead } else { JobState::Pending };
+        self.conn.execute(
+            "UPDATE jobs SET attempts = ?2, state = ?3, next_attempt_at = ?4, last_error = ?5, updated_at = ?6
+             WHERE request = ?1",
+            params![
+                request.to_string(),
+                attempts,
+                state.as_str(),
+                now + retry_delay(self.retry_base_secs, attempts),
+                error,
+                now
+            ],
+        )?;
+        Ok(state)
+    }
+
+    /// Put a dead (or stuck) job back in the queue at its current stage.
+    /// Returns false when the request is unknown.
+    pub fn requeue(&self, request: &Pubkey, now: i64) -> JobResult<bool> {
+        let n = self.conn.execute(
+            "UPDATE jobs SET state = 'pending', attempts = 0, next_attempt_at = 0, updated_at = ?2
+             WHERE request = ?1",
+            params![request.to_string(), now],
+        )?;
+        Ok(n > 0)
+    }
+}
+
+// Exponential backoff: base, 2x base, 4x base, ... capped at an hour
+fn retry_delay(base_secs: i64, attempts: u32) -> i64 {
+    let factor = 1i64.checked_shl(attempts.saturating_sub(1)).unwrap_or(i64::MAX);
+    base_secs.saturating_mul(factor).min(MAX_RETRY_DELAY_SECS)
+}
+
+fn row_to_job(row: &rusqlite::Row) -> rusqlite::Result<Job> {
+    fn parse<T: FromStr>(s: String) -> rusqlite::Result<T>
+    where
+        T::Err: fmt::Display,
+    {
+        s.parse().map_err(|e: T::Err| {
+            rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, e.to_string().into())
+        })
+    }
+
+    Ok(Job {
+        request: parse(row.get("request")?)?,
+        invoice: row.get::<_, Option<String>>("invoice")?.map(parse).transpose()?,
+        stage: parse(row.get("stage")?)?,
+        state: parse(row.get("state")?)?,
+        attempts: row.get("attempts")?,
+        next_attempt_at: row.get("next_attempt_at")?,
+        last_error: row.get("last_error")?,
+        updated_at: row.get("updated_at")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> JobStore {
+        JobStore::with_connection(Connection::open_in_memory().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn tracks_a_job_through_its_stages() {
+        let store = store();
+        let (request, invoice) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        store.ensure(&request, 100).unwrap();
+        store.ensure(&request, 200).unwrap(); // already known
+        assert_eq!(store.due(100).unwrap().len(), 1);
+
+        store.set_invoice(&request, &invoice, 110).unwrap();
+        store.record_signature(&request, Stage::Extract, "sig1", 110).unwrap();
+        store.advance(&request, Some(Stage::FundEscrow), 110).unwrap();
+
+        let job = store.get(&request).unwrap().unwrap();
+        assert_eq!((job.invoice, job.stage, job.state), (Some(invoice), Stage::FundEscrow, JobState::Pending));
+
+        // Jobs waiting on the settlement pass are not run by the job runner
+        store.advance(&request, Some(Stage::Enqueue), 120).unwrap();
+        assert!(store.due(120).unwrap().is_empty());
+        let waiting = store.awaiting_enqueue().unwrap();
+        assert_eq!(waiting.get(&invoice).map(|job| job.request), Some(request));
+
+        store.advance(&request, None, 130).unwrap();
+        let job = store.get(&request).unwrap().unwrap();
+        assert_eq!((job.stage, job.state), (Stage::Enqueue, JobState::Done));
+        assert!(store.awaiting_enqueue().unwrap().is_empty());
+        assert_eq!(store.signatures(&request).unwrap(), vec![("extract".to_string(), "sig1".to_string())]);
+    }
+
+    #[test]
+    fn backs_off_then_dead_letters_and_requeues() {
+        let mut store = store();
+        store.max_attempts = 3;
+        let request = Pubkey::new_unique();
+        store.ensure(&request, 0).unwrap();
+
+        assert_eq!(store.fail(&request, "rpc timeout", 1000).unwrap(), JobState::Pending);
+        let job = store.get(&request).unwrap().unwrap();
+        assert_eq!((job.attempts, job.next_attempt_at), (1, 1030));
+        assert!(store.due(1029).unwrap().is_empty());
+        assert_eq!(store.due(1030).unwrap().len(), 1);
+
+        assert_eq!(store.fail(&request, "rpc timeout", 1030).unwrap(), JobState::Pending);
+        assert_eq!(store.get(&request).unwrap().unwrap().next_attempt_at, 1090);
+
+        assert_eq!(store.fail(&request, "still broken", 1090).unwrap(), JobState::Dead);
+        assert!(store.due(i64::MAX).unwrap().is_empty());
+        let dead = store.list(Some(JobState::Dead)).unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].last_error.as_deref(), Some("still broken"));
+
+        assert!(store.requeue(&request, 2000).unwrap());
+        let job = store.get(&request).unwrap().unwrap();
+        assert_eq!((job.state, job.attempts, job.stage), (JobState::Pending, 0, Stage::Extract));
+        assert!(!store.requeue(&Pubkey::new_unique(), 2000).unwrap());
+    }
+
+    #[test]
+    fn picks_the_next_stage_from_invoice_status() {
+        assert_eq!(next_stage(InvoiceStatus::Validated, true, true), Some(Stage::FundEscrow));
+        assert_eq!(next_stage(InvoiceStatus::Validated, false, true), None);
+        assert_eq!(next_stage(InvoiceStatus::InEscrowAwaitingVRF, false, true), Some(Stage::RequestVrf));
+        assert_eq!(next_stage(InvoiceStatus::InEscrowAwaitingVRF, true, false), Some(Stage::Enqueue));
+        assert_eq!(next_stage(InvoiceStatus::InEscrowReadyToSettle, true, true), Some(Stage::Enqueue));
+        assert_eq!(next_stage(InvoiceStatus::InEscrowAuditPending, true, true), None);
+        assert_eq!(next_stage(InvoiceStatus::Refunded, true, true), None);
+        assert_eq!(next_stage(InvoiceStatus::ReviewRejected, true, true), None);
+        assert_eq!(next_stage(InvoiceStatus::NeedsReview, true, true), None);
+        assert_eq!(next_stage(InvoiceStatus::Paid, true, true), None);
+    }
+
+    #[test]
+    fn caps_retry_delay() {
+        assert_eq!(retry_delay(30, 1), 30);
+        assert_eq!(retry_delay(30, 4), 240);
+        assert_eq!(retry_delay(30, 20), MAX_RETRY_DELAY_SECS);
+        assert_eq!(retry_delay(30, 200), MAX_RETRY_DELAY_SECS);
+    }
+}
@@ -0,0 +1,149 @@
+use futures_util::StreamExt;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_client::rpc_config::{
+    RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcTransactionLogsConfig,
+    RpcTransactionLogsFilter,
+};
+use solana_client::rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType};
+use solana_sdk::{account::Account, commitment_config::CommitmentConfig, pubkey::Pubkey};
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use invoice_claim_client::{discriminator, InvoiceRequest, ProgramAccount, RequestStatus};
+
+// Backoff between websocket reconnect attempts
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+// Program events after which an invoice may need queueing, settling or refunding
+const TRANSITION_EVENTS: [&str; 3] = ["AuditSelected", "AuditDecided", "QueuedForPayment"];
+
+/// What the subscriptions saw on chain.
+#[derive(Debug)]
+pub enum ChainEvent {
+    /// An InvoiceRequest account was created or updated and is still Pending.
+    PendingRequest(Pubkey),
+    /// An invoice moved to a status the settlement/refund passes act on.
+    InvoiceTransition(String),
+}
+
+/// Subscribe to InvoiceRequest account updates (`programSubscribe`) and to
+/// program logs (`logsSubscribe`), forwarding anything actionable to `tx`.
+///
+/// Runs until the receiver is dropped; reconnects when the socket closes.
+pub async fn run_listener(
+    ws_url: String,
+    program_id: Pubkey,
+    commitment: CommitmentConfig,
+    tx: UnboundedSender<ChainEvent>,
+) {
+    loop {
+        match listen(&ws_url, &program_id, commitment, &tx).await {
+            Ok(()) => eprintln!("⚠️ PubSub stream closed, reconnecting..."),
+            Err(e) => eprintln!("❌ PubSub error: {}, reconnecting...", e),
+        }
+        if tx.is_closed() {
+            return;
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn listen(
+    ws_url: &str,
+    program_id: &Pubkey,
+    commitment: CommitmentConfig,
+    tx: &UnboundedSender<ChainEvent>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let client = PubsubClient::new(ws_url).await?;
+
+    let request_filter = RpcFilterType::Memcmp(Memcmp::new(
+        0,
+        MemcmpEncodedBytes::Base64(STANDARD.encode(InvoiceRequest::discriminator())),
+    ));
+    let (mut accounts, accounts_unsub) = client
+        .program_subscribe(
+            program_id,
+            Some(RpcProgramAccountsConfig {
+                filters: Some(vec![request_filter]),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    commitment: Some(commitment),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+        )
+        .await?;
+
+    let (mut logs, logs_unsub) = client
+        .logs_subscribe(
+            RpcTransactionLogsFilter::Mentions(vec![program_id.to_string()]),
+            RpcTransactionLogsConfig {
+                commitment: Some(commitment),
+            },
+        )
+        .await?;
+
+    println!("📡 Subscribed to program accounts and logs via {}", ws_url);
+
+    let transitions: Vec<([u8; 8], &str)> = TRANSITION_EVENTS
+        .iter()
+        .map(|name| (discriminator(&format!("event:{}", name)), *name))
+        .collect();
+
+    loop {
+        tokio::select! {
+            update = accounts.next() => {
+                let Some(update) = update else { break };
+                let keyed = update.value;
+                let Ok(pubkey) = Pubkey::from_str(&keyed.pubkey) else { continue };
+                let Some(account) = keyed.account.decode::<Account>() else { continue };
+                match InvoiceRequest::try_from_account_data(&account.data) {
+                    Ok(request) if request.status == RequestStatus::Pending => {
+                        if tx.send(ChainEvent::PendingRequest(pubkey)).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => println!("⚠️ Could not decode InvoiceRequest {}: {}", pubkey, e),
+                }
+            }
+            notification = logs.next() => {
+                let Some(notification) = notification else { break };
+                let logs = notification.value;
+                if logs.err.is_some() {
+                    continue;
+                }
+                if let Some(name) = find_event(&logs.logs, &transitions) {
+                    if tx.send(ChainEvent::InvoiceTransition(name.to_string())).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    drop(accounts);
+    drop(logs);
+    accounts_unsub().await;
+    logs_unsub().await;
+    Ok(())
+}
+
+/// First emitted event in `logs` whose discriminator is in `wanted`.
+fn find_event<'a>(logs: &[String], wanted: &[([u8; 8], &'a str)]) -> Option<&'a str> {
+    logs.iter()
+        .filter_map(|line| line.strip_prefix("Program data: "))
+        .filter_map(|b64| STANDARD.decode(b64).ok())
+        .filter(|data| data.len() >= 8)
+        .find_map(|data| {
+            wanted
+                .iter()
+                .find(|(disc, _)| data[..8] == disc[..])
+                .map(|(_, name)| *name)
+        })
+}
@@ -1,380 +1,631 @@
 mod escrow;
 mod payment_queue;
 mod cranker;
+mod refund;
+mod invoices;
+mod listener;
+mod ocr;
+mod ipfs;
+mod extraction;
+mod amount;
+mod duplicates;
+mod jobs;
+mod config;
 
 use std::env;
 use solana_sdk::pubkey::Pubkey;
-use solana_sdk::signature::{read_keypair_file, Keypair, Signer};
-use solana_sdk::system_program;
-use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::signature::{Keypair, Signature, Signer};
 use solana_sdk::transaction::Transaction;
-use regex::Regex;
 use solana_client::rpc_client::RpcClient;
+use std::collections::HashSet;
 use std::str::FromStr;
-use std::time::Duration;
 use dotenvy::dotenv;
-use sha2::{Digest, Sha256};
-use std::fmt::Write as _;
+use invoice_claim_client::instructions as ix;
+use invoice_claim_client::{
+    pda, InvoiceAccount, InvoiceRequest, OrgConfig, ProgramAccount, RequestStatus, MAX_INVOICE_NUMBER_LEN,
+};
 use crate::cranker::run_cranker;
 use crate::escrow::fund_escrow_for_invoice;
-use crate::payment_queue::add_to_payment_queue;
-
-#[derive(Clone, Debug)]
-pub struct InvoiceRequest {
-    pub authority: Pubkey,
-    pub ipfs_hash: String,
-    pub status: RequestStatus,
-    pub timestamp: i64,
-    pub amount: u64,
-    pub nonce: u64,
+use crate::payment_queue::enqueue_ready_invoices;
+use crate::refund::run_refund_cranker;
+use crate::listener::{run_listener, ChainEvent};
+use crate::ocr::{Document, OcrProvider};
+use crate::ipfs::IpfsClient;
+use crate::extraction::Extractor;
+use crate::amount::{currency_matches, fetch_mint_decimals, format_base_units, mint_currency, parse_base_units};
+use crate::duplicates::Candidate;
+use crate::invoices::{get_org_invoices, get_vendor_invoices, read_vendor_org};
+use crate::jobs::{Job, JobState, JobStore, Stage};
+use crate::config::Config;
+
+// Net terms applied from the issue date when an invoice has no due date or terms of its own
+const DEFAULT_NET_TERMS_DAYS: u32 = 30;
+
+/// Turns a request's IPFS document into extracted invoice fields.
+struct Pipeline {
+    ipfs: IpfsClient,
+    ocr: Box<dyn OcrProvider>,
+    extractor: Extractor,
 }
 
-impl InvoiceRequest {
-    pub fn from_account_data(data: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
-        if data.len() < 8 {
-            return Err("Data too short".into());
-        }
-
-        let mut offset = 8;
-
-        // Read authority (32 bytes)
-        if offset + 32 > data.len() {
-            return Err("Not enough data for authority".into());
-        }
-        let authority = Pubkey::try_from(&data[offset..offset + 32])?;
-        offset += 32;
+/// What running one job stage produced.
+struct StageOutcome {
+    next: Option<Stage>,
+    invoice: Option<Pubkey>,
+    signature: Option<Signature>,
+}
 
-        // Read string length (4 bytes)
-        if offset + 4 > data.len() {
-            return Err("Not enough data for string length".into());
-        }
-        let str_len = u32::from_le_bytes([
-            data[offset], data[offset + 1], data[offset + 2], data[offset + 3]
-        ]) as usize;
-        offset += 4;
-
-        // Read string data
-        if offset + str_len > data.len() {
-            return Err("Not enough data for string".into());
-        }
-        let ipfs_hash = String::from_utf8(data[offset..offset + str_len].to_vec())?;
-        offset += str_len;
+#[tokio::main]
+async fn main() {
+    dotenv().ok();
 
-        // Read status (1 byte)
-        if offset >= data.len() {
-            return Err("Not enough data for status".into());
+    let matches = config::cli().get_matches();
+    if let Some(matches) = matches.subcommand_matches("jobs") {
+        if let Err(e) = run_jobs_command(matches) {
+            eprintln!("{}", e);
+            std::process::exit(1);
         }
-        let status = if data[offset] == 0 {
-            RequestStatus::Pending
-        } else {
-            RequestStatus::Completed
-        };
-        offset += 1;
+        return;
+    }
 
-        // Read timestamp (8 bytes)
-        if offset + 8 > data.len() {
-            return Err("Not enough data for timestamp".into());
-        }
-        let timestamp = i64::from_le_bytes([
-            data[offset], data[offset + 1], data[offset + 2], data[offset + 3],
-            data[offset + 4], data[offset + 5], data[offset + 6], data[offset + 7]
-        ]);
-        offset += 8;
-
-        // Read amount (8 bytes)
-        if offset + 8 > data.len() {
-            return Err("Not enough data for amount".into());
-        }
-        let amount = u64::from_le_bytes([
-            data[offset], data[offset + 1], data[offset + 2], data[offset + 3],
-            data[offset + 4], data[offset + 5], data[offset + 6], data[offset + 7]
-        ]);
-        offset += 8;
-
-        // Read nonce (u64)
-        let mut nonce: u64 = 0;
-        if offset + 8 <= data.len() {
-            nonce = u64::from_le_bytes([
-                data[offset], data[offset + 1], data[offset + 2], data[offset + 3],
-                data[offset + 4], data[offset + 5], data[offset + 6], data[offset + 7]
-            ]);
+    let config = match Config::load(&matches) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ Invalid configuration: {}", e);
+            std::process::exit(1);
         }
+    };
 
-        Ok(InvoiceRequest {
-            authority,
-            ipfs_hash,
-            status,
-            timestamp,
-            amount,
-            nonce,
-        })
+    if let Err(e) = run_oracle(config).await {
+        eprintln!("❌ Failed to start: {}", e);
+        std::process::exit(1);
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
-pub enum RequestStatus {
-    Pending,
-    Completed,
-}
-
-const PROGRAM_ID: &str = "HQ5y6ZMwNHSrRvma4bDHtay4UDW5qBM63A5mvyGi4MkH";
-const RPC_URL: &str = "https://api.devnet.solana.com";
-
-#[tokio::main]
-async fn main() {
+// Everything that can fail is set up before the loop starts; the loop itself
+// only reports errors
+async fn run_oracle(config: Config) -> Result<(), Box<dyn std::error::Error>> {
     println!("Invoice Oracle Backend Starting...");
-    dotenv().ok();
-
-    let keypair = read_keypair_file("phantom-keypair.json")
-        .expect("Failed to read keypair file");
 
+    let keypair = config.keypair.load()?;
     println!("Oracle wallet: {}", keypair.pubkey());
 
-    let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
-    let rpc_client = RpcClient::new(RPC_URL.to_string());
+    let program_id = config.program_id;
+    let rpc_client = RpcClient::new_with_commitment(config.cluster_url.clone(), config.commitment);
 
+    println!("Cluster: {} ({:?})", config.cluster_url, config.commitment.commitment);
     println!("Watching program: {}", program_id);
-    println!("Polling every 5 seconds...\n");
+    for org in &config.orgs {
+        println!("Configured org {} ({}, OrgConfig {})", org.name.as_deref().unwrap_or("unnamed"), org.authority, org.config);
+    }
+    println!("Serving {} of {} configured orgs", served_orgs(&rpc_client, &keypair, &config).len(), config.orgs.len());
+
+    let ocr = ocr::provider_from_env().map_err(|e| format!("OCR provider: {}", e))?;
+    println!("OCR provider: {}", ocr.name());
 
-    let mut poll_count = 0;
+    let extractor = match env::var("EXTRACTION_TEMPLATES") {
+        Ok(path) => Extractor::from_file(path.as_ref()),
+        Err(_) => Extractor::new(vec![]),
+    }
+    .map_err(|e| format!("extraction templates: {}", e))?;
+
+    let pipeline = Pipeline { ipfs: IpfsClient::from_env(), ocr, extractor };
+    let jobs = JobStore::from_env().map_err(|e| format!("job store: {}", e))?;
+
+    println!("Reconciling every {} seconds...\n", config.poll_interval.as_secs());
+
+    let (event_tx, mut event_rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(run_listener(config.ws_url.clone(), program_id, config.commitment, event_tx));
+
+    let mut reconcile = tokio::time::interval(config.poll_interval);
+    let mut sweep_count = 0;
 
     loop {
-        poll_count += 1;
-        println!("Poll #{} - Checking for new requests...", poll_count);
-
-        match process_pending_requests(&rpc_client, &keypair, &program_id).await {
-            Ok(processed) => {
-                if processed > 0 {
-                    println!("✅ Processed {} requests", processed);
-                } else {
-                    println!("No pending requests found");
+        tokio::select! {
+            Some(event) = event_rx.recv() => match event {
+                ChainEvent::PendingRequest(pubkey) => {
+                    println!("\n📨 New pending request: {}", pubkey);
+                    let served = served_orgs(&rpc_client, &keypair, &config);
+                    match run_request_job(&rpc_client, &keypair, &pipeline, &jobs, &config, &served, &pubkey).await {
+                        Ok(true) => println!("✅ Processed request {}", pubkey),
+                        Ok(false) => {}
+                        Err(e) => eprintln!("❌ Failed to run job for request {}: {}", pubkey, e),
+                    }
+                }
+                ChainEvent::InvoiceTransition(name) => {
+                    println!("\n📨 {} event, running settlement passes", name);
+                    let served = served_orgs(&rpc_client, &keypair, &config);
+                    run_settlement_passes(&rpc_client, &keypair, &jobs, &program_id, &served);
+                }
+            },
+            _ = reconcile.tick() => {
+                sweep_count += 1;
+                println!("Sweep #{} - Reconciling requests and settlements...", sweep_count);
+                let served = served_orgs(&rpc_client, &keypair, &config);
+
+                match process_pending_requests(&rpc_client, &keypair, &pipeline, &jobs, &config, &served).await {
+                    Ok(processed) => {
+                        if processed > 0 {
+                            println!("✅ Processed {} requests", processed);
+                        } else {
+                            println!("No pending requests found");
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("❌ Error in processing requests: {}", e);
+                    }
                 }
+
+                run_settlement_passes(&rpc_client, &keypair, &jobs, &program_id, &served);
             }
-            Err(e) => {
-                eprintln!("❌ Error in processing requests: {}", e);
+        }
+    }
+}
+
+// `jobs [list | dead | show <request> | requeue <request>]`: inspect the job
+// store and put dead-lettered requests back in the queue
+fn run_jobs_command(matches: &clap::ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let jobs = JobStore::from_env()?;
+    let request = |matches: &clap::ArgMatches| -> Result<Pubkey, Box<dyn std::error::Error>> {
+        let request = matches.get_one::<String>("request").expect("required by clap");
+        Pubkey::from_str(request).map_err(|_| format!("{:?} is not a valid request pubkey", request).into())
+    };
+
+    match matches.subcommand() {
+        None | Some(("list", _)) => jobs.list(None)?.iter().for_each(|job| println!("{}", job)),
+        Some(("dead", _)) => jobs.list(Some(JobState::Dead))?.iter().for_each(|job| println!("{}", job)),
+        Some(("show", matches)) => {
+            let request = request(matches)?;
+            let job = jobs.get(&request)?.ok_or_else(|| format!("No job for request {}", request))?;
+            println!("{}", job);
+            for (stage, sig) in jobs.signatures(&request)? {
+                println!("    {} tx: {}", stage, sig);
+            }
+        }
+        Some(("requeue", matches)) => {
+            let request = request(matches)?;
+            if !jobs.requeue(&request, chrono::Utc::now().timestamp())? {
+                return Err(format!("No job for request {}", request).into());
             }
+            println!("Requeued {}; the running oracle picks it up on its next sweep", request);
         }
+        Some((other, _)) => unreachable!("unknown jobs subcommand {}", other),
+    }
+    Ok(())
+}
 
-        match run_cranker(&rpc_client, &keypair, &program_id) {
-            Ok(_) => println!("✅ Cranker run completed successfully"),
-            Err(e) => eprintln!("❌ Error in cranker run: {}", e),
+// OrgConfig PDAs of the configured orgs that name this oracle as an oracle
+// signer. Requests and invoices of any other org are left alone, so one
+// deployment can serve several tenants without acting for the wrong one.
+// Re-read each pass since an org can rotate its oracle signer at any time.
+fn served_orgs(rpc_client: &RpcClient, keypair: &Keypair, config: &Config) -> HashSet<Pubkey> {
+    let keys: Vec<Pubkey> = config.orgs.iter().map(|org| org.config).collect();
+    let accounts = match rpc_client.get_multiple_accounts(&keys) {
+        Ok(accounts) => accounts,
+        Err(e) => {
+            eprintln!("❌ Failed to fetch configured orgs: {}", e);
+            return HashSet::new();
         }
+    };
 
-        tokio::time::sleep(Duration::from_secs(5)).await;
+    let mut served = HashSet::new();
+    for (org, account) in config.orgs.iter().zip(accounts) {
+        match account.map(|a| OrgConfig::try_from_account_data(&a.data)) {
+            Some(Ok(org_config)) if org_config.is_oracle(&keypair.pubkey()) => {
+                served.insert(org.config);
+            }
+            Some(Ok(_)) => eprintln!("⚠️  Not an oracle signer for org {}; skipping it", org.authority),
+            Some(Err(e)) => eprintln!("⚠️  Could not decode OrgConfig {}: {}", org.config, e),
+            None => eprintln!("⚠️  Org {} has no OrgConfig on chain", org.authority),
+        }
     }
+    served
 }
 
-async fn process_pending_requests(
+// Queue ready invoices, settle due ones and refund rejected escrows
+fn run_settlement_passes(
     rpc_client: &RpcClient,
     keypair: &Keypair,
+    jobs: &JobStore,
     program_id: &Pubkey,
-) -> Result<usize, Box<dyn std::error::Error>> {
-    println!("Fetching program accounts...");
-    let accounts = rpc_client.get_program_accounts(program_id)?;
-    println!("Found {} total accounts for this program", accounts.len());
-
-    let mut processed = 0;
-
-    let mut h = Sha256::new();
-    h.update(b"account:InvoiceRequest");
-    let invoice_request_disc: [u8; 8] = h.finalize()[..8].try_into().unwrap();
+    served: &HashSet<Pubkey>,
+) {
+    // One fetch serves both invoice passes; the cranker settles from the queue
+    let invoices = match get_org_invoices(rpc_client, program_id, served) {
+        Ok(invoices) => invoices,
+        Err(e) => {
+            eprintln!("❌ Failed to fetch invoices: {}", e);
+            Default::default()
+        }
+    };
 
-    for (pubkey, account) in accounts {
-        println!("\n🔍 Checking account: {}", pubkey);
-        println!("   Data length: {} bytes", account.data.len());
+    match enqueue_ready_invoices(rpc_client, keypair, program_id, jobs, &invoices) {
+        Ok(n) if n > 0 => println!("✅ Queued {} invoices for settlement", n),
+        Ok(_) => {}
+        Err(e) => eprintln!("❌ Error queueing ready invoices: {}", e),
+    }
 
-        if account.data.len() < 8 {
-            println!("   ⚠️ Account too small, skipping");
-            continue;
-        }
+    match run_cranker(rpc_client, keypair, program_id, served) {
+        Ok(n) if n > 0 => println!("✅ Settled {} due invoices", n),
+        Ok(_) => {}
+        Err(e) => eprintln!("❌ Error in cranker run: {}", e),
+    }
 
-        let disc = &account.data[..8];
-        println!("   Discriminator: {:02x?}", disc);
+    match run_refund_cranker(rpc_client, keypair, program_id, &invoices) {
+        Ok(n) if n > 0 => println!("✅ Refunded {} rejected or expired escrows", n),
+        Ok(_) => {}
+        Err(e) => eprintln!("❌ Error in refund run: {}", e),
+    }
+}
 
-        if disc != &invoice_request_disc {
-            println!("   ℹ️ Not an InvoiceRequest account, skipping");
-            continue;
-        }
+// Start a job for every Pending request, then run each job that is due:
+// new requests as well as later stages and retries of earlier ones.
+async fn process_pending_requests(
+    rpc_client: &RpcClient,
+    keypair: &Keypair,
+    pipeline: &Pipeline,
+    jobs: &JobStore,
+    config: &Config,
+    served: &HashSet<Pubkey>,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    use solana_account_decoder::UiAccountEncoding;
+    use solana_client::rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType};
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    // Only InvoiceRequest accounts, so the sweep doesn't grow with every invoice/vendor/queue entry
+    let filter = RpcFilterType::Memcmp(Memcmp::new(
+        0,
+        MemcmpEncodedBytes::Base64(STANDARD.encode(InvoiceRequest::discriminator())),
+    ));
+    let accounts = rpc_client.get_program_accounts_with_config(
+        &config.program_id,
+        solana_client::rpc_config::RpcProgramAccountsConfig {
+            filters: Some(vec![filter]),
+            account_config: solana_client::rpc_config::RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )?;
+    println!("Found {} InvoiceRequest accounts", accounts.len());
 
-        match InvoiceRequest::from_account_data(&account.data) {
+    let now = chrono::Utc::now().timestamp();
+    for (pubkey, account) in accounts {
+        match InvoiceRequest::try_from_account_data(&account.data) {
             Ok(request) => {
-                println!("Successfully deserialized InvoiceRequest");
-                println!("Authority: {}", request.authority);
-                println!("IPFS: {}", request.ipfs_hash);
-                println!("Status: {:?}", request.status);
-                println!("Nonce: {}", request.nonce);
-
-                let decimals: u8 = env::var("MINT_DECIMALS")
-                    .ok()
-                    .and_then(|s| s.parse().ok())
-                    .unwrap_or(6);
-                log_amount("Request amount", request.amount, decimals);
-
-                if matches!(request.status, RequestStatus::Pending) {
-                    println!("\nFound PENDING request!");
-                    match extract_and_submit(rpc_client, keypair, program_id, &request, &pubkey).await {
-                        Ok(_) => {
-                            println!("Successfully processed!");
-                            processed += 1;
-                        }
-                        Err(e) => eprintln!("Failed: {}", e),
-                    }
-                } else {
-                    println!("Already completed, skipping");
+                if matches!(request.status, RequestStatus::Pending) && served.contains(&request.org) {
+                    jobs.ensure(&pubkey, now)?;
                 }
             }
-            Err(e) => println!("Failed to deserialize InvoiceRequest: {}", e),
+            Err(e) => println!("Failed to deserialize InvoiceRequest {}: {}", pubkey, e),
+        }
+    }
+
+    let mut processed = 0;
+    for job in jobs.due(now)? {
+        println!("\nRunning job for request {} at stage {}", job.request, job.stage);
+        if run_job(rpc_client, keypair, pipeline, jobs, config, &job).await? {
+            processed += 1;
         }
     }
 
     Ok(processed)
 }
 
-async fn extract_and_submit(
+// Start (or resume) the job for a request reported by the subscription
+async fn run_request_job(
     rpc_client: &RpcClient,
     keypair: &Keypair,
-    program_id: &Pubkey,
-    request: &InvoiceRequest,
+    pipeline: &Pipeline,
+    jobs: &JobStore,
+    config: &Config,
+    served: &HashSet<Pubkey>,
     request_pubkey: &Pubkey,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let api_key = env::var("OCR_API_KEY")
-        .expect("OCR_API_KEY must be set in .env file");
-
-    let ocr_filetype = env::var("OCR_FILETYPE").unwrap_or_else(|_| "pdf".to_string());
-    let gateways_csv = env::var("IPFS_GATEWAYS").unwrap_or_else(|_|
-        "https://emerald-abundant-baboon-978.mypinata.cloud/ipfs,https://ipfs.io/ipfs,https://gateway.pinata.cloud/ipfs".to_string()
-    );
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let request = InvoiceRequest::try_from_account_data(&rpc_client.get_account(request_pubkey)?.data)?;
+    if !served.contains(&request.org) {
+        println!("Request {} is for org {}, which this oracle does not serve; skipping", request_pubkey, request.org);
+        return Ok(false);
+    }
 
-    println!("\nCalling OCR API...");
-    let client = reqwest::Client::new();
-    let mut json: serde_json::Value = serde_json::json!({});
-    let mut last_err: Option<String> = None;
-    let mut tried = Vec::new();
-
-    for gw in gateways_csv.split(',') {
-        let gw = gw.trim();
-        if gw.is_empty() { continue; }
-
-        let ipfs_url = format!(
-            "{}/{}?filename=invoice.{}",
-            gw.trim_end_matches('/'),
-            request.ipfs_hash,
-            ocr_filetype
-        );
+    let now = chrono::Utc::now().timestamp();
+    jobs.ensure(request_pubkey, now)?;
+    match jobs.due(now)?.into_iter().find(|job| job.request == *request_pubkey) {
+        Some(job) => run_job(rpc_client, keypair, pipeline, jobs, config, &job).await,
+        None => {
+            println!("Job for request {} is not due, skipping", request_pubkey);
+            Ok(false)
+        }
+    }
+}
 
-        let ocr_url = format!(
-            "https://api.ocr.space/parse/imageurl?apikey={}&url={}&language=eng&OCREngine=2&filetype={}",
-            api_key,
-            ipfs_url,
-            ocr_filetype
-        );
+// Run `job` stage by stage until it finishes, fails, or reaches the Enqueue
+// stage the settlement pass drives. Every outcome is written to the store, so
+// a failure is retried after its backoff and a restart resumes where it left
+// off. Returns whether the job made it through; errors are job store errors.
+async fn run_job(
+    rpc_client: &RpcClient,
+    keypair: &Keypair,
+    pipeline: &Pipeline,
+    jobs: &JobStore,
+    config: &Config,
+    job: &Job,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut stage = job.stage;
+    let mut invoice = job.invoice;
 
-        println!("Trying OCR via gateway: {}", gw);
-        tried.push(gw.to_string());
-
-        match client.get(&ocr_url).send().await {
-            Ok(resp) => {
-                if let Ok(v) = resp.json::<serde_json::Value>().await {
-                    let errored = v.get("IsErroredOnProcessing")
-                        .and_then(|b| b.as_bool())
-                        .unwrap_or(false);
-
-                    if !errored {
-                        json = v;
-                        println!("OCR succeeded via {}", gw);
-                        break;
-                    } else {
-                        last_err = Some(format!("OCR error via {}: {:?}", gw, v.get("ErrorMessage")));
-                    }
+    loop {
+        let outcome = run_stage(rpc_client, keypair, pipeline, config, &job.request, invoice, stage).await;
+        let now = chrono::Utc::now().timestamp();
+        let outcome = match outcome {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                match jobs.fail(&job.request, &e.to_string(), now)? {
+                    JobState::Dead => eprintln!(
+                        "☠️  Request {} failed at stage {} and was dead-lettered: {}",
+                        job.request, stage, e
+                    ),
+                    _ => eprintln!("❌ Request {} failed at stage {}, will retry: {}", job.request, stage, e),
                 }
+                return Ok(false);
+            }
+        };
+
+        if let Some(invoice_pda) = outcome.invoice {
+            jobs.set_invoice(&job.request, &invoice_pda, now)?;
+            invoice = Some(invoice_pda);
+        }
+        if let Some(sig) = outcome.signature {
+            jobs.record_signature(&job.request, stage, &sig.to_string(), now)?;
+        }
+        jobs.advance(&job.request, outcome.next, now)?;
+
+        match outcome.next {
+            Some(Stage::Enqueue) => {
+                println!("Request {} is waiting to be queued for settlement", job.request);
+                return Ok(true);
+            }
+            Some(next) => stage = next,
+            None => {
+                println!("Request {} needs nothing further from the oracle", job.request);
+                return Ok(true);
             }
-            Err(e) => last_err = Some(format!("HTTP error via {}: {}", gw, e)),
         }
     }
+}
+
+async fn run_stage(
+    rpc_client: &RpcClient,
+    keypair: &Keypair,
+    pipeline: &Pipeline,
+    config: &Config,
+    request_pubkey: &Pubkey,
+    invoice: Option<Pubkey>,
+    stage: Stage,
+) -> Result<StageOutcome, Box<dyn std::error::Error>> {
+    if stage == Stage::Extract {
+        return handle_pending_request(rpc_client, keypair, pipeline, config, request_pubkey).await;
+    }
+
+    let invoice_pda = invoice.ok_or_else(|| format!("no invoice recorded for request {}", request_pubkey))?;
+    let inv = InvoiceAccount::try_from_account_data(&rpc_client.get_account(&invoice_pda)?.data)?;
 
-    if json.as_object().map(|m| m.is_empty()).unwrap_or(true) {
-        return Err(format!(
-            "OCR failed across gateways (tried: {}): {}",
-            tried.join(","),
-            last_err.unwrap_or_else(|| "unknown error".to_string())
-        ).into());
+    // Someone else may have moved the invoice on (e.g. the org funded escrow itself)
+    let planned = next_stage_for(inv.status);
+    if planned != Some(stage) || stage == Stage::Enqueue {
+        println!("Invoice {} is {:?}; moving on from stage {}", invoice_pda, inv.status, stage);
+        return Ok(StageOutcome { next: planned, invoice: None, signature: None });
+    }
+
+    let program_id = &config.program_id;
+    let org_config_pda = read_vendor_org(rpc_client, &inv.vendor)?;
+    let (signature, next) = match stage {
+        Stage::FundEscrow => {
+            println!("\nAuto-funding escrow...");
+            let sig = fund_escrow_for_invoice(
+                rpc_client,
+                keypair,
+                program_id,
+                &org_config_pda,
+                &invoice_pda,
+                &inv.vendor,
+                &inv.authority,
+            )
+            .await?;
+            (sig, invoice_next_stage(rpc_client, &invoice_pda)?)
+        }
+        // The VRF callback lands later; the settlement pass takes it from here
+        Stage::RequestVrf => {
+            println!("\nAuto-requesting VRF for routing decision...");
+            let sig =
+                request_vrf_for_invoice(rpc_client, keypair, program_id, &org_config_pda, &invoice_pda, &inv.vendor)
+                    .await?;
+            (sig, Some(Stage::Enqueue))
+        }
+        Stage::Extract | Stage::Enqueue => unreachable!("handled above"),
+    };
+    Ok(StageOutcome { next, invoice: None, signature: Some(signature) })
+}
+
+// Escrow funding and VRF requests stay opt-in through AUTO_FUND_ESCROW / AUTO_REQUEST_VRF
+fn next_stage_for(status: invoice_claim_client::InvoiceStatus) -> Option<Stage> {
+    let enabled = |var: &str| env::var(var).unwrap_or_default() == "1";
+    jobs::next_stage(status, enabled("AUTO_FUND_ESCROW"), enabled("AUTO_REQUEST_VRF"))
+}
+
+fn invoice_next_stage(rpc_client: &RpcClient, invoice_pda: &Pubkey) -> Result<Option<Stage>, Box<dyn std::error::Error>> {
+    let inv = InvoiceAccount::try_from_account_data(&rpc_client.get_account(invoice_pda)?.data)?;
+    println!("On-chain invoice status: {:?}", inv.status);
+    Ok(next_stage_for(inv.status))
+}
+
+// Run OCR and submit the result for a request seen as Pending. The account is
+// re-read first since a subscription update and a sweep can report the same
+// request, and a restart can follow a submission the store never heard about.
+async fn handle_pending_request(
+    rpc_client: &RpcClient,
+    keypair: &Keypair,
+    pipeline: &Pipeline,
+    config: &Config,
+    request_pubkey: &Pubkey,
+) -> Result<StageOutcome, Box<dyn std::error::Error>> {
+    let request = InvoiceRequest::try_from_account_data(&rpc_client.get_account(request_pubkey)?.data)?;
+    let invoice_pda = pda::invoice_account(&config.program_id, &request.authority, request.nonce);
+    if request.status != RequestStatus::Pending {
+        println!("Request {} already completed, skipping extraction", request_pubkey);
+        let next = invoice_next_stage(rpc_client, &invoice_pda)?;
+        return Ok(StageOutcome { next, invoice: Some(invoice_pda), signature: None });
     }
 
-    println!("\n===== RAW OCR API RESPONSE =====");
-    println!("{}", serde_json::to_string_pretty(&json)?);
-    println!("================================\n");
+    println!("Authority: {}", request.authority);
+    println!("IPFS: {}", request.ipfs_hash);
+    println!("Nonce: {}", request.nonce);
+
+    let signature = extract_and_submit(rpc_client, keypair, pipeline, config, &request, request_pubkey).await?;
+    let next = invoice_next_stage(rpc_client, &invoice_pda)?;
+    Ok(StageOutcome { next, invoice: Some(invoice_pda), signature: Some(signature) })
+}
 
-    let ocr_text = json["ParsedResults"][0]["ParsedText"]
-        .as_str()
-        .ok_or("Failed to extract OCR text")?;
+async fn extract_and_submit(
+    rpc_client: &RpcClient,
+    keypair: &Keypair,
+    pipeline: &Pipeline,
+    config: &Config,
+    request: &InvoiceRequest,
+    request_pubkey: &Pubkey,
+) -> Result<Signature, Box<dyn std::error::Error>> {
+    let program_id = &config.program_id;
+    // The request names its org; only configured orgs get this far
+    let org = config
+        .org_by_config(&request.org)
+        .ok_or_else(|| format!("request {} is for org {}, which is not configured", request_pubkey, request.org))?;
+    let org_config_pda = org.config;
+
+    let ocr = pipeline.ocr.as_ref();
+    let bytes = if ocr.reads_document() {
+        pipeline.ipfs.fetch(&request.ipfs_hash).await.map_err(|e| e as Box<dyn std::error::Error>)?
+    } else {
+        Vec::new()
+    };
+    let document = Document::new(&request.ipfs_hash, bytes);
+
+    println!("\nRunning OCR ({})...", ocr.name());
+    let ocr_text = ocr.extract_text(&document).await.map_err(|e| e as Box<dyn std::error::Error>)?;
     println!("OCR Text extracted");
 
-    let (vendor, amount, _) = parse_invoice(ocr_text);
+    let extracted = pipeline.extractor.extract(&ocr_text);
+    extracted.log();
+
+    let vendor = extracted
+        .vendor_name
+        .as_ref()
+        .map(|f| f.value.clone())
+        .unwrap_or_else(|| "Unknown Vendor".to_string());
     println!("Vendor: {}", vendor);
 
-    let decimals: u8 = env::var("MINT_DECIMALS")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(6);
+    // Amounts are converted with the org mint's own decimals
+    let org_config = OrgConfig::try_from_account_data(&rpc_client.get_account(&org_config_pda)?.data)?;
+    let decimals = fetch_mint_decimals(rpc_client, &org_config.mint)?;
+    log_amount("Request amount", request.amount, decimals);
+
+    // The program would escrow the amount in the mint regardless of the
+    // currency printed on the invoice
+    let mint_currency = org.currency.as_deref().or_else(|| mint_currency(&org_config.mint));
+    if let (Some(invoice), Some(mint)) = (&extracted.currency, mint_currency) {
+        if !currency_matches(&invoice.value, mint) {
+            return Err(format!(
+                "invoice for request {} is in {}, but org {} pays in {}",
+                request_pubkey, invoice.value, org.authority, mint
+            )
+            .into());
+        }
+    }
+
+    let amount = match &extracted.total {
+        Some(total) => parse_base_units(&total.value, extracted.decimal_mark(), decimals)?,
+        None => 0,
+    };
     log_amount("Parsed amount", amount, decimals);
 
     if amount == 0 {
         eprintln!("Warning: parsed amount_base_units is 0; check OCR and parsing rules");
     }
 
-    // 🔥 Force due date to 30 seconds from now (test mode)
+    // The program holds mismatching invoices in NeedsReview; report it here too
+    let amount_matches = org_config.amount_within_tolerance(request.amount, amount);
+    if !amount_matches {
+        eprintln!(
+            "⚠️  Amount discrepancy for request {}: declared {}, extracted {} (tolerance {} base units / {} bps)",
+            request_pubkey,
+            format_base_units(request.amount, decimals),
+            format_base_units(amount, decimals),
+            org_config.amount_tolerance,
+            org_config.amount_tolerance_bps,
+        );
+    }
+
     let now = chrono::Utc::now().timestamp();
-    let due_date = now + 30;
-    println!("⏰ Overriding due date to {} (30 seconds from now)", due_date);
+    let due_date = match env::var("SHORT_DUE_SECONDS").ok().and_then(|s| s.parse::<i64>().ok()) {
+        // Test mode: only when explicitly configured
+        Some(secs) => {
+            println!("⏰ SHORT_DUE_SECONDS set, overriding due date to now + {}s", secs);
+            now + secs
+        }
+        None => {
+            let net_days: u32 = env::var("NET_TERMS_DAYS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(DEFAULT_NET_TERMS_DAYS);
+            let (due_date, source) = extracted.due_timestamp(net_days, now);
+            println!("⏰ Due date {} ({:?})", due_date, source);
+            due_date
+        }
+    };
+    // The program rejects due dates that have already passed
+    if due_date <= now {
+        return Err(format!("invoice due date {} has already passed", due_date).into());
+    }
 
-    // Derive PDAs
-    let (invoice_pda, _) = Pubkey::find_program_address(
-        &[
-            b"invoice",
-            request.authority.as_ref(),
-            &request.nonce.to_le_bytes()
-        ],
-        program_id,
-    );
+    let invoice_number = extracted.invoice_number.as_ref().map(|f| f.value.clone()).unwrap_or_default();
+    if invoice_number.len() > MAX_INVOICE_NUMBER_LEN {
+        return Err(format!("invoice number {:?} is longer than {} bytes", invoice_number, MAX_INVOICE_NUMBER_LEN).into());
+    }
 
-    let org_authority_str = env::var("ORG_AUTHORITY_PUBKEY")
-        .expect("ORG_AUTHORITY_PUBKEY must be set in .env");
-    let org_authority = Pubkey::from_str(&org_authority_str)?;
+    // Derive PDAs
+    let invoice_pda = pda::invoice_account(program_id, &request.authority, request.nonce);
+    let vendor_pda = pda::vendor_account(program_id, &org_config_pda, &vendor);
 
-    let (org_config_pda, _) = Pubkey::find_program_address(
-        &[b"org_config", org_authority.as_ref()],
+    // The program would reject an exact duplicate; fail here with a clearer message
+    if let Some(dup) = duplicates::find_exact(
+        rpc_client,
         program_id,
-    );
+        &org_config_pda,
+        &vendor_pda,
+        &invoice_number,
+        &request.ipfs_hash,
+    )? {
+        return Err(format!("duplicate invoice for request {}: {}", request_pubkey, dup).into());
+    }
+    let candidate = Candidate { vendor_account: vendor_pda, invoice_number: &invoice_number, amount, due_date };
+    for near in duplicates::find_near(&candidate, &get_vendor_invoices(rpc_client, program_id, &vendor_pda)?) {
+        eprintln!("⚠️  Possible duplicate of invoice {}: {}", near.invoice, near.reason);
+    }
 
-    let (vendor_pda, _) = Pubkey::find_program_address(
-        &[b"vendor", org_config_pda.as_ref(), vendor.as_bytes()],
+    let ix = ix::process_extraction_result(
         program_id,
+        &ix::ProcessExtractionResultAccounts {
+            payer: keypair.pubkey(),
+            org_config: org_config_pda,
+            vendor_account: vendor_pda,
+            invoice_request: *request_pubkey,
+            invoice_account: invoice_pda,
+            document_registry: pda::document_registry(program_id, &org_config_pda, &request.ipfs_hash),
+            number_registry: pda::number_registry(program_id, &vendor_pda, &invoice_number, &request.ipfs_hash),
+        },
+        &ix::ProcessExtractionResult { vendor_name: vendor, amount, due_date, invoice_number },
     );
 
-    // Build instruction data
-    let mut hasher = Sha256::new();
-    hasher.update(b"global:process_extraction_result");
-    let disc: [u8; 8] = hasher.finalize()[..8].try_into().unwrap();
-
-    let mut data = disc.to_vec();
-    data.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
-    data.extend_from_slice(vendor.as_bytes());
-    data.extend_from_slice(&amount.to_le_bytes());
-    data.extend_from_slice(&due_date.to_le_bytes());
-
-    let ix = Instruction {
-        program_id: *program_id,
-        accounts: vec![
-            AccountMeta::new(keypair.pubkey(), true),
-            AccountMeta::new(org_config_pda, false),
-            AccountMeta::new_readonly(vendor_pda, false),
-            AccountMeta::new(*request_pubkey, false),
-            AccountMeta::new(invoice_pda, false),
-            AccountMeta::new_readonly(system_program::ID, false),
-        ],
-        data,
-    };
-
     println!("\nSubmitting to Solana...");
     let recent_blockhash = rpc_client.get_latest_blockhash()?;
     let tx = Transaction::new_signed_with_payer(
@@ -384,7 +635,7 @@ async fn extract_and_submit(
         recent_blockhash,
     );
 
-    let _signature = match rpc_client.send_and_confirm_transaction(&tx) {
+    let signature = match rpc_client.send_and_confirm_transaction(&tx) {
         Ok(sig) => {
             println!("Transaction successful: {}", sig);
             sig
@@ -403,216 +654,42 @@ async fn extract_and_submit(
     };
 
     if let Ok(acc) = rpc_client.get_account(&invoice_pda) {
-        if let Ok(inv) = InvoiceAccountLite::from_account_data(&acc.data) {
+        if let Ok(inv) = InvoiceAccount::try_from_account_data(&acc.data) {
             log_amount("On-chain invoice.amount", inv.amount, decimals);
         }
     }
 
-    
-    // Gated VRF logic:
-    // - Only request VRF if AUTO_REQUEST_VRF=1 AND we determine it's OK to request VRF.
-    // - "ok to request VRF" is true when:
-    //     a) AUTO_FUND_ESCROW=1 and funding succeeded, OR
-    //     b) AUTO_FUND_ESCROW=0 but the on-chain invoice status is IN_ESCROW_AWAITING_VRF (configurable via env)
-    //
-    // This implements the requested gating: don't call VRF when escrow wasn't funded unless the invoice
-    // is already in the awaiting-VRF state on-chain.
-  
-
-    // Helper: read invoice status from on-chain invoice account data
-    fn read_invoice_status_from_account_data(data: &[u8]) -> Result<u8, Box<dyn std::error::Error>> {
-        // Parse same layout as cranker/main expects:
-        // 8 disc | 32 authority | 32 vendor_pubkey | 4 vendor_name_len | vendor_name | 8 amount | 8 due_date | 4 ipfs_len | ipfs | 1 status | 8 timestamp | 8 nonce
-        if data.len() < 8 + 32 + 32 + 4 {
-            return Err("invoice account too small".into());
-        }
-        let mut o = 8 + 32 + 32;
-        
-        let name_len = u32::from_le_bytes(data[o..o+4].try_into()?) as usize;
-        o += 4;
-        if o + name_len + 8 + 8 + 4 > data.len() {
-            return Err("short invoice account while parsing vendor/name/amount/due".into());
-        }
-        o += name_len;
-        
-        o += 8;
-        o += 8;
-        
-        let ipfs_len = u32::from_le_bytes(data[o..o+4].try_into()?) as usize;
-        o += 4;
-        if o + ipfs_len + 1 > data.len() {
-            return Err("short invoice account while parsing ipfs/status".into());
-        }
-        o += ipfs_len;
-        
-        if o >= data.len() {
-            return Err("no status byte".into());
-        }
-        Ok(data[o])
+    if !amount_matches {
+        eprintln!("⚠️  Invoice {} is held for amount review; skipping escrow and VRF", invoice_pda);
     }
 
-    // Determine if funding succeeded (and thus OK to request VRF)
-    let mut ok_to_request_vrf = false;
-
-    // First, attempt funding if configured
-    if env::var("AUTO_FUND_ESCROW").unwrap_or_default() == "1" {
-        println!("\nAuto-funding escrow...");
-        match fund_escrow_for_invoice(
-            rpc_client, keypair, program_id, &invoice_pda, &request.authority, request.nonce
-        ).await {
-            Ok(_) => {
-                println!("Escrow funded successfully!");
-                ok_to_request_vrf = true;
-
-                // keep existing behavior: add to payment queue after funding (if desired)
-                let _ = add_to_payment_queue(
-                    rpc_client, keypair, program_id, &invoice_pda, &request.authority, request.nonce
-                ).await;
-            }
-            Err(e) => {
-                eprintln!("Auto-funding escrow encountered an error: {}", e);
-                ok_to_request_vrf = false;
-            }
-        }
-    } else {
-        // AUTO_FUND_ESCROW == 0: check on-chain invoice status to see if it's already InEscrowAwaitingVRF
-        // Read expected awaiting-VRF status value from env (configurable). Default to 2.
-        let expected_status: u8 = env::var("INVOICE_STATUS_AWAITING_VRF")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(2u8);
-
-        println!("\nAUTO_FUND_ESCROW is disabled; checking on-chain invoice status for awaiting-VRF (expected={})", expected_status);
-        match rpc_client.get_account(&invoice_pda) {
-            Ok(acc) => {
-                match read_invoice_status_from_account_data(&acc.data) {
-                    Ok(status_byte) => {
-                        println!("On-chain invoice status byte: {}", status_byte);
-                        if status_byte == expected_status {
-                            println!("Invoice is in awaiting-VRF state; allowing VRF request.");
-                            ok_to_request_vrf = true;
-                        } else {
-                            println!("Invoice is NOT in awaiting-VRF state; will NOT request VRF now.");
-                            ok_to_request_vrf = false;
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to parse on-chain invoice status: {}", e);
-                        ok_to_request_vrf = false;
-                    }
-                }
-            }
-            Err(e) => {
-                eprintln!("Failed to fetch on-chain invoice account to check status: {}", e);
-                ok_to_request_vrf = false;
-            }
-        }
-    }
-
-    // Finally: only invoke VRF if AUTO_REQUEST_VRF=1 AND ok_to_request_vrf is true
-    if env::var("AUTO_REQUEST_VRF").unwrap_or_default() == "1" {
-        if ok_to_request_vrf {
-            println!("\nAuto-requesting VRF for routing decision (gated: funding/status ok)...");
-            let _ = request_vrf_for_invoice(rpc_client, keypair, program_id, &invoice_pda).await;
-        } else {
-            println!("\nSkipping VRF request because funding/status gating failed (ok_to_request_vrf=false).");
-        }
-    } else {
-        println!("\nAUTO_REQUEST_VRF not enabled; skipping VRF request.");
-    }
-
-    Ok(())
-}
-
-fn parse_invoice(text: &str) -> (String, u64, i64) {
-    println!("\n===== PARSING INVOICE DATA =====");
-    let vendor = if let Some(bill_to_pos) = text.find("Bill to") {
-        let after_bill_to = &text[bill_to_pos + 7..];
-        let lines: Vec<&str> = after_bill_to.lines().collect();
-        lines.iter()
-            .skip(1)
-            .find(|line| !line.trim().is_empty() && !line.contains("@"))
-            .map(|line| line.trim().to_string())
-            .unwrap_or_else(|| "Unknown Vendor".to_string())
-    } else {
-        let name_re = Regex::new(r"([A-Z][a-z]+\s+[A-Z][a-z]+)").unwrap();
-        name_re.find(text)
-            .map(|m| m.as_str().to_string())
-            .unwrap_or_else(|| "Unknown Vendor".to_string())
-    };
-
-    let amount_re = Regex::new(r"\$([0-9]+\.[0-9]{2})\s+due").unwrap();
-    let amount_str = amount_re
-        .captures(text)
-        .and_then(|c| c.get(1))
-        .map(|m| m.as_str())
-        .unwrap_or("0.00");
-    let amount_float: f64 = amount_str.parse().unwrap_or(0.0);
-    let amount = (amount_float * 1_000_000.0) as u64;
-
-    println!("  Vendor: '{}', Amount: {}", vendor, amount);
-    println!("================================\n");
-    (vendor, amount, 0)
+    Ok(signature)
 }
 
 fn log_amount(label: &str, amount_base_units: u64, decimals: u8) {
-    let denom = 10_u128.pow(decimals as u32);
-    let base = amount_base_units as u128;
-    let ui_int = base / denom;
-    let ui_frac = base % denom;
-    println!("{} => {}.{}", label, ui_int, ui_frac);
-}
-
-#[derive(Clone, Debug)]
-struct InvoiceAccountLite {
-    pub amount: u64,
-}
-
-impl InvoiceAccountLite {
-    pub fn from_account_data(data: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
-        let mut offset = 8 + 32 + 32;
-        let name_len = u32::from_le_bytes(data[offset..offset + 4].try_into()?) as usize;
-        offset += 4 + name_len;
-        let amount = u64::from_le_bytes(data[offset..offset + 8].try_into()?);
-        Ok(InvoiceAccountLite { amount })
-    }
+    println!("{} => {}", label, format_base_units(amount_base_units, decimals));
 }
 
 async fn request_vrf_for_invoice(
     rpc_client: &RpcClient,
     keypair: &Keypair,
     program_id: &Pubkey,
+    org_config_pda: &Pubkey,
     invoice_pda: &Pubkey,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let org_authority_str = env::var("ORG_AUTHORITY_PUBKEY")
-        .expect("ORG_AUTHORITY_PUBKEY must be set in .env");
-    let org_authority = Pubkey::from_str(&org_authority_str)?;
-
-    let (org_config_pda, _) =
-        Pubkey::find_program_address(&[b"org_config", org_authority.as_ref()], program_id);
-
-    let queue_str =
-        env::var("QUEUE_PUBKEY").expect("QUEUE_PUBKEY must be set in .env to auto-request VRF");
-    let queue_pk = Pubkey::from_str(&queue_str)?;
-
-    let mut h = Sha256::new();
-    h.update(b"global:request_invoice_audit_vrf");
-    let disc: [u8; 8] = h.finalize()[..8].try_into().unwrap();
-
-    let mut data = Vec::new();
-    data.extend_from_slice(&disc);
-    data.push(42);
-
-    let ix = Instruction {
-        program_id: *program_id,
-        accounts: vec![
-            AccountMeta::new(keypair.pubkey(), true),
-            AccountMeta::new(org_config_pda, false),
-            AccountMeta::new(*invoice_pda, false),
-            AccountMeta::new(queue_pk, false),
-        ],
-        data,
-    };
+    vendor_account: &Pubkey,
+) -> Result<Signature, Box<dyn std::error::Error>> {
+    // The oracle queue, program identity, VRF program and sysvars are fixed
+    // addresses the builder fills in
+    let ix = ix::request_invoice_audit_vrf(
+        program_id,
+        &ix::RequestInvoiceAuditVrfAccounts {
+            payer: keypair.pubkey(),
+            org_config: *org_config_pda,
+            invoice_account: *invoice_pda,
+            vendor_account: *vendor_account,
+        },
+        &ix::RequestInvoiceAuditVrf { client_seed: 42 },
+    );
 
     let recent_blockhash = rpc_client.get_latest_blockhash()?;
     let tx = Transaction::new_signed_with_payer(
@@ -624,5 +701,5 @@ async fn request_vrf_for_invoice(
 
     let sig = rpc_client.send_and_confirm_transaction(&tx)?;
     println!("🎲 VRF requested for invoice {}. Tx: {}", invoice_pda, sig);
-    Ok(())
+    Ok(sig)
 }
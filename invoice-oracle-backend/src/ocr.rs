@@ -0,0 +1,316 @@
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::env;
+use std::error::Error;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use tokio::process::Command;
+
+use crate::ipfs::Cid;
+
+pub type OcrError = Box<dyn Error + Send + Sync>;
+
+const OCR_SPACE_URL: &str = "https://api.ocr.space/parse/image";
+
+/// An invoice document fetched from IPFS and verified against its CID.
+pub struct Document {
+    pub ipfs_hash: String,
+    pub filetype: String,
+    pub bytes: Vec<u8>,
+}
+
+impl Document {
+    /// The file type is sniffed from the content, falling back to `OCR_FILETYPE`.
+    pub fn new(ipfs_hash: &str, bytes: Vec<u8>) -> Self {
+        let filetype = sniff_filetype(&bytes)
+            .map(str::to_string)
+            .unwrap_or_else(|| env::var("OCR_FILETYPE").unwrap_or_else(|_| "pdf".to_string()));
+        Document { ipfs_hash: ipfs_hash.to_string(), filetype, bytes }
+    }
+}
+
+fn sniff_filetype(bytes: &[u8]) -> Option<&'static str> {
+    match bytes {
+        [b'%', b'P', b'D', b'F', ..] => Some("pdf"),
+        [0x89, b'P', b'N', b'G', ..] => Some("png"),
+        [0xff, 0xd8, 0xff, ..] => Some("jpg"),
+        [b'I', b'I', 0x2a, 0x00, ..] | [b'M', b'M', 0x00, 0x2a, ..] => Some("tif"),
+        _ => None,
+    }
+}
+
+fn mime_type(filetype: &str) -> &'static str {
+    match filetype.to_ascii_lowercase().as_str() {
+        "pdf" => "application/pdf",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "tif" | "tiff" => "image/tiff",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Turns an invoice document into plain text for the extraction engine.
+#[async_trait]
+pub trait OcrProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Whether `extract_text` reads `Document::bytes`; the mock doesn't, so
+    /// the document need not be fetched.
+    fn reads_document(&self) -> bool {
+        true
+    }
+
+    async fn extract_text(&self, document: &Document) -> Result<String, OcrError>;
+}
+
+/// Pick a provider from `OCR_PROVIDER`: `ocrspace` (default), `tesseract` or `mock`.
+pub fn provider_from_env() -> Result<Box<dyn OcrProvider>, OcrError> {
+    provider_from_vars(|key| env::var(key).ok())
+}
+
+fn provider_from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Box<dyn OcrProvider>, OcrError> {
+    let or = |key: &str, default: &str| var(key).unwrap_or_else(|| default.to_string());
+    let kind = or("OCR_PROVIDER", "ocrspace");
+    let provider: Box<dyn OcrProvider> = match kind.as_str() {
+        "ocrspace" => Box::new(OcrSpace {
+            api_key: var("OCR_API_KEY").ok_or("OCR_API_KEY must be set for OCR_PROVIDER=ocrspace")?,
+            client: reqwest::Client::new(),
+        }),
+        "tesseract" => Box::new(Tesseract {
+            tesseract_bin: or("TESSERACT_BIN", "tesseract"),
+            pdftoppm_bin: or("PDFTOPPM_BIN", "pdftoppm"),
+            language: or("TESSERACT_LANG", "eng"),
+        }),
+        "mock" => Box::new(MockOcr { dir: or("OCR_FIXTURES_DIR", "fixtures/ocr").into() }),
+        other => return Err(format!("unknown OCR_PROVIDER '{}' (expected ocrspace, tesseract or mock)", other).into()),
+    };
+    Ok(provider)
+}
+
+/// OCR.space hosted API. The verified document is uploaded as base64 rather
+/// than letting OCR.space fetch it from a gateway itself.
+pub struct OcrSpace {
+    api_key: String,
+    client: reqwest::Client,
+}
+
+#[async_trait]
+impl OcrProvider for OcrSpace {
+    fn name(&self) -> &'static str {
+        "ocrspace"
+    }
+
+    async fn extract_text(&self, document: &Document) -> Result<String, OcrError> {
+        let upload = format!("data:{};base64,{}", mime_type(&document.filetype), STANDARD.encode(&document.bytes));
+        // Key goes in a header so it doesn't end up in proxy/access logs
+        let v = self
+            .client
+            .post(OCR_SPACE_URL)
+            .header("apikey", &self.api_key)
+            .form(&[
+                ("base64Image", upload.as_str()),
+                ("language", "eng"),
+                ("OCREngine", "2"),
+                ("filetype", document.filetype.as_str()),
+            ])
+            .send()
+            .await
+            .map_err(|e| format!("OCR.space request failed: {}", e))?
+            .json::<serde_json::Value>()
+            .await
+            .map_err(|e| format!("bad OCR.space response: {}", e))?;
+
+        if v.get("IsErroredOnProcessing").and_then(|b| b.as_bool()).unwrap_or(false) {
+            return Err(format!("OCR.space error: {:?}", v.get("ErrorMessage")).into());
+        }
+
+        println!("\n===== RAW OCR API RESPONSE =====");
+        println!("{}", serde_json::to_string_pretty(&v)?);
+        println!("================================\n");
+
+        let pages: Vec<&str> = v["ParsedResults"]
+            .as_array()
+            .map(|r| r.iter().filter_map(|p| p["ParsedText"].as_str()).collect())
+            .unwrap_or_default();
+        if pages.is_empty() {
+            return Err("OCR.space response has no ParsedText".into());
+        }
+        Ok(pages.join("\n"))
+    }
+}
+
+/// Local `tesseract` CLI; PDFs are rasterised with `pdftoppm` (poppler-utils)
+/// first. Needs no network access.
+pub struct Tesseract {
+    tesseract_bin: String,
+    pdftoppm_bin: String,
+    language: String,
+}
+
+impl Tesseract {
+    async fn run(&self, program: &str, args: &[&OsStr]) -> Result<String, OcrError> {
+        let output = Command::new(program)
+            .args(args)
+            .output()
+            .await
+            .map_err(|e| format!("failed to run {}: {}", program, e))?;
+        if !output.status.success() {
+            return Err(format!("{} exited with {}: {}", program, output.status, String::from_utf8_lossy(&output.stderr)).into());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+#[async_trait]
+impl OcrProvider for Tesseract {
+    fn name(&self) -> &'static str {
+        "tesseract"
+    }
+
+    async fn extract_text(&self, document: &Document) -> Result<String, OcrError> {
+        let dir = tempfile::tempdir()?;
+        let input = dir.path().join(format!("invoice.{}", document.filetype));
+        tokio::fs::write(&input, &document.bytes).await?;
+
+        let images = if document.filetype.eq_ignore_ascii_case("pdf") {
+            let prefix = dir.path().join("page");
+            let args = ["-r".as_ref(), "300".as_ref(), "-png".as_ref(), input.as_os_str(), prefix.as_os_str()];
+            self.run(&self.pdftoppm_bin, &args).await?;
+            let mut pages: Vec<PathBuf> = std::fs::read_dir(dir.path())?
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| p.extension().is_some_and(|ext| ext == "png"))
+                .collect();
+            // pdftoppm zero-pads page numbers, so lexical order is page order
+            pages.sort();
+            pages
+        } else {
+            vec![input]
+        };
+
+        let mut text = String::new();
+        for image in &images {
+            let args = [image.as_os_str(), "stdout".as_ref(), "-l".as_ref(), self.language.as_ref()];
+            text.push_str(&self.run(&self.tesseract_bin, &args).await?);
+            text.push('\n');
+        }
+        if text.trim().is_empty() {
+            return Err(format!("tesseract found no text in {}", document.ipfs_hash).into());
+        }
+        Ok(text)
+    }
+}
+
+/// Reads `<dir>/<cid>.txt`, falling back to `<dir>/default.txt`, so the
+/// pipeline can run in CI without network access or an OCR engine. `<cid>` is
+/// the base32 CIDv1 form of the document's hash, whatever encoding it was
+/// submitted in.
+pub struct MockOcr {
+    dir: PathBuf,
+}
+
+#[async_trait]
+impl OcrProvider for MockOcr {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn reads_document(&self) -> bool {
+        false
+    }
+
+    async fn extract_text(&self, document: &Document) -> Result<String, OcrError> {
+        // The hash comes from an on-chain request; only its canonical form
+        // is safe to use as a file name
+        let cid = Cid::from_str(&document.ipfs_hash)?;
+        let specific = self.dir.join(format!("{}.txt", cid));
+        let path = if specific.exists() { specific } else { self.dir.join("default.txt") };
+        tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|e| format!("no OCR fixture for {} ({}): {}", document.ipfs_hash, path.display(), e).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const CID_V0: &str = "QmYwAPJzv5CZsnA625s3Xf2nemtYgPpHdWEz79ojWnPbdG";
+
+    fn provider(vars: &[(&str, &str)]) -> Result<Box<dyn OcrProvider>, OcrError> {
+        let vars: HashMap<_, _> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        provider_from_vars(|key| vars.get(key).cloned())
+    }
+
+    fn tesseract(tesseract_bin: &str, pdftoppm_bin: &str) -> Tesseract {
+        Tesseract {
+            tesseract_bin: tesseract_bin.to_string(),
+            pdftoppm_bin: pdftoppm_bin.to_string(),
+            language: "eng".to_string(),
+        }
+    }
+
+    #[test]
+    fn selects_provider_from_env() {
+        assert_eq!(provider(&[("OCR_PROVIDER", "mock")]).unwrap().name(), "mock");
+        assert_eq!(provider(&[("OCR_PROVIDER", "tesseract")]).unwrap().name(), "tesseract");
+        assert_eq!(provider(&[("OCR_API_KEY", "key")]).unwrap().name(), "ocrspace");
+
+        let err = provider(&[]).err().unwrap().to_string();
+        assert!(err.contains("OCR_API_KEY must be set"), "{}", err);
+        let err = provider(&[("OCR_PROVIDER", "textract")]).err().unwrap().to_string();
+        assert!(err.contains("unknown OCR_PROVIDER 'textract'"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn mock_reads_canonical_fixture_or_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let mock = MockOcr { dir: dir.path().to_path_buf() };
+        let canonical = Cid::from_str(CID_V0).unwrap().to_string();
+        std::fs::write(dir.path().join("default.txt"), "default").unwrap();
+
+        let text = mock.extract_text(&Document::new(CID_V0, Vec::new())).await.unwrap();
+        assert_eq!(text, "default");
+
+        // CIDv0 and CIDv1 of the same document share one fixture
+        std::fs::write(dir.path().join(format!("{}.txt", canonical)), "specific").unwrap();
+        for hash in [CID_V0, canonical.as_str()] {
+            let text = mock.extract_text(&Document::new(hash, Vec::new())).await.unwrap();
+            assert_eq!(text, "specific", "{}", hash);
+        }
+    }
+
+    #[tokio::test]
+    async fn mock_rejects_hashes_that_are_not_cids() {
+        let dir = tempfile::tempdir().unwrap();
+        let fixtures = dir.path().join("fixtures");
+        std::fs::create_dir(&fixtures).unwrap();
+        std::fs::write(fixtures.join("default.txt"), "default").unwrap();
+        std::fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+
+        let mock = MockOcr { dir: fixtures };
+        for hash in ["../secret", "bafy/../../secret", "/etc/passwd"] {
+            assert!(mock.extract_text(&Document::new(hash, Vec::new())).await.is_err(), "{}", hash);
+        }
+    }
+
+    #[tokio::test]
+    async fn maps_tesseract_failures() {
+        let missing = "/nonexistent/tesseract";
+        let err = tesseract(missing, "pdftoppm").run(missing, &[]).await.unwrap_err().to_string();
+        assert!(err.starts_with("failed to run /nonexistent/tesseract"), "{}", err);
+
+        let err = tesseract("false", "pdftoppm").run("false", &[]).await.unwrap_err().to_string();
+        assert!(err.starts_with("false exited with"), "{}", err);
+
+        // A PDF goes through pdftoppm first, and its failure is reported as such
+        let pdf = Document::new(CID_V0, b"%PDF-1.4".to_vec());
+        let err = tesseract("true", "false").extract_text(&pdf).await.unwrap_err().to_string();
+        assert!(err.starts_with("false exited with"), "{}", err);
+
+        let png = Document::new(CID_V0, b"\x89PNG".to_vec());
+        let err = tesseract("true", "false").extract_text(&png).await.unwrap_err().to_string();
+        assert_eq!(err, format!("tesseract found no text in {}", CID_V0));
+    }
+}
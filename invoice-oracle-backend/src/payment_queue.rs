@@ -1,107 +1,240 @@
+use solana_client::client_error::ClientError;
 use solana_client::rpc_client::RpcClient;
 use solana_sdk::{
-    instruction::{AccountMeta, Instruction},
+    instruction::InstructionError,
     pubkey::Pubkey,
-    signature::{Keypair, Signer},
-    system_program,
-    transaction::Transaction,
+    signature::{Keypair, Signature, Signer},
+    transaction::{Transaction, TransactionError},
 };
-use sha2::{Digest, Sha256};
-use std::env;
-use std::str::FromStr;
 
-pub async fn add_to_payment_queue(
+use base64::{engine::general_purpose::STANDARD, Engine};
+use solana_account_decoder::UiAccountEncoding;
+use std::collections::HashMap;
+
+use invoice_claim_client::instructions as ix;
+use invoice_claim_client::{
+    pda, InvoiceAccount, InvoiceStatus, PaymentQueue, ProgramAccount, QueueEntry, ERROR_ALREADY_QUEUED,
+};
+
+use crate::jobs::{next_stage, Job, JobState, JobStore, Stage};
+use crate::invoices::OrgInvoices;
+
+/// Load every entry of `payment_queue_pda` in list order (earliest due first).
+///
+/// One getProgramAccounts call fetches the entries, then the links are walked
+/// from `head` so the result matches the on-chain order exactly.
+pub(crate) fn load_queue_entries(
     rpc_client: &RpcClient,
-    keypair: &Keypair,
     program_id: &Pubkey,
-    invoice_pda: &Pubkey,
-    authority: &Pubkey,
-    nonce: u64,
-) -> Result<(), Box<dyn std::error::Error>> {
-    println!("Starting add_to_payment_queue for invoice: {}", invoice_pda);
+    payment_queue_pda: &Pubkey,
+    head: Option<Pubkey>,
+) -> Result<Vec<(Pubkey, QueueEntry)>, Box<dyn std::error::Error>> {
+    use solana_client::rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType};
 
-    let org_authority_str = env::var("ORG_AUTHORITY_PUBKEY")?;
-    println!("ORG_AUTHORITY_PUBKEY from env: {}", org_authority_str);
-    let org_authority = Pubkey::from_str(&org_authority_str)?;
+    let filters = vec![
+        RpcFilterType::Memcmp(Memcmp::new(0, MemcmpEncodedBytes::Base64(STANDARD.encode(QueueEntry::discriminator())))),
+        RpcFilterType::Memcmp(Memcmp::new(8, MemcmpEncodedBytes::Base64(STANDARD.encode(payment_queue_pda)))),
+    ];
 
-    let (org_config_pda, _) = Pubkey::find_program_address(
-        &[b"org_config", org_authority.as_ref()],
+    let accounts = rpc_client.get_program_accounts_with_config(
         program_id,
-    );
-    println!("Derived org_config PDA: {}", org_config_pda);
+        solana_client::rpc_config::RpcProgramAccountsConfig {
+            filters: Some(filters),
+            account_config: solana_client::rpc_config::RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )?;
 
-    let (payment_queue_pda, _) = Pubkey::find_program_address(
-        &[b"payment_queue", org_config_pda.as_ref()],
-        program_id,
-    );
-    println!("Derived payment_queue PDA: {}", payment_queue_pda);
-
-    // Check if payment queue PDA exists; if not, initialize it
-    if rpc_client.get_account(&payment_queue_pda).is_err() {
-        println!("Payment queue PDA does not exist. Initializing...");
-
-        const INIT_PAYMENT_QUEUE_DISC: [u8; 8] = [158, 252, 5, 213, 84, 121, 59, 80]; // Replace with exact from IDL if differs
-
-        assert_eq!(
-            keypair.pubkey(),
-            *authority,
-            "Keypair pubkey must match authority pubkey"
-        );
-
-        let init_ix = Instruction {
-            program_id: *program_id,
-            accounts: vec![
-                AccountMeta::new(org_authority, true),    // authority signer
-                AccountMeta::new(org_config_pda, false),  // org config
-                AccountMeta::new(payment_queue_pda, false), // payment queue PDA (will be initialized)
-                AccountMeta::new_readonly(system_program::ID, false),
-            ],
-            data: INIT_PAYMENT_QUEUE_DISC.to_vec(),
-        };
-
-        let recent_blockhash = rpc_client.get_latest_blockhash()?;
-        println!("Sending transaction to initialize payment queue...");
-        let init_tx = Transaction::new_signed_with_payer(
-            &[init_ix],
-            Some(&keypair.pubkey()),
-            &[keypair],
-            recent_blockhash,
-        );
-
-        match rpc_client.send_and_confirm_transaction(&init_tx) {
-            Ok(sig) => println!("Payment queue initialized successfully. Tx: {}", sig),
-            Err(e) => {
-                eprintln!("Failed to initialize payment queue: {}", e);
-                return Err(e.into());
+    let mut by_key = HashMap::new();
+    for (key, acc) in accounts {
+        by_key.insert(key, QueueEntry::try_from_account_data(&acc.data)?);
+    }
+
+    let mut ordered = Vec::with_capacity(by_key.len());
+    let mut cursor = head;
+    while let Some(key) = cursor {
+        let entry = by_key
+            .remove(&key)
+            .ok_or_else(|| format!("queue entry {} missing or visited twice", key))?;
+        cursor = entry.next;
+        ordered.push((key, entry));
+    }
+    Ok(ordered)
+}
+
+/// Neighbours a new entry due at `due_date` must be linked between:
+/// after the last entry due on or before it, before the first due after it.
+pub(crate) fn insert_position(entries: &[(Pubkey, QueueEntry)], due_date: i64) -> (Option<Pubkey>, Option<Pubkey>) {
+    let idx = entries.partition_point(|(_, e)| e.due_date <= due_date);
+    let prev = idx.checked_sub(1).map(|i| entries[i].0);
+    let next = entries.get(idx).map(|(k, _)| *k);
+    (prev, next)
+}
+
+/// Queue every invoice in `invoices` that cleared VRF / audit but has no QueueEntry yet.
+///
+/// The VRF callback cannot pay rent for a new entry, so the oracle enqueues
+/// ready invoices here before the cranker walks the queue. Invoices whose job
+/// is waiting at the Enqueue stage have their attempts recorded and backed off
+/// like any other stage; the job is done once the invoice is queued, by this
+/// oracle or another signer, or leaves the path to settlement (sent to audit,
+/// rejected or refunded).
+pub fn enqueue_ready_invoices(
+    rpc_client: &RpcClient,
+    keypair: &Keypair,
+    program_id: &Pubkey,
+    jobs: &JobStore,
+    invoices: &OrgInvoices,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let now = chrono::Utc::now().timestamp();
+    let waiting = jobs.awaiting_enqueue()?;
+    let finish = |job: &Job, reason: &str| -> Result<(), Box<dyn std::error::Error>> {
+        println!("[INFO] Job {} finished at stage {}: {}", job.request, Stage::Enqueue, reason);
+        jobs.advance(&job.request, None, now)?;
+        Ok(())
+    };
+
+    let mut queued = 0;
+    for (org_config_pda, invoices) in invoices {
+        for (invoice_pubkey, invoice) in invoices {
+            let job = waiting.get(invoice_pubkey);
+            if invoice.status != InvoiceStatus::InEscrowReadyToSettle {
+                // Still waiting on the VRF callback otherwise
+                if let Some(job) = job.filter(|_| next_stage(invoice.status, false, false).is_none()) {
+                    finish(job, &format!("invoice is {:?}", invoice.status))?;
+                }
+                continue;
+            }
+
+            let queue_pda = pda::payment_queue(program_id, org_config_pda);
+            let entry_pda = pda::queue_entry(program_id, &queue_pda, invoice_pubkey);
+            if rpc_client.get_account(&entry_pda).is_ok() {
+                if let Some(job) = job {
+                    finish(job, "invoice is already queued")?;
+                }
+                continue;
+            }
+
+            if let Some(job) = job {
+                if job.state == JobState::Dead || job.next_attempt_at > now {
+                    continue;
+                }
+            }
+
+            match add_to_payment_queue(rpc_client, keypair, program_id, org_config_pda, invoice_pubkey) {
+                Ok(sig) => {
+                    queued += 1;
+                    if let Some(job) = job {
+                        jobs.record_signature(&job.request, Stage::Enqueue, &sig.to_string(), now)?;
+                        jobs.advance(&job.request, None, now)?;
+                    }
+                }
+                // Another signer queued it since the entry was checked
+                Err(e) if is_program_error(e.as_ref(), ERROR_ALREADY_QUEUED) => {
+                    if let Some(job) = job {
+                        finish(job, "invoice is already queued")?;
+                    }
+                }
+                Err(e) => {
+                    eprintln!("[ERROR] add_to_payment_queue failed for {}: {}", invoice_pubkey, e);
+                    if let Some(job) = job {
+                        if jobs.fail(&job.request, &e.to_string(), now)? == JobState::Dead {
+                            eprintln!("[ERROR] Job {} dead-lettered at stage {}", job.request, Stage::Enqueue);
+                        }
+                    }
+                }
             }
         }
-    } else {
-        println!("Payment queue PDA exists.");
     }
+    Ok(queued)
+}
 
-    // Add invoice to payment queue
-    const ADD_TO_PAYMENT_QUEUE_DISC: [u8; 8] = [215, 176, 65, 168, 128, 96, 161, 68]; // From your IDL
+// Whether `e` is the invoice-claim program rejecting a transaction with `code`
+fn is_program_error(e: &(dyn std::error::Error + 'static), code: u32) -> bool {
+    matches!(
+        e.downcast_ref::<ClientError>().and_then(|e| e.get_transaction_error()),
+        Some(TransactionError::InstructionError(_, InstructionError::Custom(c))) if c == code
+    )
+}
 
-    println!("Preparing add_to_payment_queue instruction...");
+/// Create the org's PaymentQueue header if it does not exist yet.
+fn ensure_payment_queue(
+    rpc_client: &RpcClient,
+    keypair: &Keypair,
+    program_id: &Pubkey,
+    org_config_pda: &Pubkey,
+) -> Result<Pubkey, Box<dyn std::error::Error>> {
+    let payment_queue_pda = pda::payment_queue(program_id, org_config_pda);
+    if rpc_client.get_account(&payment_queue_pda).is_ok() {
+        return Ok(payment_queue_pda);
+    }
 
-    let ix = Instruction {
-        program_id: *program_id,
-        accounts: vec![
-            AccountMeta::new(org_config_pda, false),
-            AccountMeta::new_readonly(*invoice_pda, false),
-            AccountMeta::new(payment_queue_pda, false),
-            AccountMeta::new_readonly(system_program::ID, false),
-        ],
-        data: ADD_TO_PAYMENT_QUEUE_DISC.to_vec(),
-    };
+    println!("Payment queue PDA does not exist. Initializing {}...", payment_queue_pda);
 
+    let init_ix = ix::init_payment_queue(
+        program_id,
+        &ix::InitPaymentQueueAccounts {
+            authority: keypair.pubkey(),
+            org_config: *org_config_pda,
+            payment_queue: payment_queue_pda,
+        },
+    );
 
     let recent_blockhash = rpc_client.get_latest_blockhash()?;
-    println!("Signers for transaction:");
-    for signer in &[keypair] {
-        println!("Signer pubkey: {}", signer.pubkey());
-    }
+    let init_tx = Transaction::new_signed_with_payer(
+        &[init_ix],
+        Some(&keypair.pubkey()),
+        &[keypair],
+        recent_blockhash,
+    );
+
+    let sig = rpc_client.send_and_confirm_transaction(&init_tx)?;
+    println!("Payment queue initialized successfully. Tx: {}", sig);
+    Ok(payment_queue_pda)
+}
 
+/// Append `invoice_pda` to the org's payment queue, creating its QueueEntry.
+pub fn add_to_payment_queue(
+    rpc_client: &RpcClient,
+    keypair: &Keypair,
+    program_id: &Pubkey,
+    org_config_pda: &Pubkey,
+    invoice_pda: &Pubkey,
+) -> Result<Signature, Box<dyn std::error::Error>> {
+    println!("Starting add_to_payment_queue for invoice: {}", invoice_pda);
+
+    let payment_queue_pda = ensure_payment_queue(rpc_client, keypair, program_id, org_config_pda)?;
+    let entry_pda = pda::queue_entry(program_id, &payment_queue_pda, invoice_pda);
+
+    // Entries are kept sorted by due date; find where this one belongs
+    let header = PaymentQueue::try_from_account_data(&rpc_client.get_account(&payment_queue_pda)?.data)?;
+    let entries = load_queue_entries(rpc_client, program_id, &payment_queue_pda, header.head)?;
+    let invoice = InvoiceAccount::try_from_account_data(&rpc_client.get_account(invoice_pda)?.data)?;
+    let due_date = invoice.due_date;
+    let (prev, next) = insert_position(&entries, due_date);
+    println!(
+        "Queue {} has {} entries; inserting due {} between {:?} and {:?}",
+        payment_queue_pda, header.count, due_date, prev, next
+    );
+
+    let ix = ix::add_to_payment_queue(
+        program_id,
+        &ix::AddToPaymentQueueAccounts {
+            signer: keypair.pubkey(), // org authority or oracle signer (pays entry rent)
+            org_config: *org_config_pda,
+            invoice_account: *invoice_pda,
+            vendor_account: invoice.vendor,
+            payment_queue: payment_queue_pda,
+            queue_entry: entry_pda,
+            prev_entry: prev,
+            next_entry: next,
+        },
+    );
+
+    let recent_blockhash = rpc_client.get_latest_blockhash()?;
     println!("Sending transaction to add invoice to payment queue...");
     let tx = Transaction::new_signed_with_payer(
         &[ix],
@@ -113,7 +246,7 @@ pub async fn add_to_payment_queue(
     match rpc_client.send_and_confirm_transaction(&tx) {
         Ok(sig) => {
             println!("Added to payment queue successfully. Transaction: {}", sig);
-            Ok(())
+            Ok(sig)
         }
         Err(e) => {
             eprintln!("Failed to add to payment queue: {}", e);
@@ -132,3 +265,20 @@ pub async fn add_to_payment_queue(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognises_program_errors() {
+        let rejected = |error: TransactionError| -> Box<dyn std::error::Error> { ClientError::from(error).into() };
+        let custom = |code| rejected(TransactionError::InstructionError(0, InstructionError::Custom(code)));
+
+        assert!(is_program_error(custom(ERROR_ALREADY_QUEUED).as_ref(), ERROR_ALREADY_QUEUED));
+        assert!(!is_program_error(custom(6000).as_ref(), ERROR_ALREADY_QUEUED));
+        assert!(!is_program_error(rejected(TransactionError::AccountNotFound).as_ref(), ERROR_ALREADY_QUEUED));
+        let other: Box<dyn std::error::Error> = "custom program error: 0x1782".into();
+        assert!(!is_program_error(other.as_ref(), ERROR_ALREADY_QUEUED));
+    }
+}
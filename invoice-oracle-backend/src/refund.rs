@@ -0,0 +1,207 @@
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use invoice_claim_client::instructions as ix;
+use invoice_claim_client::{
+    pda, AuditBallot, InvoiceAccount, OrgConfig, InvoiceStatus, ProgramAccount, ESCROW_EXPIRY_SECS, TOKEN_PROGRAM_ID,
+};
+
+use crate::invoices::OrgInvoices;
+
+
+/// Return escrowed funds for every invoice in `invoices` left in `Refunded`
+/// status (rejected by audit, escrow not yet returned), or still waiting on
+/// VRF or audit `ESCROW_EXPIRY_SECS` past its due date.
+///
+/// Funds go back to the org treasurer's ATA for the org mint, which is the
+/// payer ATA `fund_escrow_for_invoice` drew from.
+pub fn run_refund_cranker(
+    rpc_client: &RpcClient,
+    payer: &Keypair,
+    program_id: &Pubkey,
+    invoices: &OrgInvoices,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let now = chrono::Utc::now().timestamp();
+    let mut refunded = 0;
+    for (org_config_pda, invoices) in invoices {
+        if !invoices.iter().any(|(_, invoice)| is_refundable(invoice, now)) {
+            continue;
+        }
+
+        let org = OrgConfig::try_from_account_data(&rpc_client.get_account(org_config_pda)?.data)?;
+
+        for (invoice_pubkey, invoice) in invoices {
+            if !is_refundable(invoice, now) {
+                continue;
+            }
+
+            match refund_escrow(
+                rpc_client,
+                payer,
+                program_id,
+                invoice_pubkey,
+                invoice,
+                org_config_pda,
+                &org,
+            ) {
+                Ok(_) => refunded += 1,
+                Err(e) => eprintln!("[ERROR] refund_escrow failed for {}: {}", invoice_pubkey, e),
+            }
+        }
+    }
+
+    Ok(refunded)
+}
+
+/// Whether `refund_escrow` accepts `invoice` at `now`: rejected, or an escrow
+/// the VRF or audit never resolved before it expired.
+fn is_refundable(invoice: &InvoiceAccount, now: i64) -> bool {
+    match invoice.status {
+        InvoiceStatus::Refunded => true,
+        InvoiceStatus::InEscrowAwaitingVRF | InvoiceStatus::InEscrowAuditPending => {
+            now > invoice.due_date.saturating_add(ESCROW_EXPIRY_SECS)
+        }
+        _ => false,
+    }
+}
+
+fn refund_escrow(
+    rpc_client: &RpcClient,
+    payer: &Keypair,
+    program_id: &Pubkey,
+    invoice_pubkey: &Pubkey,
+    invoice: &InvoiceAccount,
+    org_config_pda: &Pubkey,
+    org: &OrgConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use spl_associated_token_account::instruction::create_associated_token_account;
+
+    let (treasurer, mint_pubkey) = (&org.treasurer, &org.mint);
+
+    let escrow_auth_pda = pda::escrow_authority(program_id, invoice_pubkey);
+
+    let escrow_ata = spl_associated_token_account::get_associated_token_address(&escrow_auth_pda, mint_pubkey);
+    let refund_ata = spl_associated_token_account::get_associated_token_address(treasurer, mint_pubkey);
+
+    // The program reads both token accounts, so create them if missing
+    // (an unfunded escrow simply refunds zero).
+    let mut instructions: Vec<Instruction> = vec![];
+    if rpc_client.get_account(&escrow_ata).is_err() {
+        println!("[INFO] Escrow ATA missing. Will create: {}", escrow_ata);
+        instructions.push(create_associated_token_account(
+            &payer.pubkey(),
+            &escrow_auth_pda,
+            mint_pubkey,
+            &TOKEN_PROGRAM_ID,
+        ));
+    }
+    if rpc_client.get_account(&refund_ata).is_err() {
+        println!("[INFO] Refund ATA missing. Will create: {}", refund_ata);
+        instructions.push(create_associated_token_account(
+            &payer.pubkey(),
+            treasurer,
+            mint_pubkey,
+            &TOKEN_PROGRAM_ID,
+        ));
+    }
+
+    // An expired audit may have left its ballot open; its rent goes back to whoever opened it
+    let audit_ballot = pda::audit_ballot(program_id, invoice_pubkey);
+    let rent_receiver = match rpc_client.get_account(&audit_ballot) {
+        Ok(acc) => AuditBallot::try_from_account_data(&acc.data)?.payer,
+        Err(_) => payer.pubkey(),
+    };
+
+    instructions.push(ix::refund_escrow(
+        program_id,
+        &ix::RefundEscrowAccounts {
+            org_config: *org_config_pda,
+            invoice_account: *invoice_pubkey,
+            vendor_account: invoice.vendor,
+            // Released by the refund so the document can be resubmitted
+            document_registry: pda::document_registry(program_id, org_config_pda, &invoice.ipfs_hash),
+            number_registry: pda::number_registry(
+                program_id,
+                &invoice.vendor,
+                &invoice.invoice_number,
+                &invoice.ipfs_hash,
+            ),
+            escrow_authority: escrow_auth_pda,
+            escrow_ata,
+            refund_ata,
+            audit_ballot,
+            rent_receiver,
+            signer: payer.pubkey(),
+        },
+    ));
+
+    let recent_blockhash = rpc_client.get_latest_blockhash()?;
+    let tx = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&payer.pubkey()),
+        &[payer],
+        recent_blockhash,
+    );
+
+    match rpc_client.send_and_confirm_transaction(&tx) {
+        Ok(sig) => {
+            println!("💸 Escrow refunded for invoice {}. Tx: {}", invoice_pubkey, sig);
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("❌ refund_escrow failed: {}", e);
+            if let Ok(sim) = rpc_client.simulate_transaction(&tx) {
+                eprintln!("\n===== TRANSACTION LOGS =====");
+                if let Some(logs) = sim.value.logs {
+                    for log in logs {
+                        eprintln!("{}", log);
+                    }
+                }
+                eprintln!("===========================\n");
+            }
+            Err(e.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invoice(status: InvoiceStatus, due_date: i64) -> InvoiceAccount {
+        InvoiceAccount {
+            authority: Pubkey::new_unique(),
+            vendor: Pubkey::new_unique(),
+            vendor_name: "Acme".to_string(),
+            amount: 100,
+            due_date,
+            ipfs_hash: "Qm".to_string(),
+            status,
+            timestamp: 0,
+            nonce: 0,
+            invoice_number: "INV-1".to_string(),
+            escrowed_at: 0,
+        }
+    }
+
+    #[test]
+    fn refunds_rejected_and_expired_escrows() {
+        let due = 1_000;
+        let expired = due + ESCROW_EXPIRY_SECS + 1;
+
+        assert!(is_refundable(&invoice(InvoiceStatus::Refunded, due), due));
+        for status in [InvoiceStatus::InEscrowAwaitingVRF, InvoiceStatus::InEscrowAuditPending] {
+            assert!(!is_refundable(&invoice(status, due), due + ESCROW_EXPIRY_SECS));
+            assert!(is_refundable(&invoice(status, due), expired));
+        }
+
+        // Funds that are due to the vendor, or already returned, never are
+        for status in [InvoiceStatus::InEscrowReadyToSettle, InvoiceStatus::Paid, InvoiceStatus::RefundCompleted] {
+            assert!(!is_refundable(&invoice(status, due), expired));
+        }
+    }
+}
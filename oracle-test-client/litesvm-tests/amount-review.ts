@@ -0,0 +1,192 @@
+import { describe, it } from "mocha";
+import { expect } from "chai";
+import { Program } from "@coral-xyz/anchor";
+import { InvoiceClaim } from "../target/types/invoice_claim";
+import { PublicKey, Keypair } from "@solana/web3.js";
+import { LiteSVMProvider } from "anchor-litesvm";
+import * as anchor from "@coral-xyz/anchor";
+import {
+    expectError,
+    fund,
+    getRegistryKeys,
+    getSetup,
+    getVendorPda,
+    initOrg,
+    submitInvoice as submit,
+} from "./setup";
+
+describe("Amount Review Tests", () => {
+    let litesvm: any;
+    let provider: LiteSVMProvider;
+    let program: Program<InvoiceClaim>;
+
+    // Each authority is its org's oracle; `outsider` owns a second org
+    let authority: Keypair;
+    let outsider: Keypair;
+    let orgConfigPda: PublicKey;
+    let outsiderOrgConfigPda: PublicKey;
+
+    const vendorName = "Reviewed Vendor";
+    const declared = new anchor.BN(1_000_000);
+    const dueDate = new anchor.BN(Math.floor(Date.now() / 1000) + 30 * 86_400);
+    let nextNonce = 1;
+
+    const vendorPda = (orgConfig: PublicKey) => getVendorPda(program, orgConfig, vendorName)[0];
+
+    const registryKeys = (ipfsHash: string, invoiceNumber: string) =>
+        getRegistryKeys(program, orgConfigPda, vendorPda(orgConfigPda), ipfsHash, invoiceNumber);
+
+    // Request `declared` and have the oracle report `extracted`; returns the invoice PDA.
+    // Each submission is a new document unless `ipfsHash` and `invoiceNumber` are given.
+    const submitInvoice = (extracted: anchor.BN, ipfsHash?: string, invoiceNumber?: string) => {
+        const nonce = nextNonce++;
+        return submit(program, authority, orgConfigPda, {
+            nonce,
+            vendorName,
+            ipfsHash: ipfsHash ?? `bafkreiamountreview${nonce}`,
+            invoiceNumber: invoiceNumber ?? `REV-${nonce}`,
+            amount: declared,
+            dueDate,
+            extracted,
+        });
+    };
+
+    // The registries always belong to the invoice's own org, whichever org reviews it
+    const review = async (reviewer: Keypair, invoice: PublicKey, approve: boolean, orgConfig: PublicKey = orgConfigPda) => {
+        const { ipfsHash, invoiceNumber } = await program.account.invoiceAccount.fetch(invoice);
+        return program.methods
+            .reviewInvoiceAmount(approve)
+            .accounts({
+                reviewer: reviewer.publicKey,
+                orgConfig,
+                invoiceAccount: invoice,
+                vendorAccount: vendorPda(orgConfig),
+                ...registryKeys(ipfsHash, invoiceNumber),
+            })
+            .signers([reviewer])
+            .rpc();
+    };
+
+    const setTolerance = (amountTolerance: anchor.BN, amountToleranceBps: number) =>
+        program.methods
+            .updateOrgConfig({
+                perInvoiceCap: null,
+                dailyCap: null,
+                paused: null,
+                oracleSigner: null,
+                mint: null,
+                extraOracleSigners: null,
+                amountTolerance,
+                amountToleranceBps,
+            })
+            .accounts({
+                authority: authority.publicKey,
+                orgConfig: orgConfigPda,
+            })
+            .signers([authority])
+            .rpc();
+
+    const statusOf = async (invoice: PublicKey) => (await program.account.invoiceAccount.fetch(invoice)).status;
+
+    before(async () => {
+        ({ litesvm, provider, program } = await getSetup());
+
+        authority = Keypair.generate();
+        outsider = Keypair.generate();
+        fund(litesvm, authority);
+        fund(litesvm, outsider);
+
+        orgConfigPda = await initOrg(program, authority, vendorName);
+        outsiderOrgConfigPda = await initOrg(program, outsider, vendorName);
+    });
+
+    describe("Tolerance", () => {
+        it("Validates an exact match with no tolerance configured", async () => {
+            const invoice = await submitInvoice(declared);
+            expect(await statusOf(invoice)).to.deep.equal({ validated: {} });
+        });
+
+        it("Holds any mismatch for review with no tolerance configured", async () => {
+            const invoice = await submitInvoice(declared.addn(1));
+            expect(await statusOf(invoice)).to.deep.equal({ needsReview: {} });
+        });
+
+        it("Validates a mismatch within the absolute tolerance", async () => {
+            await setTolerance(new anchor.BN(500), 0);
+            const invoice = await submitInvoice(declared.subn(500));
+            expect(await statusOf(invoice)).to.deep.equal({ validated: {} });
+        });
+
+        it("Validates a mismatch within the relative tolerance", async () => {
+            // 1% of the declared amount
+            await setTolerance(new anchor.BN(0), 100);
+            const invoice = await submitInvoice(declared.addn(10_000));
+            expect(await statusOf(invoice)).to.deep.equal({ validated: {} });
+        });
+
+        it("Holds a mismatch outside both tolerances for review", async () => {
+            await setTolerance(new anchor.BN(500), 100);
+            const invoice = await submitInvoice(declared.addn(10_001));
+            expect(await statusOf(invoice)).to.deep.equal({ needsReview: {} });
+
+            await setTolerance(new anchor.BN(0), 0);
+        });
+    });
+
+    describe("Review", () => {
+        it("Fails to review as anyone but the org authority", async () => {
+            const invoice = await submitInvoice(declared.addn(1));
+            await expectError(review(outsider, invoice, true), "Unauthorized");
+            expect(await statusOf(invoice)).to.deep.equal({ needsReview: {} });
+        });
+
+        it("Fails to review through another org the invoice does not belong to", async () => {
+            const invoice = await submitInvoice(declared.addn(1));
+            await expectError(review(outsider, invoice, true, outsiderOrgConfigPda), "InvalidVendor");
+            expect(await statusOf(invoice)).to.deep.equal({ needsReview: {} });
+        });
+
+        it("Validates a held invoice on approval", async () => {
+            const invoice = await submitInvoice(declared.addn(1));
+            await review(authority, invoice, true);
+            expect(await statusOf(invoice)).to.deep.equal({ validated: {} });
+        });
+
+        it("Rejects a held invoice on rejection", async () => {
+            const invoice = await submitInvoice(declared.addn(1));
+            await review(authority, invoice, false);
+            expect(await statusOf(invoice)).to.deep.equal({ reviewRejected: {} });
+        });
+
+        it("Releases a rejected invoice's document and number for resubmission", async () => {
+            const ipfsHash = "bafkreiamountreviewresubmitted";
+            const invoiceNumber = "REV-RESUBMITTED";
+            const rejected = await submitInvoice(declared.addn(1), ipfsHash, invoiceNumber);
+            await review(authority, rejected, false);
+
+            const { documentRegistry, numberRegistry } = registryKeys(ipfsHash, invoiceNumber);
+            for (const registry of [documentRegistry, numberRegistry]) {
+                const { invoice } = await program.account.invoiceRegistry.fetch(registry);
+                expect(invoice.equals(PublicKey.default)).to.be.true;
+            }
+
+            const resubmitted = await submitInvoice(declared, ipfsHash, invoiceNumber);
+            expect(await statusOf(resubmitted)).to.deep.equal({ validated: {} });
+            const { invoice } = await program.account.invoiceRegistry.fetch(documentRegistry);
+            expect(invoice.equals(resubmitted)).to.be.true;
+        });
+
+        it("Keeps an approved invoice's document claimed", async () => {
+            const ipfsHash = "bafkreiamountreviewapproved";
+            const approved = await submitInvoice(declared.addn(1), ipfsHash, "REV-APPROVED");
+            await review(authority, approved, true);
+
+            await expectError(submitInvoice(declared, ipfsHash, "REV-APPROVED-AGAIN"), "DuplicateDocument");
+        });
+
+        it("Fails to review an invoice that is not held", async () => {
+            const invoice = await submitInvoice(declared);
+            await expectError(review(authority, invoice, true), "InvalidStatus");
+        });
+    });
+});
@@ -0,0 +1,286 @@
+import { describe, it } from "mocha";
+import { expect } from "chai";
+import { Program } from "@coral-xyz/anchor";
+import { InvoiceClaim } from "../target/types/invoice_claim";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { LiteSVMProvider } from "anchor-litesvm";
+import {
+    expectError,
+    fund,
+    getAuditBallotPda,
+    getSetup,
+    getVendorPda,
+    initOrg,
+    seedInvoice,
+} from "./setup";
+
+describe("Multi-Signature Audit Tests", () => {
+    let litesvm: any;
+    let provider: LiteSVMProvider;
+    let program: Program<InvoiceClaim>;
+    let authority: Keypair;
+    let auditors: Keypair[];
+    // Owns a second org with itself as sole decider
+    let outsider: Keypair;
+
+    let orgConfigPda: PublicKey;
+    let outsiderOrgConfigPda: PublicKey;
+
+    const vendorName = "Audited Vendor";
+
+    const vendorPda = (orgConfig: PublicKey) => getVendorPda(program, orgConfig, vendorName)[0];
+
+    const ballotPda = (invoice: PublicKey) => getAuditBallotPda(program, invoice)[0];
+
+    // Write an AuditPending invoice straight into the SVM; the VRF flow that
+    // normally selects one for audit is not available locally.
+    const seedAuditPendingInvoice = (nonce: number, status: object = { inEscrowAuditPending: {} }) =>
+        seedInvoice(litesvm, program, authority.publicKey, {
+            nonce,
+            vendor: vendorPda(orgConfigPda),
+            vendorName,
+            status,
+            dueDate: Math.floor(Date.now() / 1000) + 3600,
+            ipfsHash: "bafkreiaudittest",
+            invoiceNumber: "",
+        });
+
+    const setAuditors = (keys: PublicKey[] | null, threshold: number | null) =>
+        program.methods
+            .updateOrgConfig({
+                perInvoiceCap: null,
+                dailyCap: null,
+                paused: null,
+                oracleSigner: null,
+                mint: null,
+                extraOracleSigners: null,
+                amountTolerance: null,
+                amountToleranceBps: null,
+                treasurer: null,
+                auditor: null,
+                vendorManager: null,
+                auditors: keys,
+                auditThreshold: threshold,
+            })
+            .accounts({
+                authority: authority.publicKey,
+                orgConfig: orgConfigPda,
+            })
+            .signers([authority])
+            .rpc();
+
+    const vote = (
+        reviewer: Keypair,
+        invoice: PublicKey,
+        approve: boolean,
+        rentReceiver: PublicKey,
+        orgConfig: PublicKey = orgConfigPda
+    ) =>
+        program.methods
+            .auditDecide(approve)
+            .accounts({
+                reviewer: reviewer.publicKey,
+                orgConfig,
+                invoiceAccount: invoice,
+                vendorAccount: vendorPda(orgConfig),
+                auditBallot: ballotPda(invoice),
+                rentReceiver,
+                systemProgram: SystemProgram.programId,
+            })
+            .signers([reviewer])
+            .rpc();
+
+    const requestVrf = (payer: Keypair, invoice: PublicKey, orgConfig: PublicKey = orgConfigPda) =>
+        program.methods
+            .requestInvoiceAuditVrf(42)
+            .accounts({
+                payer: payer.publicKey,
+                orgConfig,
+                invoiceAccount: invoice,
+                vendorAccount: vendorPda(orgConfig),
+            })
+            .signers([payer])
+            .rpc();
+
+    // The VRF program identity does not sign, so the callback can be replayed directly
+    const vrfCallback = (invoice: PublicKey, orgConfig: PublicKey, vendorAccount: PublicKey) =>
+        program.methods
+            .callbackInvoiceVrf(Array.from(Buffer.alloc(32)))
+            .accounts({ invoiceAccount: invoice, orgConfig, vendorAccount })
+            .rpc();
+
+    before(async () => {
+        ({ litesvm, provider, program } = await getSetup());
+
+        authority = Keypair.generate();
+        auditors = [Keypair.generate(), Keypair.generate(), Keypair.generate()];
+        outsider = Keypair.generate();
+        [authority, outsider, ...auditors].forEach((keypair) => fund(litesvm, keypair));
+
+        // Every invoice is picked for audit
+        orgConfigPda = await initOrg(program, authority, vendorName, { auditRateBps: 10_000 });
+        outsiderOrgConfigPda = await initOrg(program, outsider, vendorName, { auditRateBps: 10_000 });
+
+        // Stand-in for the VRF program so requests get past account loading
+        litesvm.addProgramFromFile(
+            new PublicKey("Vrf1RNUjXmQGjmQrQLvJHs9SNkvDJEsRVFPkfSQUwGz"),
+            "target/deploy/invoice_claim.so"
+        );
+    });
+
+    describe("VRF Selection", () => {
+        it("Fails to request VRF as anyone but the org authority or oracle", async () => {
+            const invoice = await seedAuditPendingInvoice(10, { inEscrowAwaitingVrf: {} });
+            await expectError(requestVrf(outsider, invoice), "Unauthorized");
+        });
+
+        it("Fails to request VRF through another org the invoice does not belong to", async () => {
+            const invoice = await seedAuditPendingInvoice(11, { inEscrowAwaitingVrf: {} });
+            await expectError(requestVrf(outsider, invoice, outsiderOrgConfigPda), "InvalidVendor");
+        });
+
+        it("Fails to apply the VRF outcome with another org's audit rate", async () => {
+            const invoice = await seedAuditPendingInvoice(12, { inEscrowAwaitingVrf: {} });
+
+            await expectError(
+                vrfCallback(invoice, outsiderOrgConfigPda, vendorPda(orgConfigPda)),
+                "WrongOrg"
+            );
+            await expectError(
+                vrfCallback(invoice, outsiderOrgConfigPda, vendorPda(outsiderOrgConfigPda)),
+                "InvalidVendor"
+            );
+
+            // Every invoice is audited at the org's 100% rate
+            await vrfCallback(invoice, orgConfigPda, vendorPda(orgConfigPda));
+            const updated = await program.account.invoiceAccount.fetch(invoice);
+            expect(updated.status).to.deep.equal({ inEscrowAuditPending: {} });
+        });
+    });
+
+    describe("Auditor Set", () => {
+        it("Lets the authority decide alone while no auditor set is configured", async () => {
+            const invoice = await seedAuditPendingInvoice(1);
+            await vote(authority, invoice, true, authority.publicKey);
+
+            const updated = await program.account.invoiceAccount.fetch(invoice);
+            expect(updated.status).to.deep.equal({ inEscrowReadyToSettle: {} });
+            expect(await program.account.auditBallot.fetchNullable(ballotPda(invoice))).to.be.null;
+        });
+
+        it("Fails with a threshold above the number of auditors", async () => {
+            await expectError(
+                setAuditors(auditors.map((a) => a.publicKey), 4),
+                "InvalidAuditThreshold"
+            );
+        });
+
+        it("Fails with a zero threshold", async () => {
+            await expectError(setAuditors(auditors.map((a) => a.publicKey), 0), "InvalidAuditThreshold");
+        });
+
+        it("Fails with a duplicate auditor", async () => {
+            const key = auditors[0].publicKey;
+            await expectError(setAuditors([key, key], 1), "InvalidWallet");
+        });
+
+        it("Configures a 2-of-3 auditor set", async () => {
+            await setAuditors(auditors.map((a) => a.publicKey), 2);
+
+            const orgConfig = await program.account.orgConfig.fetch(orgConfigPda);
+            expect(orgConfig.auditors.map((a) => a.toString())).to.deep.equal(
+                auditors.map((a) => a.publicKey.toString())
+            );
+            expect(orgConfig.auditThreshold).to.equal(2);
+        });
+    });
+
+    describe("Ballots", () => {
+        let invoice: PublicKey;
+
+        before(async () => {
+            invoice = await seedAuditPendingInvoice(2);
+        });
+
+        it("Fails to vote as a key outside the auditor set", async () => {
+            await expectError(vote(authority, invoice, true, authority.publicKey), "Unauthorized");
+        });
+
+        it("Fails to decide through another org the invoice does not belong to", async () => {
+            // The outsider decides alone in its own org; that must not reach this invoice
+            await expectError(
+                vote(outsider, invoice, true, outsider.publicKey, outsiderOrgConfigPda),
+                "InvalidVendor"
+            );
+
+            const updated = await program.account.invoiceAccount.fetch(invoice);
+            expect(updated.status).to.deep.equal({ inEscrowAuditPending: {} });
+            expect(await program.account.auditBallot.fetchNullable(ballotPda(invoice))).to.be.null;
+        });
+
+        it("Records a vote without moving the invoice before quorum", async () => {
+            await vote(auditors[0], invoice, true, auditors[0].publicKey);
+
+            const ballot = await program.account.auditBallot.fetch(ballotPda(invoice));
+            expect(ballot.approvals.map((a) => a.toString())).to.deep.equal([auditors[0].publicKey.toString()]);
+            expect(ballot.rejections).to.be.empty;
+            expect(ballot.threshold).to.equal(2);
+            expect(ballot.payer.toString()).to.equal(auditors[0].publicKey.toString());
+
+            const updated = await program.account.invoiceAccount.fetch(invoice);
+            expect(updated.status).to.deep.equal({ inEscrowAuditPending: {} });
+        });
+
+        it("Fails when an auditor votes twice", async () => {
+            await expectError(vote(auditors[0], invoice, false, auditors[0].publicKey), "AlreadyVoted");
+        });
+
+        it("Fails to close the ballot to anyone but its payer", async () => {
+            await expectError(vote(auditors[1], invoice, true, auditors[1].publicKey), "InvalidWallet");
+        });
+
+        it("Approves and closes the ballot once the quorum approves", async () => {
+            await vote(auditors[1], invoice, true, auditors[0].publicKey);
+
+            const updated = await program.account.invoiceAccount.fetch(invoice);
+            expect(updated.status).to.deep.equal({ inEscrowReadyToSettle: {} });
+            expect(await program.account.auditBallot.fetchNullable(ballotPda(invoice))).to.be.null;
+        });
+
+        it("Refunds once the quorum rejects", async () => {
+            const rejected = await seedAuditPendingInvoice(3);
+
+            await vote(auditors[2], rejected, false, auditors[2].publicKey);
+            await vote(auditors[0], rejected, true, auditors[2].publicKey);
+
+            let updated = await program.account.invoiceAccount.fetch(rejected);
+            expect(updated.status).to.deep.equal({ inEscrowAuditPending: {} });
+
+            await vote(auditors[1], rejected, false, auditors[2].publicKey);
+
+            updated = await program.account.invoiceAccount.fetch(rejected);
+            expect(updated.status).to.deep.equal({ refunded: {} });
+            expect(await program.account.auditBallot.fetchNullable(ballotPda(rejected))).to.be.null;
+        });
+
+        it("Rejects as soon as approval can no longer reach the quorum", async () => {
+            const fourth = Keypair.generate();
+            fund(litesvm, fourth);
+            await setAuditors([...auditors, fourth].map((a) => a.publicKey), 3);
+
+            const split = await seedAuditPendingInvoice(4);
+            await vote(auditors[0], split, true, auditors[0].publicKey);
+            await vote(auditors[1], split, false, auditors[0].publicKey);
+
+            const ballot = await program.account.auditBallot.fetch(ballotPda(split));
+            expect(ballot.voters).to.equal(4);
+
+            // Two rejections out of four leave only two possible approvals for a 3-of-4 quorum
+            await vote(auditors[2], split, false, auditors[0].publicKey);
+
+            const updated = await program.account.invoiceAccount.fetch(split);
+            expect(updated.status).to.deep.equal({ refunded: {} });
+            expect(await program.account.auditBallot.fetchNullable(ballotPda(split))).to.be.null;
+        });
+    });
+});
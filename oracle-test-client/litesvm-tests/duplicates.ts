@@ -0,0 +1,95 @@
+import { describe, it } from "mocha";
+import { expect } from "chai";
+import { Program } from "@coral-xyz/anchor";
+import { InvoiceClaim } from "../target/types/invoice_claim";
+import { PublicKey, Keypair } from "@solana/web3.js";
+import { LiteSVMProvider } from "anchor-litesvm";
+import * as anchor from "@coral-xyz/anchor";
+import {
+    expectError,
+    fund,
+    getRegistryKeys,
+    getRequestPda,
+    getSetup,
+    getVendorPda,
+    initOrg,
+    submitInvoice as submit,
+} from "./setup";
+
+describe("Duplicate Invoice Detection Tests", () => {
+    let litesvm: any;
+    let provider: LiteSVMProvider;
+    let program: Program<InvoiceClaim>;
+    let authority: Keypair;
+
+    let orgConfigPda: PublicKey;
+    let vendorPda: PublicKey;
+
+    const vendorName = "Duplicate Test Vendor";
+    const dueDate = new anchor.BN(Math.floor(Date.now() / 1000) + 30 * 86_400);
+    let nextNonce = 1;
+
+    // Request extraction of `ipfsHash` and submit the oracle result for it
+    const submitInvoice = async (ipfsHash: string, invoiceNumber: string) => {
+        const nonce = nextNonce++;
+        const invoicePda = await submit(program, authority, orgConfigPda, {
+            nonce,
+            vendorName,
+            ipfsHash,
+            invoiceNumber,
+            amount: new anchor.BN(1_000_000),
+            dueDate,
+        });
+        return { requestPda: getRequestPda(program, authority.publicKey, nonce)[0], invoicePda };
+    };
+
+    before(async () => {
+        ({ litesvm, provider, program } = await getSetup());
+
+        authority = Keypair.generate();
+        fund(litesvm, authority);
+
+        // org_init makes the authority the oracle signer
+        orgConfigPda = await initOrg(program, authority, vendorName);
+        [vendorPda] = getVendorPda(program, orgConfigPda, vendorName);
+    });
+
+    it("Claims both registries for a new invoice", async () => {
+        const { requestPda, invoicePda } = await submitInvoice("bafkreidupdoc1", "INV-0042");
+
+        const request = await program.account.invoiceRequest.fetch(requestPda);
+        expect(request.org.toString()).to.equal(orgConfigPda.toString());
+        const invoice = await program.account.invoiceAccount.fetch(invoicePda);
+        expect(invoice.invoiceNumber).to.equal("INV-0042");
+
+        const registries = getRegistryKeys(program, orgConfigPda, vendorPda, "bafkreidupdoc1", "INV-0042");
+        const docRegistry = await program.account.invoiceRegistry.fetch(registries.documentRegistry);
+        const numberRegistry = await program.account.invoiceRegistry.fetch(registries.numberRegistry);
+        expect(docRegistry.invoice.toString()).to.equal(invoicePda.toString());
+        expect(numberRegistry.invoice.toString()).to.equal(invoicePda.toString());
+        expect(docRegistry.org.toString()).to.equal(orgConfigPda.toString());
+    });
+
+    it("Rejects the same document under a new nonce", async () => {
+        await expectError(submitInvoice("bafkreidupdoc1", "INV-0043"), "DuplicateDocument");
+    });
+
+    it("Rejects the same invoice number written differently", async () => {
+        await expectError(submitInvoice("bafkreidupdoc2", "inv 0042"), "DuplicateInvoiceNumber");
+    });
+
+    it("Accepts a different document and number", async () => {
+        const { invoicePda } = await submitInvoice("bafkreidupdoc3", "INV-0044");
+        const invoice = await program.account.invoiceAccount.fetch(invoicePda);
+        expect(invoice.status).to.deep.equal({ validated: {} });
+    });
+
+    it("Falls back to the document for invoices without a number", async () => {
+        await submitInvoice("bafkreidupdoc4", "");
+        await expectError(submitInvoice("bafkreidupdoc4", ""), "DuplicateDocument");
+    });
+
+    it("Fails with an invoice number over 32 bytes", async () => {
+        await expectError(submitInvoice("bafkreidupdoc5", "X".repeat(33)), "InvalidInvoiceNumber");
+    });
+});
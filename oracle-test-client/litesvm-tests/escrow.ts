@@ -0,0 +1,499 @@
+import { describe, it } from "mocha";
+import { expect } from "chai";
+import { Program } from "@coral-xyz/anchor";
+import { InvoiceClaim } from "../target/types/invoice_claim";
+import { PublicKey, Keypair, SystemProgram, LAMPORTS_PER_SOL } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { LiteSVMProvider } from "anchor-litesvm";
+import * as anchor from "@coral-xyz/anchor";
+import {
+    expectError,
+    fund,
+    getAuditBallotPda,
+    getEscrowAuthorityPda,
+    getPaymentQueuePda,
+    getQueueEntryPda,
+    getRegistryPdas,
+    getSetup,
+    getVendorPda,
+    initOrg,
+    seedInvoice as seed,
+    setTokenAccount as writeTokenAccount,
+    tokenBalance as balanceOf,
+} from "./setup";
+
+describe("Escrow Tests", () => {
+    let litesvm: any;
+    let provider: LiteSVMProvider;
+    let program: Program<InvoiceClaim>;
+
+    // Two orgs on the same mint; `attacker` owns the second one
+    let authority: Keypair;
+    let attacker: Keypair;
+    let orgConfigPda: PublicKey;
+    let attackerOrgConfigPda: PublicKey;
+
+    const mint = Keypair.generate().publicKey;
+    const vendorName = "Escrow Vendor";
+    const vendorWallet = Keypair.generate().publicKey;
+    let nextNonce = 1;
+
+    const vendorPda = (orgConfig: PublicKey) => getVendorPda(program, orgConfig, vendorName)[0];
+
+    const queuePda = (orgConfig: PublicKey) => getPaymentQueuePda(program, orgConfig)[0];
+
+    const queueEntryPda = (invoice: PublicKey) => getQueueEntryPda(program, queuePda(orgConfigPda), invoice)[0];
+
+    const escrowAuthorityPda = (invoice: PublicKey) => getEscrowAuthorityPda(program, invoice)[0];
+
+    const ballotPda = (invoice: PublicKey) => getAuditBallotPda(program, invoice);
+
+    // Token account for the org mint unless another is given
+    const setTokenAccount = (owner: PublicKey, amount: bigint, tokenMint: PublicKey = mint) =>
+        writeTokenAccount(litesvm, tokenMint, owner, amount);
+
+    const tokenBalance = (address: PublicKey) => balanceOf(litesvm, address);
+
+    const svmNow = () => Number(litesvm.getClock().unixTimestamp);
+
+    // Document and invoice-number registries of an invoice in `authority`'s org, with their bumps
+    const registryPdas = (ipfsHash: string, invoiceNumber: string) =>
+        getRegistryPdas(program, orgConfigPda, vendorPda(orgConfigPda), ipfsHash, invoiceNumber);
+
+    // Write an invoice of `authority`'s org straight into the SVM in the given
+    // status, along with the registry entries it claimed when it was validated
+    const seedInvoice = (status: object, amount = 1_000_000, dueDate = svmNow() + 3600) => {
+        const nonce = nextNonce++;
+        return seed(
+            litesvm,
+            program,
+            authority.publicKey,
+            {
+                nonce,
+                vendor: vendorPda(orgConfigPda),
+                vendorName,
+                status,
+                dueDate,
+                amount: new anchor.BN(amount),
+                ipfsHash: `bafkreiescrow${nonce}`,
+                invoiceNumber: `INV-ESC-${nonce}`,
+            },
+            orgConfigPda
+        );
+    };
+
+    const fundEscrow = (payer: Keypair, orgConfig: PublicKey, invoice: PublicKey, payerAta: PublicKey, escrowAta: PublicKey) =>
+        program.methods
+            .fundEscrow()
+            .accounts({
+                orgConfig,
+                invoiceAccount: invoice,
+                vendorAccount: vendorPda(orgConfig),
+                escrowAuthority: escrowAuthorityPda(invoice),
+                payer: payer.publicKey,
+                authority: authority.publicKey,
+                payerAta,
+                escrowAta,
+                mint,
+                tokenProgram: TOKEN_PROGRAM_ID,
+            })
+            .signers([payer])
+            .rpc();
+
+    // The registries always belong to the invoice's own org, whichever org refunds it
+    const refund = async (
+        signer: Keypair,
+        orgConfig: PublicKey,
+        invoice: PublicKey,
+        escrowAta: PublicKey,
+        refundAta: PublicKey,
+        rentReceiver: PublicKey = signer.publicKey
+    ) => {
+        const { ipfsHash, invoiceNumber } = await program.account.invoiceAccount.fetch(invoice);
+        const { documentRegistry, numberRegistry } = registryPdas(ipfsHash, invoiceNumber);
+        return program.methods
+            .refundEscrow()
+            .accounts({
+                orgConfig,
+                invoiceAccount: invoice,
+                vendorAccount: vendorPda(orgConfig),
+                documentRegistry: documentRegistry[0],
+                numberRegistry: numberRegistry[0],
+                escrowAuthority: escrowAuthorityPda(invoice),
+                escrowAta,
+                refundAta,
+                auditBallot: ballotPda(invoice)[0],
+                rentReceiver,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                signer: signer.publicKey,
+            })
+            .signers([signer])
+            .rpc();
+    };
+
+    const closeInvoice = (invoice: PublicKey, ipfsHash: string, invoiceNumber: string) => {
+        const { documentRegistry, numberRegistry } = registryPdas(ipfsHash, invoiceNumber);
+        return program.methods
+            .closeInvoice()
+            .accounts({
+                invoiceAccount: invoice,
+                documentRegistry: documentRegistry[0],
+                numberRegistry: numberRegistry[0],
+                authority: authority.publicKey,
+            })
+            .signers([authority])
+            .rpc();
+    };
+
+    // Settle an unqueued invoice of `authority`'s org as its owner
+    const settle = (invoice: PublicKey, vendorAta: PublicKey, escrowAta: PublicKey) =>
+        program.methods
+            .settleToVendor()
+            .accounts({
+                orgConfig: orgConfigPda,
+                invoiceAccount: invoice,
+                vendorAccount: vendorPda(orgConfigPda),
+                paymentQueue: queuePda(orgConfigPda),
+                queueEntry: queueEntryPda(invoice),
+                prevEntry: null,
+                nextEntry: null,
+                rentReceiver: authority.publicKey,
+                escrowAuthority: escrowAuthorityPda(invoice),
+                vendorAta,
+                escrowAta,
+                mint,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                signer: authority.publicKey,
+            })
+            .signers([authority])
+            .rpc();
+
+    const setVendorActive = (active: boolean) =>
+        (active ? program.methods.activateVendor() : program.methods.deactivateVendor())
+            .accounts({
+                vendorAccount: vendorPda(orgConfigPda),
+                orgConfig: orgConfigPda,
+                authority: authority.publicKey,
+            })
+            .signers([authority])
+            .rpc();
+
+    const setCaps = (perInvoiceCap: number, dailyCap: number) =>
+        program.methods
+            .updateOrgConfig({
+                perInvoiceCap: new anchor.BN(perInvoiceCap),
+                dailyCap: new anchor.BN(dailyCap),
+                paused: null,
+                oracleSigner: null,
+                mint: null,
+                extraOracleSigners: null,
+                amountTolerance: null,
+                amountToleranceBps: null,
+            })
+            .accounts({
+                authority: authority.publicKey,
+                orgConfig: orgConfigPda,
+            })
+            .signers([authority])
+            .rpc();
+
+    // Move the SVM clock forward; a fresh blockhash lets an identical transaction be resent
+    const advanceClock = (seconds: number) => {
+        const clock = litesvm.getClock();
+        clock.unixTimestamp += BigInt(seconds);
+        litesvm.setClock(clock);
+        litesvm.expireBlockhash();
+    };
+
+    before(async () => {
+        ({ litesvm, provider, program } = await getSetup());
+
+        authority = Keypair.generate();
+        attacker = Keypair.generate();
+        fund(litesvm, authority);
+        fund(litesvm, attacker);
+
+        orgConfigPda = await initOrg(program, authority, vendorName, { mint, vendorWallet });
+        attackerOrgConfigPda = await initOrg(program, attacker, vendorName, { mint, vendorWallet });
+    });
+
+    describe("Funding", () => {
+        it("Fails to fund another org's invoice through a foreign org", async () => {
+            const invoice = await seedInvoice({ validated: {} });
+            const attackerAta = setTokenAccount(attacker.publicKey, 1_000_000n);
+            const escrowAta = setTokenAccount(escrowAuthorityPda(invoice), 0n);
+
+            // The attacker is its own org's treasurer; that must not count here
+            await expectError(
+                fundEscrow(attacker, attackerOrgConfigPda, invoice, attackerAta, escrowAta),
+                "InvalidVendor"
+            );
+
+            const orgConfig = await program.account.orgConfig.fetch(attackerOrgConfigPda);
+            expect(orgConfig.dailySpent.toNumber()).to.equal(0);
+        });
+
+        it("Funds a validated invoice from the org treasurer", async () => {
+            const invoice = await seedInvoice({ validated: {} });
+            const payerAta = setTokenAccount(authority.publicKey, 1_000_000n);
+            const escrowAta = setTokenAccount(escrowAuthorityPda(invoice), 0n);
+
+            await fundEscrow(authority, orgConfigPda, invoice, payerAta, escrowAta);
+
+            expect(tokenBalance(escrowAta)).to.equal(1_000_000n);
+            const updated = await program.account.invoiceAccount.fetch(invoice);
+            expect(updated.status).to.deep.equal({ inEscrowAwaitingVrf: {} });
+        });
+    });
+
+    describe("Settlement", () => {
+        // A due, escrowed invoice with its escrow token account
+        const seedDueInvoice = async (dueDate = svmNow() - 1) => {
+            const invoice = await seedInvoice({ inEscrowReadyToSettle: {} }, 1_000_000, dueDate);
+            return { invoice, escrowAta: setTokenAccount(escrowAuthorityPda(invoice), 1_000_000n) };
+        };
+
+        before(async () => {
+            await program.methods
+                .initPaymentQueue()
+                .accounts({
+                    authority: authority.publicKey,
+                    orgConfig: orgConfigPda,
+                    paymentQueue: queuePda(orgConfigPda),
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([authority])
+                .rpc();
+        });
+
+        it("Fails to pay an invoice that has not cleared audit", async () => {
+            const invoice = await seedInvoice({ inEscrowAuditPending: {} }, 1_000_000, svmNow() - 1);
+            const escrowAta = setTokenAccount(escrowAuthorityPda(invoice), 1_000_000n);
+            await expectError(settle(invoice, setTokenAccount(vendorWallet, 0n), escrowAta), "InvalidStatus");
+        });
+
+        it("Fails to pay out to an account not owned by the vendor wallet", async () => {
+            const { invoice, escrowAta } = await seedDueInvoice();
+            await expectError(settle(invoice, setTokenAccount(attacker.publicKey, 0n), escrowAta), "InvalidWallet");
+        });
+
+        it("Fails to pay out to an account for another mint", async () => {
+            const { invoice, escrowAta } = await seedDueInvoice();
+            const otherMint = Keypair.generate().publicKey;
+            await expectError(settle(invoice, setTokenAccount(vendorWallet, 0n, otherMint), escrowAta), "WrongMint");
+        });
+
+        it("Fails to pay an inactive vendor", async () => {
+            const { invoice, escrowAta } = await seedDueInvoice();
+            await setVendorActive(false);
+            await expectError(settle(invoice, setTokenAccount(vendorWallet, 0n), escrowAta), "VendorInactive");
+            await setVendorActive(true);
+        });
+
+        it("Fails to pay an invoice before its due date", async () => {
+            const { invoice, escrowAta } = await seedDueInvoice(svmNow() + 3600);
+            await expectError(settle(invoice, setTokenAccount(vendorWallet, 0n), escrowAta), "PaymentNotDue");
+
+            const updated = await program.account.invoiceAccount.fetch(invoice);
+            expect(updated.status).to.deep.equal({ inEscrowReadyToSettle: {} });
+        });
+
+        it("Pays the vendor and records the payment on the vendor account", async () => {
+            const previous = await program.account.vendorAccount.fetch(vendorPda(orgConfigPda));
+            const { invoice, escrowAta } = await seedDueInvoice();
+            const vendorAta = setTokenAccount(vendorWallet, 0n);
+
+            await settle(invoice, vendorAta, escrowAta);
+
+            expect(tokenBalance(vendorAta)).to.equal(1_000_000n);
+            expect(tokenBalance(escrowAta)).to.equal(0n);
+            const updated = await program.account.invoiceAccount.fetch(invoice);
+            expect(updated.status).to.deep.equal({ paid: {} });
+
+            const vendor = await program.account.vendorAccount.fetch(vendorPda(orgConfigPda));
+            expect(vendor.totalPaid.sub(previous.totalPaid).toNumber()).to.equal(1_000_000);
+            expect(vendor.lastPayment.toNumber()).to.equal(svmNow());
+        });
+    });
+
+    describe("Daily Cap", () => {
+        before(async () => {
+            await setCaps(1_500_000, 1_500_000);
+            // Start from a fresh UTC day whatever earlier tests escrowed
+            advanceClock(86_400);
+        });
+
+        it("Fails to fund past the daily cap and funds again once the window rolls over", async () => {
+            const first = await seedInvoice({ validated: {} });
+            const second = await seedInvoice({ validated: {} });
+            const payerAta = setTokenAccount(authority.publicKey, 2_000_000n);
+            const firstEscrow = setTokenAccount(escrowAuthorityPda(first), 0n);
+            const secondEscrow = setTokenAccount(escrowAuthorityPda(second), 0n);
+
+            await fundEscrow(authority, orgConfigPda, first, payerAta, firstEscrow);
+            let orgConfig = await program.account.orgConfig.fetch(orgConfigPda);
+            expect(orgConfig.dailySpent.toNumber()).to.equal(1_000_000);
+
+            // A second 1_000_000 escrow would bring the day to 2_000_000
+            await expectError(
+                fundEscrow(authority, orgConfigPda, second, payerAta, secondEscrow),
+                "DailyCapExceeded"
+            );
+            expect(tokenBalance(secondEscrow)).to.equal(0n);
+            expect((await program.account.invoiceAccount.fetch(second)).status).to.deep.equal({ validated: {} });
+
+            advanceClock(86_400);
+            await fundEscrow(authority, orgConfigPda, second, payerAta, secondEscrow);
+
+            expect(tokenBalance(secondEscrow)).to.equal(1_000_000n);
+            orgConfig = await program.account.orgConfig.fetch(orgConfigPda);
+            expect(orgConfig.dailySpent.toNumber()).to.equal(1_000_000);
+            expect(orgConfig.lastResetDay.toNumber()).to.equal(Math.floor(svmNow() / 86_400));
+        });
+
+        it("Gives a same-day refund's spend back to the daily cap, but not a later one's", async () => {
+            advanceClock(86_400);
+            const payerAta = setTokenAccount(authority.publicKey, 2_000_000n);
+
+            // Fund, then reject as an audit would, and refund
+            const fundAndRefund = async () => {
+                const invoice = await seedInvoice({ validated: {} });
+                const escrowAta = setTokenAccount(escrowAuthorityPda(invoice), 0n);
+                await fundEscrow(authority, orgConfigPda, invoice, payerAta, escrowAta);
+
+                const funded = await program.account.invoiceAccount.fetch(invoice);
+                expect(funded.escrowedAt.toNumber()).to.equal(svmNow());
+                litesvm.setAccount(invoice, {
+                    lamports: LAMPORTS_PER_SOL,
+                    data: await program.coder.accounts.encode("invoiceAccount", { ...funded, status: { refunded: {} } }),
+                    executable: false,
+                    owner: program.programId,
+                });
+                return { invoice, escrowAta };
+            };
+
+            let { invoice, escrowAta } = await fundAndRefund();
+            await refund(authority, orgConfigPda, invoice, escrowAta, setTokenAccount(authority.publicKey, 0n));
+            let orgConfig = await program.account.orgConfig.fetch(orgConfigPda);
+            expect(orgConfig.dailySpent.toNumber()).to.equal(0);
+
+            // A refund after the window has rolled leaves the closed day's spend as it was
+            ({ invoice, escrowAta } = await fundAndRefund());
+            advanceClock(86_400);
+            await refund(authority, orgConfigPda, invoice, escrowAta, setTokenAccount(authority.publicKey, 0n));
+            orgConfig = await program.account.orgConfig.fetch(orgConfigPda);
+            expect(orgConfig.dailySpent.toNumber()).to.equal(1_000_000);
+        });
+    });
+
+    describe("Refund", () => {
+        it("Fails to refund another org's invoice through a foreign org", async () => {
+            const invoice = await seedInvoice({ refunded: {} });
+            const escrowAta = setTokenAccount(escrowAuthorityPda(invoice), 1_000_000n);
+            const attackerAta = setTokenAccount(attacker.publicKey, 0n);
+
+            await expectError(refund(attacker, attackerOrgConfigPda, invoice, escrowAta, attackerAta), "InvalidVendor");
+
+            expect(tokenBalance(escrowAta)).to.equal(1_000_000n);
+            const updated = await program.account.invoiceAccount.fetch(invoice);
+            expect(updated.status).to.deep.equal({ refunded: {} });
+        });
+
+        it("Fails to refund into an account outside the org", async () => {
+            const invoice = await seedInvoice({ refunded: {} });
+            const escrowAta = setTokenAccount(escrowAuthorityPda(invoice), 1_000_000n);
+
+            await expectError(
+                refund(authority, orgConfigPda, invoice, escrowAta, setTokenAccount(attacker.publicKey, 0n)),
+                "InvalidWallet"
+            );
+        });
+
+        it("Refunds a rejected invoice's escrow to the org", async () => {
+            const invoice = await seedInvoice({ refunded: {} });
+            const escrowAta = setTokenAccount(escrowAuthorityPda(invoice), 1_000_000n);
+            const refundAta = setTokenAccount(authority.publicKey, 0n);
+
+            await refund(authority, orgConfigPda, invoice, escrowAta, refundAta);
+
+            expect(tokenBalance(escrowAta)).to.equal(0n);
+            expect(tokenBalance(refundAta)).to.equal(1_000_000n);
+            const updated = await program.account.invoiceAccount.fetch(invoice);
+            expect(updated.status).to.deep.equal({ refundCompleted: {} });
+
+            // The document and invoice number are free to be submitted again
+            for (const [registry] of Object.values(registryPdas(updated.ipfsHash, updated.invoiceNumber))) {
+                const { invoice: holder } = await program.account.invoiceRegistry.fetch(registry);
+                expect(holder.equals(PublicKey.default)).to.be.true;
+            }
+        });
+
+        it("Closes the ballot of an expired audit and returns its rent to the payer", async () => {
+            const invoice = await seedInvoice({ inEscrowAuditPending: {} }, 1_000_000, svmNow() - 31 * 86_400);
+            const escrowAta = setTokenAccount(escrowAuthorityPda(invoice), 1_000_000n);
+            const ballotPayer = Keypair.generate().publicKey;
+            const [ballot, bump] = ballotPda(invoice);
+            litesvm.setAccount(ballot, {
+                lamports: LAMPORTS_PER_SOL,
+                data: await program.coder.accounts.encode("auditBallot", {
+                    invoice,
+                    org: orgConfigPda,
+                    threshold: 2,
+                    voters: 3,
+                    approvals: [authority.publicKey],
+                    rejections: [],
+                    payer: ballotPayer,
+                    bump,
+                }),
+                executable: false,
+                owner: program.programId,
+            });
+
+            // The rent goes back to whoever opened the ballot, not the caller
+            await expectError(
+                refund(authority, orgConfigPda, invoice, escrowAta, setTokenAccount(authority.publicKey, 0n)),
+                "InvalidWallet"
+            );
+
+            await refund(authority, orgConfigPda, invoice, escrowAta, setTokenAccount(authority.publicKey, 0n), ballotPayer);
+
+            const updated = await program.account.invoiceAccount.fetch(invoice);
+            expect(updated.status).to.deep.equal({ refundCompleted: {} });
+            expect(litesvm.getAccount(ballot)?.lamports ?? 0).to.equal(0);
+            expect(litesvm.getBalance(ballotPayer)).to.equal(BigInt(LAMPORTS_PER_SOL));
+        });
+
+        it("Fails to refund an escrow that has not expired", async () => {
+            const invoice = await seedInvoice({ inEscrowAwaitingVrf: {} });
+            const escrowAta = setTokenAccount(escrowAuthorityPda(invoice), 1_000_000n);
+
+            await expectError(
+                refund(authority, orgConfigPda, invoice, escrowAta, setTokenAccount(authority.publicKey, 0n)),
+                "InvalidStatus"
+            );
+        });
+    });
+
+    describe("Close", () => {
+        it("Fails to close an invoice that still holds escrow", async () => {
+            const invoice = await seedInvoice({ inEscrowReadyToSettle: {} });
+            const { ipfsHash, invoiceNumber } = await program.account.invoiceAccount.fetch(invoice);
+
+            await expectError(closeInvoice(invoice, ipfsHash, invoiceNumber), "InvalidStatus");
+            expect(litesvm.getAccount(invoice)).to.not.be.null;
+        });
+
+        it("Closes a paid invoice and releases its registries", async () => {
+            const invoice = await seedInvoice({ paid: {} });
+            const { ipfsHash, invoiceNumber } = await program.account.invoiceAccount.fetch(invoice);
+
+            await closeInvoice(invoice, ipfsHash, invoiceNumber);
+
+            expect(await program.account.invoiceAccount.fetchNullable(invoice)).to.be.null;
+            for (const [registry] of Object.values(registryPdas(ipfsHash, invoiceNumber))) {
+                const { invoice: holder } = await program.account.invoiceRegistry.fetch(registry);
+                expect(holder.equals(PublicKey.default)).to.be.true;
+            }
+        });
+    });
+});
@@ -0,0 +1,415 @@
+import { describe, it } from "mocha";
+import { expect } from "chai";
+import { EventParser, Program } from "@coral-xyz/anchor";
+import { InvoiceClaim } from "../target/types/invoice_claim";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { FailedTransactionMetadata } from "litesvm";
+import { LiteSVMProvider } from "anchor-litesvm";
+import * as anchor from "@coral-xyz/anchor";
+import {
+    fund,
+    getAuditBallotPda,
+    getEscrowAuthorityPda,
+    getInvoicePda,
+    getPaymentQueuePda,
+    getQueueEntryPda,
+    getRegistryKeys,
+    getRequestPda,
+    getSetup,
+    getVendorPda,
+    initOrg,
+    seedInvoice as seed,
+    setTokenAccount as writeTokenAccount,
+} from "./setup";
+
+describe("Event Tests", () => {
+    let litesvm: any;
+    let provider: LiteSVMProvider;
+    let program: Program<InvoiceClaim>;
+    let eventParser: EventParser;
+
+    // The authority is also the org's oracle, treasurer and sole auditor
+    let authority: Keypair;
+    let orgConfigPda: PublicKey;
+    let vendorPda: PublicKey;
+    let paymentQueuePda: PublicKey;
+
+    const mint = Keypair.generate().publicKey;
+    const vendorName = "Event Vendor";
+    const vendorWallet = Keypair.generate().publicKey;
+    const amount = new anchor.BN(1_000_000);
+    let nextNonce = 1;
+
+    const svmNow = () => Number(litesvm.getClock().unixTimestamp);
+
+    const pdaFor = (seed: "request" | "invoice", nonce: number) =>
+        (seed === "request" ? getRequestPda : getInvoicePda)(program, authority.publicKey, nonce)[0];
+
+    const registryKeys = (ipfsHash: string, invoiceNumber: string) =>
+        getRegistryKeys(program, orgConfigPda, vendorPda, ipfsHash, invoiceNumber);
+
+    const escrowAuthorityPda = (invoice: PublicKey) => getEscrowAuthorityPda(program, invoice)[0];
+
+    const setTokenAccount = (owner: PublicKey, balance: bigint) => writeTokenAccount(litesvm, mint, owner, balance);
+
+    // Write an invoice and its registry entries straight into the SVM, for the
+    // stages that need VRF to reach locally
+    const seedInvoice = (status: object, dueDate: number) => {
+        const nonce = nextNonce++;
+        return seed(
+            litesvm,
+            program,
+            authority.publicKey,
+            {
+                nonce,
+                vendor: vendorPda,
+                vendorName,
+                status,
+                dueDate,
+                amount,
+                ipfsHash: `bafkreievent${nonce}`,
+                invoiceNumber: `EVT-${nonce}`,
+            },
+            orgConfigPda
+        );
+    };
+
+    // Send through the SVM directly so the transaction logs can be decoded into events
+    const sendForEvents = async (method: any) => {
+        const tx = await method.transaction();
+        tx.recentBlockhash = litesvm.latestBlockhash();
+        tx.feePayer = authority.publicKey;
+        tx.sign(authority);
+
+        const result = litesvm.sendTransaction(tx);
+        if (result instanceof FailedTransactionMetadata) {
+            throw new Error(`Transaction failed: ${result.meta().logs().join("\n")}`);
+        }
+        return [...eventParser.parseLogs(result.logs())];
+    };
+
+    // The single event of `name` a transaction emitted
+    const eventOf = (events: { name: string; data: any }[], name: string) => {
+        const matching = events.filter((event) => event.name === name);
+        expect(matching, `${name} events`).to.have.length(1);
+        return matching[0].data;
+    };
+
+    const expectKey = (actual: PublicKey, expected: PublicKey) =>
+        expect(actual.toString()).to.equal(expected.toString());
+
+    const request = (nonce: number, ipfsHash: string) =>
+        program.methods.requestInvoiceExtraction(ipfsHash, amount, new anchor.BN(nonce)).accounts({
+            authority: authority.publicKey,
+            orgConfig: orgConfigPda,
+            invoiceRequest: pdaFor("request", nonce),
+            systemProgram: SystemProgram.programId,
+        });
+
+    const processResult = (nonce: number, ipfsHash: string, extracted: anchor.BN) => {
+        const invoiceNumber = `EVT-${nonce}`;
+        return program.methods
+            .processExtractionResult(vendorName, extracted, new anchor.BN(svmNow() + 30 * 86_400), invoiceNumber)
+            .accounts({
+                payer: authority.publicKey,
+                orgConfig: orgConfigPda,
+                vendorAccount: vendorPda,
+                invoiceRequest: pdaFor("request", nonce),
+                invoiceAccount: pdaFor("invoice", nonce),
+                ...registryKeys(ipfsHash, invoiceNumber),
+                systemProgram: SystemProgram.programId,
+            });
+    };
+
+    before(async () => {
+        ({ litesvm, provider, program } = await getSetup());
+        eventParser = new EventParser(program.programId, program.coder);
+
+        authority = Keypair.generate();
+        fund(litesvm, authority);
+
+        // The vendor is registered by a test, for its event
+        orgConfigPda = await initOrg(program, authority, null, { mint });
+        [vendorPda] = getVendorPda(program, orgConfigPda, vendorName);
+        [paymentQueuePda] = getPaymentQueuePda(program, orgConfigPda);
+
+        await program.methods
+            .initPaymentQueue()
+            .accounts({
+                authority: authority.publicKey,
+                orgConfig: orgConfigPda,
+                paymentQueue: paymentQueuePda,
+                systemProgram: SystemProgram.programId,
+            })
+            .signers([authority])
+            .rpc();
+    });
+
+    describe("Org", () => {
+        it("Emits OrgConfigUpdated with the resulting config", async () => {
+            const events = await sendForEvents(
+                program.methods
+                    .updateOrgConfig({
+                        perInvoiceCap: new anchor.BN(2_000_000),
+                        dailyCap: new anchor.BN(5_000_000),
+                        paused: null,
+                        oracleSigner: null,
+                        mint: null,
+                        extraOracleSigners: null,
+                        amountTolerance: null,
+                        amountToleranceBps: null,
+                    })
+                    .accounts({ authority: authority.publicKey, orgConfig: orgConfigPda })
+            );
+
+            const event = eventOf(events, "orgConfigUpdated");
+            expectKey(event.org, orgConfigPda);
+            expect(event.perInvoiceCap.toNumber()).to.equal(2_000_000);
+            expect(event.dailyCap.toNumber()).to.equal(5_000_000);
+            expect(event.paused).to.equal(false);
+            expectKey(event.mint, mint);
+            expectKey(event.treasurer, authority.publicKey);
+        });
+    });
+
+    describe("Vendor", () => {
+        it("Emits VendorRegistered", async () => {
+            const events = await sendForEvents(
+                program.methods.registerVendor(vendorName, vendorWallet).accounts({
+                    vendorAccount: vendorPda,
+                    orgConfig: orgConfigPda,
+                    authority: authority.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+            );
+
+            const event = eventOf(events, "vendorRegistered");
+            expectKey(event.vendor, vendorPda);
+            expectKey(event.org, orgConfigPda);
+            expect(event.vendorName).to.equal(vendorName);
+            expectKey(event.wallet, vendorWallet);
+        });
+
+        it("Emits VendorDeactivated and VendorActivated", async () => {
+            const accounts = { vendorAccount: vendorPda, orgConfig: orgConfigPda, authority: authority.publicKey };
+
+            const deactivated = eventOf(
+                await sendForEvents(program.methods.deactivateVendor().accounts(accounts)),
+                "vendorDeactivated"
+            );
+            expectKey(deactivated.vendor, vendorPda);
+            expectKey(deactivated.org, orgConfigPda);
+
+            const activated = eventOf(
+                await sendForEvents(program.methods.activateVendor().accounts(accounts)),
+                "vendorActivated"
+            );
+            expectKey(activated.vendor, vendorPda);
+            expectKey(activated.org, orgConfigPda);
+        });
+    });
+
+    describe("Invoice", () => {
+        it("Emits RequestCreated and InvoiceValidated", async () => {
+            const nonce = nextNonce++;
+            const ipfsHash = "bafkreieventvalid";
+
+            const created = eventOf(await sendForEvents(request(nonce, ipfsHash)), "requestCreated");
+            expectKey(created.request, pdaFor("request", nonce));
+            expectKey(created.authority, authority.publicKey);
+            expectKey(created.org, orgConfigPda);
+            expect(created.ipfsHash).to.equal(ipfsHash);
+            expect(created.amount.toNumber()).to.equal(amount.toNumber());
+            expect(created.nonce.toNumber()).to.equal(nonce);
+
+            const events = await sendForEvents(processResult(nonce, ipfsHash, amount));
+            const validated = eventOf(events, "invoiceValidated");
+            expectKey(validated.invoice, pdaFor("invoice", nonce));
+            expectKey(validated.request, pdaFor("request", nonce));
+            expectKey(validated.org, orgConfigPda);
+            expectKey(validated.vendor, vendorPda);
+            expect(validated.amount.toNumber()).to.equal(amount.toNumber());
+            expect(validated.status).to.deep.equal({ validated: {} });
+            expect(events.filter((event) => event.name === "amountMismatch")).to.be.empty;
+        });
+
+        it("Emits AmountMismatch and AmountReviewed", async () => {
+            const nonce = nextNonce++;
+            const ipfsHash = "bafkreieventmismatch";
+            const extracted = amount.addn(1);
+
+            await sendForEvents(request(nonce, ipfsHash));
+            const mismatch = eventOf(await sendForEvents(processResult(nonce, ipfsHash, extracted)), "amountMismatch");
+            expectKey(mismatch.invoice, pdaFor("invoice", nonce));
+            expect(mismatch.declaredAmount.toNumber()).to.equal(amount.toNumber());
+            expect(mismatch.extractedAmount.toNumber()).to.equal(extracted.toNumber());
+            expect(mismatch.status).to.deep.equal({ needsReview: {} });
+
+            const reviewed = eventOf(
+                await sendForEvents(
+                    program.methods.reviewInvoiceAmount(true).accounts({
+                        reviewer: authority.publicKey,
+                        orgConfig: orgConfigPda,
+                        invoiceAccount: pdaFor("invoice", nonce),
+                        vendorAccount: vendorPda,
+                        ...registryKeys(ipfsHash, `EVT-${nonce}`),
+                    })
+                ),
+                "amountReviewed"
+            );
+            expectKey(reviewed.reviewer, authority.publicKey);
+            expect(reviewed.approved).to.equal(true);
+            expect(reviewed.status).to.deep.equal({ validated: {} });
+        });
+    });
+
+    describe("Escrow", () => {
+        it("Emits EscrowFunded", async () => {
+            const invoice = await seedInvoice({ validated: {} }, svmNow() + 3600);
+            const payerAta = setTokenAccount(authority.publicKey, 1_000_000n);
+
+            const events = await sendForEvents(
+                program.methods.fundEscrow().accounts({
+                    orgConfig: orgConfigPda,
+                    invoiceAccount: invoice,
+                    vendorAccount: vendorPda,
+                    escrowAuthority: escrowAuthorityPda(invoice),
+                    payer: authority.publicKey,
+                    authority: authority.publicKey,
+                    payerAta,
+                    escrowAta: setTokenAccount(escrowAuthorityPda(invoice), 0n),
+                    mint,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                })
+            );
+
+            const event = eventOf(events, "escrowFunded");
+            expectKey(event.invoice, invoice);
+            expectKey(event.org, orgConfigPda);
+            expectKey(event.payer, authority.publicKey);
+            expect(event.amount.toNumber()).to.equal(amount.toNumber());
+            expect(event.dailySpent.toNumber()).to.equal(amount.toNumber());
+            expect(event.status).to.deep.equal({ inEscrowAwaitingVrf: {} });
+        });
+
+        it("Emits Refunded", async () => {
+            const invoice = await seedInvoice({ refunded: {} }, svmNow() + 3600);
+            const { ipfsHash, invoiceNumber } = await program.account.invoiceAccount.fetch(invoice);
+            const refundAta = setTokenAccount(authority.publicKey, 0n);
+
+            const events = await sendForEvents(
+                program.methods.refundEscrow().accounts({
+                    orgConfig: orgConfigPda,
+                    invoiceAccount: invoice,
+                    vendorAccount: vendorPda,
+                    ...registryKeys(ipfsHash, invoiceNumber),
+                    escrowAuthority: escrowAuthorityPda(invoice),
+                    escrowAta: setTokenAccount(escrowAuthorityPda(invoice), 1_000_000n),
+                    refundAta,
+                    auditBallot: getAuditBallotPda(program, invoice)[0],
+                    rentReceiver: authority.publicKey,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                    signer: authority.publicKey,
+                })
+            );
+
+            const event = eventOf(events, "refunded");
+            expectKey(event.invoice, invoice);
+            expectKey(event.refundAta, refundAta);
+            expect(event.amount.toNumber()).to.equal(amount.toNumber());
+            expect(event.status).to.deep.equal({ refundCompleted: {} });
+        });
+    });
+
+    describe("Audit and Settlement", () => {
+        let invoice: PublicKey;
+
+        before(async () => {
+            invoice = await seedInvoice({ inEscrowAuditPending: {} }, svmNow() - 1);
+        });
+
+        it("Emits AuditVoteCast and AuditDecided", async () => {
+            const events = await sendForEvents(
+                program.methods.auditDecide(true).accounts({
+                    reviewer: authority.publicKey,
+                    orgConfig: orgConfigPda,
+                    invoiceAccount: invoice,
+                    vendorAccount: vendorPda,
+                    auditBallot: getAuditBallotPda(program, invoice)[0],
+                    rentReceiver: authority.publicKey,
+                    systemProgram: SystemProgram.programId,
+                })
+            );
+
+            const vote = eventOf(events, "auditVoteCast");
+            expectKey(vote.reviewer, authority.publicKey);
+            expect(vote.approve).to.equal(true);
+            expect(vote.approvals).to.equal(1);
+            expect(vote.rejections).to.equal(0);
+            expect(vote.threshold).to.equal(1);
+
+            const decided = eventOf(events, "auditDecided");
+            expectKey(decided.invoice, invoice);
+            expect(decided.approved).to.equal(true);
+            expect(decided.status).to.deep.equal({ inEscrowReadyToSettle: {} });
+        });
+
+        it("Emits QueuedForPayment", async () => {
+            const entry = getQueueEntryPda(program, paymentQueuePda, invoice)[0];
+
+            const event = eventOf(
+                await sendForEvents(
+                    program.methods.addToPaymentQueue().accounts({
+                        signer: authority.publicKey,
+                        orgConfig: orgConfigPda,
+                        invoiceAccount: invoice,
+                        vendorAccount: vendorPda,
+                        paymentQueue: paymentQueuePda,
+                        queueEntry: entry,
+                        prevEntry: null,
+                        nextEntry: null,
+                        systemProgram: SystemProgram.programId,
+                    })
+                ),
+                "queuedForPayment"
+            );
+            expectKey(event.invoice, invoice);
+            expectKey(event.queue, paymentQueuePda);
+            expectKey(event.entry, entry);
+            expect(event.amount.toNumber()).to.equal(amount.toNumber());
+        });
+
+        it("Emits Settled", async () => {
+            const vendorAta = setTokenAccount(vendorWallet, 0n);
+
+            const event = eventOf(
+                await sendForEvents(
+                    program.methods.settleToVendor().accounts({
+                        orgConfig: orgConfigPda,
+                        invoiceAccount: invoice,
+                        vendorAccount: vendorPda,
+                        paymentQueue: paymentQueuePda,
+                        queueEntry: getQueueEntryPda(program, paymentQueuePda, invoice)[0],
+                        prevEntry: null,
+                        nextEntry: null,
+                        rentReceiver: authority.publicKey,
+                        escrowAuthority: escrowAuthorityPda(invoice),
+                        vendorAta,
+                        escrowAta: setTokenAccount(escrowAuthorityPda(invoice), 1_000_000n),
+                        mint,
+                        tokenProgram: TOKEN_PROGRAM_ID,
+                        signer: authority.publicKey,
+                    })
+                ),
+                "settled"
+            );
+            expectKey(event.invoice, invoice);
+            expectKey(event.vendor, vendorPda);
+            expectKey(event.vendorAta, vendorAta);
+            expect(event.amount.toNumber()).to.equal(amount.toNumber());
+            expect(event.paidAt.toNumber()).to.equal(svmNow());
+            expect(event.status).to.deep.equal({ paid: {} });
+        });
+    });
+});
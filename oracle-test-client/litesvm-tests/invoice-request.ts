@@ -0,0 +1,182 @@
+import { describe, it } from "mocha";
+import { expect } from "chai";
+import { Program } from "@coral-xyz/anchor";
+import { InvoiceClaim } from "../target/types/invoice_claim";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { LiteSVMProvider } from "anchor-litesvm";
+import * as anchor from "@coral-xyz/anchor";
+import {
+    expectError,
+    fund,
+    getInvoicePda,
+    getRegistryKeys,
+    getRequestPda,
+    getSetup,
+    getVendorPda,
+    initOrg,
+} from "./setup";
+
+describe("Invoice Request Org Tests", () => {
+    let litesvm: any;
+    let provider: LiteSVMProvider;
+    let program: Program<InvoiceClaim>;
+
+    // Two tenants with a vendor of the same name; each authority is its org's oracle
+    let authority: Keypair;
+    let otherAuthority: Keypair;
+    let orgConfigPda: PublicKey;
+    let otherOrgConfigPda: PublicKey;
+
+    const vendorName = "Shared Vendor Name";
+    const amount = new anchor.BN(1_000_000);
+    const dueDate = new anchor.BN(Math.floor(Date.now() / 1000) + 30 * 86_400);
+    let nextNonce = 1;
+
+    const vendorPda = (orgConfig: PublicKey) => getVendorPda(program, orgConfig, vendorName)[0];
+
+    const pdaFor = (seed: "request" | "invoice", nonce: number) =>
+        (seed === "request" ? getRequestPda : getInvoicePda)(program, authority.publicKey, nonce)[0];
+
+    const setPaused = (paused: boolean) =>
+        program.methods
+            .updateOrgConfig({
+                perInvoiceCap: null,
+                dailyCap: null,
+                paused,
+                oracleSigner: null,
+                mint: null,
+                extraOracleSigners: null,
+                amountTolerance: null,
+                amountToleranceBps: null,
+            })
+            .accounts({
+                authority: authority.publicKey,
+                orgConfig: orgConfigPda,
+            })
+            .signers([authority])
+            .rpc();
+
+    const requestExtraction = async (ipfsHash: string) => {
+        const nonce = nextNonce++;
+        const requestPda = pdaFor("request", nonce);
+
+        await program.methods
+            .requestInvoiceExtraction(ipfsHash, amount, new anchor.BN(nonce))
+            .accounts({
+                authority: authority.publicKey,
+                orgConfig: orgConfigPda,
+                invoiceRequest: requestPda,
+                systemProgram: SystemProgram.programId,
+            })
+            .signers([authority])
+            .rpc();
+
+        return { nonce, requestPda };
+    };
+
+    // Submit the oracle result for a request as `oracle`, against `orgConfig`
+    const processResult = (oracle: Keypair, orgConfig: PublicKey, nonce: number, ipfsHash: string) => {
+        const vendor = vendorPda(orgConfig);
+        const invoiceNumber = `INV-${nonce}`;
+
+        return program.methods
+            .processExtractionResult(vendorName, amount, dueDate, invoiceNumber)
+            .accounts({
+                payer: oracle.publicKey,
+                orgConfig,
+                vendorAccount: vendor,
+                invoiceRequest: pdaFor("request", nonce),
+                invoiceAccount: pdaFor("invoice", nonce),
+                ...getRegistryKeys(program, orgConfig, vendor, ipfsHash, invoiceNumber),
+                systemProgram: SystemProgram.programId,
+            })
+            .signers([oracle])
+            .rpc();
+    };
+
+    // Write an extraction result straight onto the request as `oracle`, against `orgConfig`
+    const commitResult = (oracle: Keypair, orgConfig: PublicKey, nonce: number) =>
+        program.methods
+            .commitInvoiceExtraction(amount, Keypair.generate().publicKey)
+            .accounts({
+                invoiceRequest: pdaFor("request", nonce),
+                orgConfig,
+                oracle: oracle.publicKey,
+            })
+            .signers([oracle])
+            .rpc();
+
+    before(async () => {
+        ({ litesvm, provider, program } = await getSetup());
+
+        authority = Keypair.generate();
+        otherAuthority = Keypair.generate();
+        fund(litesvm, authority);
+        fund(litesvm, otherAuthority);
+
+        orgConfigPda = await initOrg(program, authority, vendorName);
+        otherOrgConfigPda = await initOrg(program, otherAuthority, vendorName);
+
+        // commit_invoice_extraction takes the ephemeral rollup's magic program but never
+        // calls into it here, so any deployed program can stand in for it locally
+        litesvm.addProgramFromFile(
+            new PublicKey("Magic11111111111111111111111111111111111111"),
+            "target/deploy/invoice_claim.so"
+        );
+    });
+
+    it("Records the target org on the request", async () => {
+        const { nonce, requestPda } = await requestExtraction("bafkreiorgreq1");
+
+        const request = await program.account.invoiceRequest.fetch(requestPda);
+        expect(request.org.toString()).to.equal(orgConfigPda.toString());
+
+        await processResult(authority, orgConfigPda, nonce, "bafkreiorgreq1");
+        const invoice = await program.account.invoiceAccount.fetch(pdaFor("invoice", nonce));
+        expect(invoice.status).to.deep.equal({ validated: {} });
+    });
+
+    it("Fails to request extraction from a paused org", async () => {
+        await setPaused(true);
+        await expectError(requestExtraction("bafkreiorgreq2"), "OrgPaused");
+        await setPaused(false);
+    });
+
+    it("Fails to process a request against another org", async () => {
+        const { nonce, requestPda } = await requestExtraction("bafkreiorgreq3");
+
+        // The other org's oracle and same-named vendor must not pick up this request
+        await expectError(processResult(otherAuthority, otherOrgConfigPda, nonce, "bafkreiorgreq3"), "WrongOrg");
+
+        const request = await program.account.invoiceRequest.fetch(requestPda);
+        expect(request.status).to.deep.equal({ pending: {} });
+    });
+
+    it("Fails to commit a result as a non-oracle", async () => {
+        const { nonce, requestPda } = await requestExtraction("bafkreiorgreq4");
+
+        await expectError(commitResult(otherAuthority, orgConfigPda, nonce), "Unauthorized");
+
+        const request = await program.account.invoiceRequest.fetch(requestPda);
+        expect(request.status).to.deep.equal({ pending: {} });
+    });
+
+    it("Fails to commit a result against another org", async () => {
+        const { nonce, requestPda } = await requestExtraction("bafkreiorgreq5");
+
+        // The other org's own oracle must not write results for this org's request
+        await expectError(commitResult(otherAuthority, otherOrgConfigPda, nonce), "WrongOrg");
+
+        const request = await program.account.invoiceRequest.fetch(requestPda);
+        expect(request.status).to.deep.equal({ pending: {} });
+    });
+
+    it("Commits a result as the org's oracle", async () => {
+        const { nonce, requestPda } = await requestExtraction("bafkreiorgreq6");
+
+        await commitResult(authority, orgConfigPda, nonce);
+
+        const request = await program.account.invoiceRequest.fetch(requestPda);
+        expect(request.status).to.deep.equal({ completed: {} });
+    });
+});
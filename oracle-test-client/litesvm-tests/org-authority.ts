@@ -0,0 +1,278 @@
+import { describe, it } from "mocha";
+import { expect } from "chai";
+import { Program } from "@coral-xyz/anchor";
+import { InvoiceClaim } from "../target/types/invoice_claim";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { LiteSVMProvider } from "anchor-litesvm";
+import * as anchor from "@coral-xyz/anchor";
+import {
+    expectError,
+    fund,
+    getEscrowAuthorityPda,
+    getSetup,
+    getVendorPda,
+    initOrg,
+    submitInvoice,
+} from "./setup";
+
+describe("Org Authority Transfer and Role Tests", () => {
+    let litesvm: any;
+    let provider: LiteSVMProvider;
+    let program: Program<InvoiceClaim>;
+
+    let creator: Keypair;
+    let newAuthority: Keypair;
+    let treasurer: Keypair;
+    let auditor: Keypair;
+    let vendorManager: Keypair;
+    let outsider: Keypair;
+
+    let orgConfigPda: PublicKey;
+    const mint = Keypair.generate().publicKey;
+
+    const vendorPda = (name: string) => getVendorPda(program, orgConfigPda, name)[0];
+
+    const updateRoles = (signer: Keypair, roles: { treasurer?: PublicKey; auditor?: PublicKey; vendorManager?: PublicKey }) =>
+        program.methods
+            .updateOrgConfig({
+                perInvoiceCap: null,
+                dailyCap: null,
+                paused: null,
+                oracleSigner: null,
+                mint: null,
+                extraOracleSigners: null,
+                amountTolerance: null,
+                amountToleranceBps: null,
+                treasurer: roles.treasurer ?? null,
+                auditor: roles.auditor ?? null,
+                vendorManager: roles.vendorManager ?? null,
+            })
+            .accounts({
+                authority: signer.publicKey,
+                orgConfig: orgConfigPda,
+            })
+            .signers([signer])
+            .rpc();
+
+    const registerVendor = (signer: Keypair, name: string) =>
+        program.methods
+            .registerVendor(name, Keypair.generate().publicKey)
+            .accounts({
+                vendorAccount: vendorPda(name),
+                orgConfig: orgConfigPda,
+                authority: signer.publicKey,
+                systemProgram: SystemProgram.programId,
+            })
+            .signers([signer])
+            .rpc();
+
+    const propose = (signer: Keypair, proposed: PublicKey | null) =>
+        program.methods
+            .proposeOrgAuthority(proposed)
+            .accounts({
+                authority: signer.publicKey,
+                orgConfig: orgConfigPda,
+            })
+            .signers([signer])
+            .rpc();
+
+    const accept = (signer: Keypair) =>
+        program.methods
+            .acceptOrgAuthority()
+            .accounts({
+                newAuthority: signer.publicKey,
+                orgConfig: orgConfigPda,
+            })
+            .signers([signer])
+            .rpc();
+
+    before(async () => {
+        ({ litesvm, provider, program } = await getSetup());
+
+        [creator, newAuthority, treasurer, auditor, vendorManager, outsider] = Array.from({ length: 6 }, () =>
+            Keypair.generate()
+        );
+        [creator, newAuthority, treasurer, auditor, vendorManager, outsider].forEach((keypair) =>
+            fund(litesvm, keypair)
+        );
+
+        orgConfigPda = await initOrg(program, creator, null, { mint });
+    });
+
+    describe("Roles", () => {
+        it("Starts with every role held by the creator", async () => {
+            const orgConfig = await program.account.orgConfig.fetch(orgConfigPda);
+            for (const key of [
+                orgConfig.creator,
+                orgConfig.treasurer,
+                orgConfig.auditor,
+                orgConfig.vendorManager,
+            ]) {
+                expect(key.toString()).to.equal(creator.publicKey.toString());
+            }
+            expect(orgConfig.pendingAuthority).to.be.null;
+        });
+
+        it("Fails to assign roles as a non-authority", async () => {
+            await expectError(updateRoles(outsider, { treasurer: outsider.publicKey }), "Unauthorized");
+        });
+
+        it("Assigns the treasurer, auditor and vendor manager", async () => {
+            await updateRoles(creator, {
+                treasurer: treasurer.publicKey,
+                auditor: auditor.publicKey,
+                vendorManager: vendorManager.publicKey,
+            });
+
+            const orgConfig = await program.account.orgConfig.fetch(orgConfigPda);
+            expect(orgConfig.treasurer.toString()).to.equal(treasurer.publicKey.toString());
+            expect(orgConfig.auditor.toString()).to.equal(auditor.publicKey.toString());
+            expect(orgConfig.vendorManager.toString()).to.equal(vendorManager.publicKey.toString());
+        });
+
+        it("Lets the vendor manager register and deactivate vendors", async () => {
+            await registerVendor(vendorManager, "Managed Vendor");
+
+            await program.methods
+                .deactivateVendor()
+                .accounts({
+                    vendorAccount: vendorPda("Managed Vendor"),
+                    orgConfig: orgConfigPda,
+                    authority: vendorManager.publicKey,
+                })
+                .signers([vendorManager])
+                .rpc();
+
+            const vendor = await program.account.vendorAccount.fetch(vendorPda("Managed Vendor"));
+            expect(vendor.isActive).to.equal(false);
+        });
+
+        it("Fails to repoint a vendor wallet as the vendor manager", async () => {
+            await expectError(
+                program.methods
+                    .updateVendorWallet(vendorManager.publicKey)
+                    .accounts({
+                        vendorAccount: vendorPda("Managed Vendor"),
+                        orgConfig: orgConfigPda,
+                        authority: vendorManager.publicKey,
+                    })
+                    .signers([vendorManager])
+                    .rpc(),
+                "Unauthorized"
+            );
+
+            const vendor = await program.account.vendorAccount.fetch(vendorPda("Managed Vendor"));
+            expect(vendor.wallet.toString()).to.not.equal(vendorManager.publicKey.toString());
+        });
+
+        it("Fails to register a vendor as another role holder", async () => {
+            await expectError(registerVendor(treasurer, "Treasurer Vendor"), "Unauthorized");
+        });
+
+        it("Fails to fund escrow as anyone but the treasurer", async () => {
+            const vendorName = "Escrow Vendor";
+            const ipfsHash = "bafkreiorgrole1";
+            const invoiceNumber = "INV-ROLE-1";
+
+            // The creator is still the oracle signer, so it can validate an invoice
+            await registerVendor(creator, vendorName);
+            const invoice = await submitInvoice(program, creator, orgConfigPda, {
+                nonce: 1,
+                vendorName,
+                ipfsHash,
+                invoiceNumber,
+                amount: new anchor.BN(1_000_000),
+                dueDate: new anchor.BN(Math.floor(Date.now() / 1000) + 30 * 86_400),
+            });
+
+            // Rejected before any token account is touched
+            await expectError(
+                program.methods
+                    .fundEscrow()
+                    .accounts({
+                        orgConfig: orgConfigPda,
+                        invoiceAccount: invoice,
+                        vendorAccount: vendorPda(vendorName),
+                        escrowAuthority: getEscrowAuthorityPda(program, invoice)[0],
+                        payer: auditor.publicKey,
+                        authority: creator.publicKey,
+                        payerAta: Keypair.generate().publicKey,
+                        escrowAta: Keypair.generate().publicKey,
+                        mint,
+                        tokenProgram: TOKEN_PROGRAM_ID,
+                    })
+                    .signers([auditor])
+                    .rpc(),
+                "Unauthorized"
+            );
+        });
+    });
+
+    describe("Authority Transfer", () => {
+        it("Fails to propose as a non-authority", async () => {
+            await expectError(propose(outsider, outsider.publicKey), "Unauthorized");
+        });
+
+        it("Cancels a pending proposal", async () => {
+            await propose(creator, outsider.publicKey);
+            await propose(creator, null);
+
+            const orgConfig = await program.account.orgConfig.fetch(orgConfigPda);
+            expect(orgConfig.pendingAuthority).to.be.null;
+            await expectError(accept(outsider), "Unauthorized");
+        });
+
+        it("Fails to accept as anyone but the proposed authority", async () => {
+            await propose(creator, newAuthority.publicKey);
+
+            const orgConfig = await program.account.orgConfig.fetch(orgConfigPda);
+            expect(orgConfig.pendingAuthority.toString()).to.equal(newAuthority.publicKey.toString());
+            await expectError(accept(outsider), "Unauthorized");
+        });
+
+        it("Transfers authority on accept and keeps the same org account", async () => {
+            // Hand vendor management back to the creator so the transfer has a delegated role to move
+            await updateRoles(creator, { vendorManager: creator.publicKey });
+            await accept(newAuthority);
+
+            const orgConfig = await program.account.orgConfig.fetch(orgConfigPda);
+            expect(orgConfig.authority.toString()).to.equal(newAuthority.publicKey.toString());
+            expect(orgConfig.creator.toString()).to.equal(creator.publicKey.toString());
+            expect(orgConfig.pendingAuthority).to.be.null;
+
+            // Roles the old authority still held move with it; delegated ones stay put
+            expect(orgConfig.oracleSigner.toString()).to.equal(newAuthority.publicKey.toString());
+            expect(orgConfig.vendorManager.toString()).to.equal(newAuthority.publicKey.toString());
+            expect(orgConfig.treasurer.toString()).to.equal(treasurer.publicKey.toString());
+            expect(orgConfig.auditor.toString()).to.equal(auditor.publicKey.toString());
+
+            // Vendors registered before the transfer still belong to the org
+            const vendor = await program.account.vendorAccount.fetch(vendorPda("Managed Vendor"));
+            expect(vendor.org.toString()).to.equal(orgConfigPda.toString());
+        });
+
+        it("Gives the new authority control and revokes the old one", async () => {
+            await expectError(updateRoles(creator, { auditor: creator.publicKey }), "Unauthorized");
+            await expectError(registerVendor(creator, "Old Authority Vendor"), "Unauthorized");
+            await expectError(
+                program.methods
+                    .deactivateVendor()
+                    .accounts({
+                        vendorAccount: vendorPda("Managed Vendor"),
+                        orgConfig: orgConfigPda,
+                        authority: creator.publicKey,
+                    })
+                    .signers([creator])
+                    .rpc(),
+                "Unauthorized"
+            );
+
+            await updateRoles(newAuthority, { auditor: newAuthority.publicKey });
+            await registerVendor(newAuthority, "Post Transfer Vendor");
+
+            const orgConfig = await program.account.orgConfig.fetch(orgConfigPda);
+            expect(orgConfig.auditor.toString()).to.equal(newAuthority.publicKey.toString());
+        });
+    });
+});
@@ -422,6 +422,117 @@ describe("Organization Management Tests", () => {
             console.log("\n");
         });
 
+        it("Successfully sets extra oracle signers", async () => {
+            const extraOracles = [Keypair.generate().publicKey, Keypair.generate().publicKey];
+
+            await program.methods
+                .updateOrgConfig({
+                    perInvoiceCap: null,
+                    dailyCap: null,
+                    paused: null,
+                    oracleSigner: null,
+                    mint: null,
+                    extraOracleSigners: extraOracles,
+                })
+                .accounts({
+                    authority: authority.publicKey,
+                    orgConfig: orgConfigPda,
+                })
+                .signers([authority])
+                .rpc();
+
+            const orgConfig = await program.account.orgConfig.fetch(orgConfigPda);
+            expect(orgConfig.extraOracleSigners.map((k) => k.toString())).to.deep.equal(
+                extraOracles.map((k) => k.toString())
+            );
+
+            console.log("Extra oracle signers updated!");
+            console.log("\n");
+        });
+
+        it("Fails to set more than 4 extra oracle signers", async () => {
+            const tooMany = Array.from({ length: 5 }, () => Keypair.generate().publicKey);
+
+            try {
+                await program.methods
+                    .updateOrgConfig({
+                        perInvoiceCap: null,
+                        dailyCap: null,
+                        paused: null,
+                        oracleSigner: null,
+                        mint: null,
+                        extraOracleSigners: tooMany,
+                    })
+                    .accounts({
+                        authority: authority.publicKey,
+                        orgConfig: orgConfigPda,
+                    })
+                    .signers([authority])
+                    .rpc();
+
+                expect.fail("Should have failed with TooManyOracleSigners error");
+            } catch (err) {
+                expect(err.error.errorCode.code).to.equal("TooManyOracleSigners");
+                console.log("Correctly rejected oversized oracle signer set");
+            }
+            console.log("\n");
+        });
+
+        it("Successfully sets amount tolerances", async () => {
+            await program.methods
+                .updateOrgConfig({
+                    perInvoiceCap: null,
+                    dailyCap: null,
+                    paused: null,
+                    oracleSigner: null,
+                    mint: null,
+                    extraOracleSigners: null,
+                    amountTolerance: new anchor.BN(10_000),
+                    amountToleranceBps: 50,
+                })
+                .accounts({
+                    authority: authority.publicKey,
+                    orgConfig: orgConfigPda,
+                })
+                .signers([authority])
+                .rpc();
+
+            const orgConfig = await program.account.orgConfig.fetch(orgConfigPda);
+            expect(orgConfig.amountTolerance.toString()).to.equal("10000");
+            expect(orgConfig.amountToleranceBps).to.equal(50);
+
+            console.log("Amount tolerances updated!");
+            console.log("\n");
+        });
+
+        it("Fails to set amount_tolerance_bps above 10000", async () => {
+            try {
+                await program.methods
+                    .updateOrgConfig({
+                        perInvoiceCap: null,
+                        dailyCap: null,
+                        paused: null,
+                        oracleSigner: null,
+                        mint: null,
+                        extraOracleSigners: null,
+                        amountTolerance: null,
+                        amountToleranceBps: 10_001,
+                    })
+                    .accounts({
+                        authority: authority.publicKey,
+                        orgConfig: orgConfigPda,
+                    })
+                    .signers([authority])
+                    .rpc();
+
+                expect.fail("Should have failed with InvalidTolerance error");
+            } catch (err) {
+                expect(err.error.errorCode.code).to.equal("InvalidTolerance");
+                console.log("Correctly rejected tolerance above 100%");
+            }
+            console.log("\n");
+        });
+
         it("Successfully updates multiple fields at once", async () => {
             const newPerInvoiceCap = new anchor.BN(3_000_000_000);
             const newDailyCap = new anchor.BN(30_000_000_000);
@@ -596,5 +707,113 @@ describe("Organization Management Tests", () => {
             console.log("Canonical bump:", derivedBump);
             console.log("\n");
         });
+
+        it("Reports the full daily cap as remaining budget before any spend", async () => {
+            const orgConfig = await program.account.orgConfig.fetch(orgConfigPda);
+
+            const remaining = await program.methods
+                .getRemainingDailyBudget()
+                .accounts({ orgConfig: orgConfigPda })
+                .view();
+
+            expect(orgConfig.dailySpent.toString()).to.equal("0");
+            expect(remaining.toString()).to.equal(orgConfig.dailyCap.toString());
+
+            console.log("Remaining daily budget:", remaining.toString());
+            console.log("\n");
+        });
+    });
+
+    describe("Payment Queue Authorization", () => {
+        let paymentQueuePda: PublicKey;
+
+        const queueEntryPda = (invoiceKey: PublicKey) =>
+            PublicKey.findProgramAddressSync(
+                [Buffer.from("queue_entry"), paymentQueuePda.toBuffer(), invoiceKey.toBuffer()],
+                program.programId
+            )[0];
+
+        before(async () => {
+            [paymentQueuePda] = PublicKey.findProgramAddressSync(
+                [Buffer.from("payment_queue"), orgConfigPda.toBuffer()],
+                program.programId
+            );
+
+            await program.methods
+                .initPaymentQueue()
+                .accounts({
+                    authority: authority.publicKey,
+                    orgConfig: orgConfigPda,
+                    paymentQueue: paymentQueuePda,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([authority])
+                .rpc();
+
+            const queue = await program.account.paymentQueue.fetch(paymentQueuePda);
+            expect(queue.head).to.be.null;
+            expect(queue.tail).to.be.null;
+            expect(queue.count.toString()).to.equal("0");
+        });
+
+        it("Fails when an outsider removes an invoice from the queue", async () => {
+            const outsider = Keypair.generate();
+            litesvm.setAccount(outsider.publicKey, {
+                lamports: 100 * LAMPORTS_PER_SOL,
+                data: Buffer.alloc(0),
+                executable: false,
+                owner: SystemProgram.programId,
+            });
+
+            const invoiceKey = Keypair.generate().publicKey;
+            try {
+                await program.methods
+                    .removeFromPaymentQueue(invoiceKey)
+                    .accounts({
+                        signer: outsider.publicKey,
+                        orgConfig: orgConfigPda,
+                        paymentQueue: paymentQueuePda,
+                        queueEntry: queueEntryPda(invoiceKey),
+                        prevEntry: null,
+                        nextEntry: null,
+                        rentReceiver: outsider.publicKey,
+                        systemProgram: SystemProgram.programId,
+                    })
+                    .signers([outsider])
+                    .rpc();
+
+                expect.fail("Should have failed with Unauthorized error");
+            } catch (err) {
+                expect(err.error.errorCode.code).to.equal("Unauthorized");
+                console.log("Correctly rejected queue removal by outsider");
+            }
+            console.log("\n");
+        });
+
+        it("Fails to remove an invoice that is not queued", async () => {
+            const invoiceKey = Keypair.generate().publicKey;
+            try {
+                await program.methods
+                    .removeFromPaymentQueue(invoiceKey)
+                    .accounts({
+                        signer: authority.publicKey,
+                        orgConfig: orgConfigPda,
+                        paymentQueue: paymentQueuePda,
+                        queueEntry: queueEntryPda(invoiceKey),
+                        prevEntry: null,
+                        nextEntry: null,
+                        rentReceiver: authority.publicKey,
+                        systemProgram: SystemProgram.programId,
+                    })
+                    .signers([authority])
+                    .rpc();
+
+                expect.fail("Should have failed with NotInQueue error");
+            } catch (err) {
+                expect(err.error.errorCode.code).to.equal("NotInQueue");
+                console.log("Correctly rejected removal of unqueued invoice");
+            }
+            console.log("\n");
+        });
     });
 });
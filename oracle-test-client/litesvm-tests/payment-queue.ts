@@ -0,0 +1,208 @@
+import { describe, it } from "mocha";
+import { expect } from "chai";
+import { Program } from "@coral-xyz/anchor";
+import { InvoiceClaim } from "../target/types/invoice_claim";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { LiteSVMProvider } from "anchor-litesvm";
+import {
+    expectError,
+    fund,
+    getOrgConfigPda,
+    getPaymentQueuePda,
+    getQueueEntryPda,
+    getSetup,
+    getVendorPda,
+    initOrg,
+    seedInvoice,
+} from "./setup";
+
+describe("Payment Queue Ordering Tests", () => {
+    let litesvm: any;
+    let provider: LiteSVMProvider;
+    let program: Program<InvoiceClaim>;
+    let authority: Keypair;
+    // Owns a second org with its own queue
+    let outsider: Keypair;
+
+    let orgConfigPda: PublicKey;
+    let paymentQueuePda: PublicKey;
+
+    const vendorName = "Queued Vendor";
+
+    // Invoices keyed by due date offset (seconds from now)
+    const invoices: Record<number, PublicKey> = {};
+    const baseDue = Math.floor(Date.now() / 1000) + 3600;
+
+    const queuePda = (orgConfig: PublicKey) => getPaymentQueuePda(program, orgConfig)[0];
+
+    const vendorPda = (orgConfig: PublicKey) => getVendorPda(program, orgConfig, vendorName)[0];
+
+    const queueEntryPda = (invoice: PublicKey, queue: PublicKey = paymentQueuePda) =>
+        getQueueEntryPda(program, queue, invoice)[0];
+
+    // Org with one registered vendor and an empty payment queue
+    const initQueuedOrg = async (owner: Keypair) => {
+        fund(litesvm, owner);
+        const orgConfig = await initOrg(program, owner, vendorName, { auditRateBps: 500 });
+
+        await program.methods
+            .initPaymentQueue()
+            .accounts({
+                authority: owner.publicKey,
+                orgConfig,
+                paymentQueue: queuePda(orgConfig),
+                systemProgram: SystemProgram.programId,
+            })
+            .signers([owner])
+            .rpc();
+        return orgConfig;
+    };
+
+    // Write a ReadyToSettle invoice straight into the SVM; the VRF flow that
+    // normally produces one is not available locally.
+    const seedReadyInvoice = async (nonce: number, dueOffset: number) => {
+        invoices[dueOffset] = await seedInvoice(litesvm, program, authority.publicKey, {
+            nonce,
+            vendor: vendorPda(orgConfigPda),
+            vendorName,
+            status: { inEscrowReadyToSettle: {} },
+            dueDate: baseDue + dueOffset,
+            ipfsHash: "bafkreiqueuetest",
+            invoiceNumber: "",
+        });
+    };
+
+    const addToQueue = (invoice: PublicKey, prev: PublicKey | null, next: PublicKey | null) =>
+        program.methods
+            .addToPaymentQueue()
+            .accounts({
+                signer: authority.publicKey,
+                orgConfig: orgConfigPda,
+                invoiceAccount: invoice,
+                vendorAccount: vendorPda(orgConfigPda),
+                paymentQueue: paymentQueuePda,
+                queueEntry: queueEntryPda(invoice),
+                prevEntry: prev ? queueEntryPda(prev) : null,
+                nextEntry: next ? queueEntryPda(next) : null,
+                systemProgram: SystemProgram.programId,
+            })
+            .signers([authority])
+            .rpc();
+
+    // Due dates in list order, walking from the head
+    const walkQueue = async (): Promise<number[]> => {
+        const queue = await program.account.paymentQueue.fetch(paymentQueuePda);
+        const dues: number[] = [];
+        let cursor = queue.head;
+        while (cursor) {
+            const entry = await program.account.queueEntry.fetch(cursor);
+            dues.push(entry.dueDate.toNumber() - baseDue);
+            cursor = entry.next;
+        }
+        return dues;
+    };
+
+    before(async () => {
+        ({ litesvm, provider, program } = await getSetup());
+
+        authority = Keypair.generate();
+        outsider = Keypair.generate();
+
+        orgConfigPda = await initQueuedOrg(authority);
+        paymentQueuePda = queuePda(orgConfigPda);
+        await initQueuedOrg(outsider);
+
+        await seedReadyInvoice(1, 300);
+        await seedReadyInvoice(2, 100);
+        await seedReadyInvoice(3, 200);
+    });
+
+    it("Inserts into an empty queue", async () => {
+        await addToQueue(invoices[300], null, null);
+
+        expect(await walkQueue()).to.deep.equal([300]);
+        console.log("Queued first invoice");
+        console.log("\n");
+    });
+
+    it("Inserts an earlier due date at the head", async () => {
+        await addToQueue(invoices[100], null, invoices[300]);
+
+        expect(await walkQueue()).to.deep.equal([100, 300]);
+        console.log("\n");
+    });
+
+    it("Fails when the hint would break due date order", async () => {
+        await expectError(addToQueue(invoices[200], null, invoices[100]), "QueueOrderViolation");
+        console.log("\n");
+    });
+
+    it("Fails when the hinted neighbours are not adjacent", async () => {
+        await expectError(addToQueue(invoices[200], null, invoices[300]), "QueueLinkMismatch");
+        console.log("\n");
+    });
+
+    it("Inserts in the middle and keeps the queue sorted", async () => {
+        await addToQueue(invoices[200], invoices[100], invoices[300]);
+
+        expect(await walkQueue()).to.deep.equal([100, 200, 300]);
+        const queue = await program.account.paymentQueue.fetch(paymentQueuePda);
+        expect(queue.count.toString()).to.equal("3");
+        expect(queue.tail.toString()).to.equal(queueEntryPda(invoices[300]).toString());
+        console.log("\n");
+    });
+
+    it("Fails to queue the same invoice twice", async () => {
+        await expectError(addToQueue(invoices[200], invoices[100], invoices[300]), "AlreadyQueued");
+        console.log("\n");
+    });
+
+    it("Fails to queue an invoice in another org's queue", async () => {
+        const [outsiderOrg] = getOrgConfigPda(program, outsider.publicKey);
+        const outsiderQueue = queuePda(outsiderOrg);
+
+        await expectError(
+            program.methods
+                .addToPaymentQueue()
+                .accounts({
+                    signer: outsider.publicKey,
+                    orgConfig: outsiderOrg,
+                    invoiceAccount: invoices[100],
+                    vendorAccount: vendorPda(outsiderOrg),
+                    paymentQueue: outsiderQueue,
+                    queueEntry: queueEntryPda(invoices[100], outsiderQueue),
+                    prevEntry: null,
+                    nextEntry: null,
+                    systemProgram: SystemProgram.programId,
+                })
+                .signers([outsider])
+                .rpc(),
+            "InvalidVendor"
+        );
+
+        const queue = await program.account.paymentQueue.fetch(outsiderQueue);
+        expect(queue.count.toString()).to.equal("0");
+        console.log("\n");
+    });
+
+    it("Removes a middle entry and relinks its neighbours", async () => {
+        await program.methods
+            .removeFromPaymentQueue(invoices[200])
+            .accounts({
+                signer: authority.publicKey,
+                orgConfig: orgConfigPda,
+                paymentQueue: paymentQueuePda,
+                queueEntry: queueEntryPda(invoices[200]),
+                prevEntry: queueEntryPda(invoices[100]),
+                nextEntry: queueEntryPda(invoices[300]),
+                rentReceiver: authority.publicKey,
+                systemProgram: SystemProgram.programId,
+            })
+            .signers([authority])
+            .rpc();
+
+        expect(await walkQueue()).to.deep.equal([100, 300]);
+        expect(litesvm.getAccount(queueEntryPda(invoices[200]))).to.be.null;
+        console.log("\n");
+    });
+});
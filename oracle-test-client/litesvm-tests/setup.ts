@@ -1,3 +1,4 @@
+import * as anchor from "@coral-xyz/anchor";
 import { AnchorError, Program } from "@coral-xyz/anchor";
 import { InvoiceClaim } from "../target/types/invoice_claim";
 import idl from "../target/idl/invoice_claim.json";
@@ -7,10 +8,14 @@ import {
     SystemProgram,
     Keypair
 } from "@solana/web3.js";
-import { AccountInfoBytes, ComputeBudget } from "litesvm";
+import { AccountInfoBytes, ComputeBudget, LiteSVM } from "litesvm";
 import { fromWorkspace, LiteSVMProvider } from "anchor-litesvm";
 import { expect } from "chai";
+import { createHash } from "crypto";
 import {
+    ACCOUNT_SIZE,
+    AccountLayout,
+    AccountState,
     TOKEN_PROGRAM_ID,
     createMint,
     createAccount,
@@ -54,69 +59,347 @@ export function fundedSystemAccountInfo(
     };
 }
 
-export async function expectAnchorError(error: Error, code: string) {
-    expect(error).toBeInstanceOf(AnchorError);
+// Helper to give a keypair enough SOL for its fees and rent
+export function fund(litesvm: LiteSVM, keypair: Keypair, lamports: number = 100 * LAMPORTS_PER_SOL) {
+    litesvm.setAccount(keypair.publicKey, fundedSystemAccountInfo(lamports));
+}
+
+export function expectAnchorError(error: Error, code: string) {
+    expect(error).to.be.instanceOf(AnchorError);
     const { errorCode } = (error as AnchorError).error;
-    expect(errorCode.code).toBe(code);
+    expect(errorCode.code).to.equal(code);
+}
+
+// Helper to await a transaction that must fail with Anchor error `code`
+export async function expectError(promise: Promise<unknown>, code: string) {
+    try {
+        await promise;
+    } catch (err) {
+        expectAnchorError(err, code);
+        return;
+    }
+    expect.fail(`Should have failed with ${code} error`);
+}
+
+export const sha256 = (data: string) => createHash("sha256").update(data).digest();
+
+// Helper to derive org config PDA
+export function getOrgConfigPda(
+    program: Program<InvoiceClaim>,
+    creator: PublicKey
+): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+        [Buffer.from("org_config"), creator.toBuffer()],
+        program.programId
+    );
+}
+
+// Helper to derive vendor PDA
+export function getVendorPda(
+    program: Program<InvoiceClaim>,
+    orgConfig: PublicKey,
+    vendorName: string
+): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+        [Buffer.from("vendor"), orgConfig.toBuffer(), Buffer.from(vendorName)],
+        program.programId
+    );
+}
+
+// Helper to derive extraction request PDA
+export function getRequestPda(
+    program: Program<InvoiceClaim>,
+    authority: PublicKey,
+    nonce: number
+): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+        [Buffer.from("request"), authority.toBuffer(), new anchor.BN(nonce).toArrayLike(Buffer, "le", 8)],
+        program.programId
+    );
 }
 
 // Helper to derive invoice PDA
 export function getInvoicePda(
     program: Program<InvoiceClaim>,
-    company: PublicKey,
-    invoiceId: string
+    authority: PublicKey,
+    nonce: number
 ): [PublicKey, number] {
     return PublicKey.findProgramAddressSync(
-        [Buffer.from("invoice"), company.toBuffer(), Buffer.from(invoiceId)],
+        [Buffer.from("invoice"), authority.toBuffer(), new anchor.BN(nonce).toArrayLike(Buffer, "le", 8)],
         program.programId
     );
 }
 
-// Helper to derive extraction request PDA
-export function getExtractionRequestPda(
+// Helper to derive the document and invoice-number registry PDAs of an invoice.
+// Mirrors document_key / invoice_number_key in state.rs
+export function getRegistryPdas(
+    program: Program<InvoiceClaim>,
+    orgConfig: PublicKey,
+    vendor: PublicKey,
+    ipfsHash: string,
+    invoiceNumber: string
+): { documentRegistry: [PublicKey, number]; numberRegistry: [PublicKey, number] } {
+    const normalized = invoiceNumber.replace(/[^a-z0-9]/gi, "").toUpperCase();
+    return {
+        documentRegistry: PublicKey.findProgramAddressSync(
+            [Buffer.from("invoice_doc"), orgConfig.toBuffer(), sha256(ipfsHash)],
+            program.programId
+        ),
+        numberRegistry: PublicKey.findProgramAddressSync(
+            [Buffer.from("invoice_number"), vendor.toBuffer(), sha256(normalized || ipfsHash)],
+            program.programId
+        ),
+    };
+}
+
+// Helper to get just the registry addresses, for an instruction's accounts
+export function getRegistryKeys(
+    program: Program<InvoiceClaim>,
+    orgConfig: PublicKey,
+    vendor: PublicKey,
+    ipfsHash: string,
+    invoiceNumber: string
+) {
+    const { documentRegistry, numberRegistry } = getRegistryPdas(program, orgConfig, vendor, ipfsHash, invoiceNumber);
+    return { documentRegistry: documentRegistry[0], numberRegistry: numberRegistry[0] };
+}
+
+// Helper to derive escrow authority PDA
+export function getEscrowAuthorityPda(
     program: Program<InvoiceClaim>,
     invoice: PublicKey
 ): [PublicKey, number] {
     return PublicKey.findProgramAddressSync(
-        [Buffer.from("extraction_request"), invoice.toBuffer()],
+        [Buffer.from("escrow_auth"), invoice.toBuffer()],
         program.programId
     );
 }
 
-// Helper to derive org config PDA
-export function getOrgConfigPda(
+// Helper to derive audit ballot PDA
+export function getAuditBallotPda(
     program: Program<InvoiceClaim>,
-    company: PublicKey
+    invoice: PublicKey
 ): [PublicKey, number] {
     return PublicKey.findProgramAddressSync(
-        [Buffer.from("org_config"), company.toBuffer()],
+        [Buffer.from("audit_ballot"), invoice.toBuffer()],
         program.programId
     );
 }
 
-// Helper to derive vendor PDA
-export function getVendorPda(
+// Helper to derive payment queue PDA
+export function getPaymentQueuePda(
     program: Program<InvoiceClaim>,
-    company: PublicKey,
-    vendorName: string
+    orgConfig: PublicKey
 ): [PublicKey, number] {
     return PublicKey.findProgramAddressSync(
-        [Buffer.from("vendor"), company.toBuffer(), Buffer.from(vendorName)],
+        [Buffer.from("payment_queue"), orgConfig.toBuffer()],
         program.programId
     );
 }
 
-// Helper to derive escrow PDA
-export function getEscrowPda(
+// Helper to derive queue entry PDA
+export function getQueueEntryPda(
     program: Program<InvoiceClaim>,
+    paymentQueue: PublicKey,
     invoice: PublicKey
 ): [PublicKey, number] {
     return PublicKey.findProgramAddressSync(
-        [Buffer.from("escrow"), invoice.toBuffer()],
+        [Buffer.from("queue_entry"), paymentQueue.toBuffer(), invoice.toBuffer()],
         program.programId
     );
 }
 
+// Helper to create an org owned by `owner`, who is also its oracle signer,
+// with `vendorName` registered when given. Returns the org config PDA.
+export async function initOrg(
+    program: Program<InvoiceClaim>,
+    owner: Keypair,
+    vendorName: string | null,
+    options: { mint?: PublicKey; auditRateBps?: number; vendorWallet?: PublicKey } = {}
+): Promise<PublicKey> {
+    const [orgConfig] = getOrgConfigPda(program, owner.publicKey);
+
+    await program.methods
+        .orgInit(
+            Keypair.generate().publicKey,
+            options.mint ?? Keypair.generate().publicKey,
+            new anchor.BN(1_000_000_000),
+            new anchor.BN(10_000_000_000),
+            options.auditRateBps ?? 0
+        )
+        .accounts({
+            orgConfig,
+            authority: owner.publicKey,
+            systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+    if (vendorName !== null) {
+        await program.methods
+            .registerVendor(vendorName, options.vendorWallet ?? Keypair.generate().publicKey)
+            .accounts({
+                vendorAccount: getVendorPda(program, orgConfig, vendorName)[0],
+                orgConfig,
+                authority: owner.publicKey,
+                systemProgram: SystemProgram.programId,
+            })
+            .signers([owner])
+            .rpc();
+    }
+    return orgConfig;
+}
+
+// Helper to request extraction of an invoice as `authority` and submit the
+// oracle's result for it. Returns the invoice PDA.
+export async function submitInvoice(
+    program: Program<InvoiceClaim>,
+    authority: Keypair,
+    orgConfig: PublicKey,
+    invoice: {
+        nonce: number;
+        vendorName: string;
+        ipfsHash: string;
+        invoiceNumber: string;
+        amount: anchor.BN;
+        dueDate: anchor.BN;
+        extracted?: anchor.BN;
+    }
+): Promise<PublicKey> {
+    const { nonce, vendorName, ipfsHash, invoiceNumber, amount, dueDate } = invoice;
+    const [requestPda] = getRequestPda(program, authority.publicKey, nonce);
+    const [invoicePda] = getInvoicePda(program, authority.publicKey, nonce);
+    const [vendorPda] = getVendorPda(program, orgConfig, vendorName);
+
+    await program.methods
+        .requestInvoiceExtraction(ipfsHash, amount, new anchor.BN(nonce))
+        .accounts({
+            authority: authority.publicKey,
+            orgConfig,
+            invoiceRequest: requestPda,
+            systemProgram: SystemProgram.programId,
+        })
+        .signers([authority])
+        .rpc();
+
+    await program.methods
+        .processExtractionResult(vendorName, invoice.extracted ?? amount, dueDate, invoiceNumber)
+        .accounts({
+            payer: authority.publicKey,
+            orgConfig,
+            vendorAccount: vendorPda,
+            invoiceRequest: requestPda,
+            invoiceAccount: invoicePda,
+            ...getRegistryKeys(program, orgConfig, vendorPda, ipfsHash, invoiceNumber),
+            systemProgram: SystemProgram.programId,
+        })
+        .signers([authority])
+        .rpc();
+
+    return invoicePda;
+}
+
+// Helper to write an invoice straight into the SVM, for the statuses only the
+// VRF flow reaches on chain. With `orgConfig`, the registry entries it claimed
+// when it was validated are written too. Returns the invoice PDA.
+export async function seedInvoice(
+    litesvm: LiteSVM,
+    program: Program<InvoiceClaim>,
+    authority: PublicKey,
+    invoice: {
+        nonce: number;
+        vendor: PublicKey;
+        vendorName: string;
+        status: object;
+        dueDate: number;
+        amount?: anchor.BN;
+        ipfsHash?: string;
+        invoiceNumber?: string;
+    },
+    orgConfig?: PublicKey
+): Promise<PublicKey> {
+    const { nonce, vendor, vendorName, status } = invoice;
+    const ipfsHash = invoice.ipfsHash ?? `bafkreiseeded${nonce}`;
+    const invoiceNumber = invoice.invoiceNumber ?? `INV-SEED-${nonce}`;
+    const [invoicePda] = getInvoicePda(program, authority, nonce);
+
+    litesvm.setAccount(invoicePda, {
+        lamports: LAMPORTS_PER_SOL,
+        data: await program.coder.accounts.encode("invoiceAccount", {
+            authority,
+            vendor,
+            vendorName,
+            amount: invoice.amount ?? new anchor.BN(1_000_000),
+            dueDate: new anchor.BN(invoice.dueDate),
+            ipfsHash,
+            status,
+            timestamp: new anchor.BN(0),
+            nonce: new anchor.BN(nonce),
+            invoiceNumber,
+            escrowedAt: new anchor.BN(0),
+        }),
+        executable: false,
+        owner: program.programId,
+    });
+
+    if (orgConfig) {
+        const registries = getRegistryPdas(program, orgConfig, vendor, ipfsHash, invoiceNumber);
+        for (const [registry, bump] of Object.values(registries)) {
+            litesvm.setAccount(registry, {
+                lamports: LAMPORTS_PER_SOL,
+                data: await program.coder.accounts.encode("invoiceRegistry", {
+                    org: orgConfig,
+                    invoice: invoicePda,
+                    createdAt: new anchor.BN(0),
+                    bump,
+                }),
+                executable: false,
+                owner: program.programId,
+            });
+        }
+    }
+    return invoicePda;
+}
+
+// Helper to write an initialized SPL token account straight into the SVM.
+// Returns its address.
+export function setTokenAccount(
+    litesvm: LiteSVM,
+    mint: PublicKey,
+    owner: PublicKey,
+    amount: bigint
+): PublicKey {
+    const address = Keypair.generate().publicKey;
+    const data = Buffer.alloc(ACCOUNT_SIZE);
+    AccountLayout.encode(
+        {
+            mint,
+            owner,
+            amount,
+            delegateOption: 0,
+            delegate: PublicKey.default,
+            state: AccountState.Initialized,
+            isNativeOption: 0,
+            isNative: 0n,
+            delegatedAmount: 0n,
+            closeAuthorityOption: 0,
+            closeAuthority: PublicKey.default,
+        },
+        data
+    );
+    litesvm.setAccount(address, {
+        lamports: LAMPORTS_PER_SOL,
+        data,
+        executable: false,
+        owner: TOKEN_PROGRAM_ID,
+    });
+    return address;
+}
+
+export function tokenBalance(litesvm: LiteSVM, address: PublicKey): bigint {
+    return AccountLayout.decode(litesvm.getAccount(address).data).amount;
+}
+
 // Helper to create a test org with token mint and treasury
 export async function setupTestOrg(
     provider: LiteSVMProvider,
@@ -181,8 +464,8 @@ export async function setupTestVendor(
     vendorName: string,
     vendorWallet: PublicKey
 ) {
-    const [vendorPda] = getVendorPda(program, company.publicKey, vendorName);
     const [orgConfig] = getOrgConfigPda(program, company.publicKey);
+    const [vendorPda] = getVendorPda(program, orgConfig, vendorName);
 
     await program.methods
         .registerVendor(vendorName, vendorWallet)
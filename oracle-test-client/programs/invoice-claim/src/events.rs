@@ -0,0 +1,170 @@
+use anchor_lang::prelude::*;
+use crate::state::InvoiceStatus;
+
+// Invoice lifecycle events, emitted alongside the msg! logs so indexers can
+// decode structured data instead of scraping log strings.
+
+#[event]
+pub struct RequestCreated {
+    pub request: Pubkey,
+    pub authority: Pubkey,
+    pub org: Pubkey,
+    pub ipfs_hash: String,
+    pub amount: u64,
+    pub nonce: u64,
+}
+
+#[event]
+pub struct InvoiceValidated {
+    pub invoice: Pubkey,
+    pub request: Pubkey,
+    pub org: Pubkey,
+    pub vendor: Pubkey,
+    pub amount: u64,
+    pub due_date: i64,
+    pub status: InvoiceStatus,
+}
+
+// OCR amount disagreed with the declared amount; the invoice waits for review
+#[event]
+pub struct AmountMismatch {
+    pub invoice: Pubkey,
+    pub request: Pubkey,
+    pub org: Pubkey,
+    pub declared_amount: u64,
+    pub extracted_amount: u64,
+    pub status: InvoiceStatus,
+}
+
+#[event]
+pub struct AmountReviewed {
+    pub invoice: Pubkey,
+    pub reviewer: Pubkey,
+    pub approved: bool,
+    pub status: InvoiceStatus,
+}
+
+#[event]
+pub struct EscrowFunded {
+    pub invoice: Pubkey,
+    pub org: Pubkey,
+    pub payer: Pubkey,
+    pub amount: u64,
+    pub daily_spent: u64,               // Org spend for the current day, including this escrow
+    pub status: InvoiceStatus,
+}
+
+#[event]
+pub struct VrfRequested {
+    pub invoice: Pubkey,
+    pub org: Pubkey,
+    pub client_seed: u8,
+}
+
+// VRF outcome; `selected` is false when the invoice skipped audit
+#[event]
+pub struct AuditSelected {
+    pub invoice: Pubkey,
+    pub selected: bool,
+    pub random_value: u64,
+    pub audit_rate_bps: u16,
+    pub status: InvoiceStatus,
+}
+
+#[event]
+pub struct AuditVoteCast {
+    pub invoice: Pubkey,
+    pub reviewer: Pubkey,
+    pub approve: bool,
+    pub approvals: u8,
+    pub rejections: u8,
+    pub threshold: u8,
+}
+
+#[event]
+pub struct AuditDecided {
+    pub invoice: Pubkey,
+    pub reviewer: Pubkey,
+    pub approved: bool,
+    pub status: InvoiceStatus,
+}
+
+#[event]
+pub struct QueuedForPayment {
+    pub invoice: Pubkey,
+    pub queue: Pubkey,
+    pub entry: Pubkey,
+    pub due_date: i64,
+    pub amount: u64,
+}
+
+#[event]
+pub struct Settled {
+    pub invoice: Pubkey,
+    pub vendor: Pubkey,
+    pub vendor_ata: Pubkey,
+    pub amount: u64,
+    pub paid_at: i64,
+    pub status: InvoiceStatus,
+}
+
+#[event]
+pub struct Refunded {
+    pub invoice: Pubkey,
+    pub refund_ata: Pubkey,
+    pub amount: u64,
+    pub status: InvoiceStatus,
+}
+
+#[event]
+pub struct VendorRegistered {
+    pub vendor: Pubkey,
+    pub org: Pubkey,
+    pub vendor_name: String,
+    pub wallet: Pubkey,
+}
+
+#[event]
+pub struct VendorDeactivated {
+    pub vendor: Pubkey,
+    pub org: Pubkey,
+}
+
+#[event]
+pub struct VendorActivated {
+    pub vendor: Pubkey,
+    pub org: Pubkey,
+}
+
+// Snapshot of the updatable fields after update_org_config
+#[event]
+pub struct OrgConfigUpdated {
+    pub org: Pubkey,
+    pub per_invoice_cap: u64,
+    pub daily_cap: u64,
+    pub paused: bool,
+    pub oracle_signer: Pubkey,
+    pub extra_oracle_signers: Vec<Pubkey>,
+    pub mint: Pubkey,
+    pub amount_tolerance: u64,
+    pub amount_tolerance_bps: u16,
+    pub treasurer: Pubkey,
+    pub auditor: Pubkey,
+    pub vendor_manager: Pubkey,
+    pub auditors: Vec<Pubkey>,
+    pub audit_threshold: u8,
+}
+
+#[event]
+pub struct OrgAuthorityProposed {
+    pub org: Pubkey,
+    pub authority: Pubkey,
+    pub pending_authority: Option<Pubkey>,  // None when a proposal is cancelled
+}
+
+#[event]
+pub struct OrgAuthorityTransferred {
+    pub org: Pubkey,
+    pub previous_authority: Pubkey,
+    pub new_authority: Pubkey,
+}
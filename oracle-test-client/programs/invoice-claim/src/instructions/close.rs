@@ -12,12 +12,43 @@ pub struct CloseInvoice<'info> {
     )]
     pub invoice_account: Account<'info, InvoiceAccount>,
 
+    // Released with the invoice, so no registry is left naming a closed account
+    #[account(
+        mut,
+        seeds = [b"invoice_doc", document_registry.org.as_ref(), &document_key(&invoice_account.ipfs_hash)],
+        bump = document_registry.bump
+    )]
+    pub document_registry: Account<'info, InvoiceRegistry>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"invoice_number",
+            invoice_account.vendor.as_ref(),
+            &invoice_number_key(&invoice_account.invoice_number, &invoice_account.ipfs_hash)
+        ],
+        bump = number_registry.bump
+    )]
+    pub number_registry: Account<'info, InvoiceRegistry>,
+
     #[account(mut)]
     pub authority: Signer<'info>,
 }
 
 pub fn close_invoice(ctx: Context<CloseInvoice>) -> Result<()> {
     let invoice = &ctx.accounts.invoice_account;
+    // Only invoices with no escrow left behind may be closed
+    require!(
+        matches!(
+            invoice.status,
+            InvoiceStatus::Paid | InvoiceStatus::RefundCompleted | InvoiceStatus::ReviewRejected
+        ),
+        InvoiceError::InvalidStatus
+    );
+
+    ctx.accounts.document_registry.release(&invoice.key());
+    ctx.accounts.number_registry.release(&invoice.key());
+
     msg!("Closing invoice account for vendor: {}", invoice.vendor_name);
     msg!("Rent returned to: {}", ctx.accounts.authority.key());
     Ok(())
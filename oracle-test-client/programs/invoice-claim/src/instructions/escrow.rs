@@ -1,12 +1,14 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token};
+use anchor_spl::token::{self, Token, TokenAccount};
 use crate::state::*;
+use crate::events::*;
+use super::payment_queue::close_queue_entry;
 
 #[derive(Accounts)]
 pub struct FundEscrow<'info> {
     #[account(
         mut,
-        seeds = [b"org_config", org_config.authority.as_ref()],
+        seeds = [b"org_config", org_config.creator.as_ref()],
         bump = org_config.bump
     )]
     pub org_config: Account<'info, OrgConfig>,
@@ -18,6 +20,14 @@ pub struct FundEscrow<'info> {
     )]
     pub invoice_account: Account<'info, InvoiceAccount>,
 
+    // Ties the invoice to this org, so its treasurer and daily cap are the ones applied
+    #[account(
+        seeds = [b"vendor", org_config.key().as_ref(), vendor_account.vendor_name.as_bytes()],
+        bump,
+        constraint = vendor_account.key() == invoice_account.vendor @ InvoiceError::InvalidVendor,
+    )]
+    pub vendor_account: Account<'info, VendorAccount>,
+
     /// CHECK: PDA only used as signing authority
     #[account(
         seeds = [b"escrow_auth", invoice_account.key().as_ref()],
@@ -43,8 +53,9 @@ pub struct FundEscrow<'info> {
 }
 
 pub fn fund_escrow(ctx: Context<FundEscrow>) -> Result<()> {
-    let cfg = &ctx.accounts.org_config;
+    let cfg = &mut ctx.accounts.org_config;
     require!(!cfg.paused, InvoiceError::OrgPaused);
+    require!(cfg.is_treasurer(&ctx.accounts.payer.key()), InvoiceError::Unauthorized);
 
     let inv = &mut ctx.accounts.invoice_account;
     let amount = inv.amount;
@@ -55,6 +66,11 @@ pub fn fund_escrow(ctx: Context<FundEscrow>) -> Result<()> {
     // Ensure mint matches configuration
     require_keys_eq!(ctx.accounts.mint.key(), cfg.mint, InvoiceError::WrongMint);
 
+    // Escrow is where funds leave the payer, so this is the point that counts
+    // against daily_cap; settlement later only releases what is already counted.
+    let now = Clock::get()?.unix_timestamp;
+    cfg.record_spend(amount, now)?;
+
     // Transfer tokens from payer to escrow
        // Transfer tokens from payer to escrow
     token::transfer( CpiContext::new(
@@ -67,13 +83,25 @@ pub fn fund_escrow(ctx: Context<FundEscrow>) -> Result<()> {
     amount)?;
 
     inv.status = InvoiceStatus::InEscrowAwaitingVRF;
+    inv.escrowed_at = now;
+
+    emit!(EscrowFunded {
+        invoice: inv.key(),
+        org: cfg.key(),
+        payer: ctx.accounts.payer.key(),
+        amount,
+        daily_spent: cfg.daily_spent,
+        status: inv.status,
+    });
     Ok(())
 }
 
 #[derive(Accounts)]
 pub struct SettleToVendor<'info> {
     #[account(
-        mut
+        mut,
+        seeds = [b"org_config", org_config.creator.as_ref()],
+        bump = org_config.bump
     )]
     pub org_config: Account<'info, OrgConfig>,
 
@@ -84,6 +112,40 @@ pub struct SettleToVendor<'info> {
     )]
     pub invoice_account: Account<'info, InvoiceAccount>,
 
+    // Registered vendor the invoice was validated against; payment stats updated here
+    #[account(
+        mut,
+        seeds = [b"vendor", org_config.key().as_ref(), vendor_account.vendor_name.as_bytes()],
+        bump,
+        constraint = vendor_account.key() == invoice_account.vendor @ InvoiceError::InvalidVendor,
+    )]
+    pub vendor_account: Account<'info, VendorAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"payment_queue", org_config.key().as_ref()],
+        bump = payment_queue.bump
+    )]
+    pub payment_queue: Account<'info, PaymentQueue>,
+
+    /// CHECK: QueueEntry PDA for this invoice; may be empty when not queued
+    #[account(
+        mut,
+        seeds = [b"queue_entry", payment_queue.key().as_ref(), invoice_account.key().as_ref()],
+        bump
+    )]
+    pub queue_entry: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub prev_entry: Option<Account<'info, QueueEntry>>,
+
+    #[account(mut)]
+    pub next_entry: Option<Account<'info, QueueEntry>>,
+
+    /// CHECK: must be the entry's rent payer; checked in close_queue_entry
+    #[account(mut)]
+    pub rent_receiver: UncheckedAccount<'info>,
+
     /// CHECK: PDA only used as signing authority
     #[account(
         seeds = [b"escrow_auth", invoice_account.key().as_ref()],
@@ -91,13 +153,17 @@ pub struct SettleToVendor<'info> {
     )]
     pub escrow_authority: UncheckedAccount<'info>,
 
-    #[account(mut)]
-    /// CHECK: vendor's SPL token account (validated at runtime)
-    pub vendor_ata: UncheckedAccount<'info>,
+    // Payout must land in the registered vendor wallet's account for the org mint
+    #[account(
+        mut,
+        constraint = vendor_ata.mint == org_config.mint @ InvoiceError::WrongMint,
+        constraint = vendor_ata.owner == vendor_account.wallet @ InvoiceError::InvalidWallet,
+    )]
+    pub vendor_ata: Account<'info, TokenAccount>,
     #[account(mut)]
     /// CHECK: escrow SPL token account owned by escrow authority PDA
     pub escrow_ata: UncheckedAccount<'info>,
-    /// CHECK: SPL token mint
+    /// CHECK: SPL token mint; key checked against OrgConfig
     pub mint: UncheckedAccount<'info>,
 
     pub token_program: Program<'info, Token>,
@@ -109,16 +175,25 @@ pub struct SettleToVendor<'info> {
 pub fn settle_to_vendor(ctx: Context<SettleToVendor>) -> Result<()> {
     let cfg = &ctx.accounts.org_config;
     require!(!cfg.paused, InvoiceError::OrgPaused);
+    require_keys_eq!(ctx.accounts.mint.key(), cfg.mint, InvoiceError::WrongMint);
+
+    let vendor = &ctx.accounts.vendor_account;
+    require!(vendor.is_active, InvoiceError::VendorInactive);
 
     let inv = &mut ctx.accounts.invoice_account;
 
     // ✅ Allow either the invoice owner or the org's oracle signer to settle
     let s = ctx.accounts.signer.key();
     require!(
-        s == inv.authority || s == cfg.oracle_signer,
+        s == inv.authority || cfg.is_oracle(&s),
         InvoiceError::Unauthorized
     );
 
+    // Only invoices that cleared VRF / audit may be paid out
+    require!(
+        inv.status == InvoiceStatus::InEscrowReadyToSettle,
+        InvoiceError::InvalidStatus
+    );
 
     let now = Clock::get()?.unix_timestamp;
     require!(now >= inv.due_date, InvoiceError::PaymentNotDue);
@@ -149,6 +224,198 @@ pub fn settle_to_vendor(ctx: Context<SettleToVendor>) -> Result<()> {
     )?;
 
     inv.status = InvoiceStatus::Paid;
+
+    let vendor = &mut ctx.accounts.vendor_account;
+    vendor.total_paid = vendor.total_paid.checked_add(amount).ok_or(InvoiceError::Overflow)?;
+    vendor.last_payment = now;
+
+    emit!(Settled {
+        invoice: invoice_key,
+        vendor: vendor.key(),
+        vendor_ata: ctx.accounts.vendor_ata.key(),
+        amount,
+        paid_at: now,
+        status: inv.status,
+    });
+
+    // Drop the paid invoice from the queue in the same transaction
+    let a = &mut *ctx.accounts;
+    close_queue_entry(
+        &mut a.payment_queue,
+        &a.queue_entry,
+        a.prev_entry.as_mut(),
+        a.next_entry.as_mut(),
+        &a.rent_receiver,
+        now,
+    )?;
+
     Ok(())
 }
 
+#[derive(Accounts)]
+pub struct RefundEscrow<'info> {
+    // Same-day refunds give their spend back to daily_cap
+    #[account(
+        mut,
+        seeds = [b"org_config", org_config.creator.as_ref()],
+        bump = org_config.bump
+    )]
+    pub org_config: Account<'info, OrgConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"invoice", invoice_account.authority.as_ref(), &invoice_account.nonce.to_le_bytes()],
+        bump,
+    )]
+    pub invoice_account: Account<'info, InvoiceAccount>,
+
+    // Ties the invoice to this org: only the org it was validated under may refund it
+    #[account(
+        seeds = [b"vendor", org_config.key().as_ref(), vendor_account.vendor_name.as_bytes()],
+        bump,
+        constraint = vendor_account.key() == invoice_account.vendor @ InvoiceError::InvalidVendor,
+    )]
+    pub vendor_account: Account<'info, VendorAccount>,
+
+    // Released when the invoice is refunded, so the document can be resubmitted
+    #[account(
+        mut,
+        seeds = [b"invoice_doc", org_config.key().as_ref(), &document_key(&invoice_account.ipfs_hash)],
+        bump = document_registry.bump
+    )]
+    pub document_registry: Account<'info, InvoiceRegistry>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"invoice_number",
+            vendor_account.key().as_ref(),
+            &invoice_number_key(&invoice_account.invoice_number, &invoice_account.ipfs_hash)
+        ],
+        bump = number_registry.bump
+    )]
+    pub number_registry: Account<'info, InvoiceRegistry>,
+
+    /// CHECK: PDA only used as signing authority
+    #[account(
+        seeds = [b"escrow_auth", invoice_account.key().as_ref()],
+        bump
+    )]
+    pub escrow_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = escrow_ata.mint == org_config.mint @ InvoiceError::WrongMint,
+        constraint = escrow_ata.owner == escrow_authority.key() @ InvoiceError::Unauthorized,
+    )]
+    pub escrow_ata: Account<'info, TokenAccount>,
+
+    // Treasury vault itself, or a token account held by the treasury / org authority / treasurer (the payer ATA)
+    #[account(
+        mut,
+        constraint = refund_ata.mint == org_config.mint @ InvoiceError::WrongMint,
+    )]
+    pub refund_ata: Account<'info, TokenAccount>,
+
+    /// CHECK: the invoice's audit ballot; closed here if an expired audit left one open
+    #[account(
+        mut,
+        seeds = [b"audit_ballot", invoice_account.key().as_ref()],
+        bump
+    )]
+    pub audit_ballot: UncheckedAccount<'info>,
+
+    /// CHECK: must be the open ballot's rent payer; unused when there is none
+    #[account(mut)]
+    pub rent_receiver: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub signer: Signer<'info>,
+}
+
+pub fn refund_escrow(ctx: Context<RefundEscrow>) -> Result<()> {
+    let cfg = &mut ctx.accounts.org_config;
+
+    let s = ctx.accounts.signer.key();
+    require!(
+        s == cfg.authority || cfg.is_oracle(&s),
+        InvoiceError::Unauthorized
+    );
+
+    let refund_ata = &ctx.accounts.refund_ata;
+    require!(
+        refund_ata.key() == cfg.treasury_vault
+            || refund_ata.owner == cfg.treasury_vault
+            || refund_ata.owner == cfg.authority
+            || refund_ata.owner == cfg.treasurer,
+        InvoiceError::InvalidWallet
+    );
+
+    let inv = &mut ctx.accounts.invoice_account;
+
+    // Rejected audits are always refundable; escrows stuck before settlement
+    // become refundable once they are ESCROW_EXPIRY_SECS past due.
+    let now = Clock::get()?.unix_timestamp;
+    let expired = matches!(
+        inv.status,
+        InvoiceStatus::InEscrowAwaitingVRF | InvoiceStatus::InEscrowAuditPending
+    ) && now > inv.due_date.saturating_add(ESCROW_EXPIRY_SECS);
+    require!(
+        inv.status == InvoiceStatus::Refunded || expired,
+        InvoiceError::InvalidStatus
+    );
+
+    // Status-only flows never moved tokens, so never refund more than the escrow holds
+    let amount = ctx.accounts.escrow_ata.amount.min(inv.amount);
+
+    if amount > 0 {
+        let invoice_key = inv.key();
+        let bump = ctx.bumps.escrow_authority;
+        let signer_seeds: &[&[u8]] = &[
+            b"escrow_auth",
+            invoice_key.as_ref(),
+            &[bump],
+        ];
+
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token::Transfer {
+                    from: ctx.accounts.escrow_ata.to_account_info(),
+                    to: ctx.accounts.refund_ata.to_account_info(),
+                    authority: ctx.accounts.escrow_authority.to_account_info(),
+                },
+                &[signer_seeds],
+            ),
+            amount,
+        )?;
+    }
+
+    inv.status = InvoiceStatus::RefundCompleted;
+    cfg.release_spend(inv.amount, inv.escrowed_at, now);
+
+    // An expired audit leaves its ballot open; return the rent to whoever opened it
+    let ballot = ctx.accounts.audit_ballot.to_account_info();
+    if ballot.owner == &crate::ID {
+        let payer = AuditBallot::try_deserialize(&mut &ballot.try_borrow_data()?[..])?.payer;
+        let receiver = ctx.accounts.rent_receiver.to_account_info();
+        require_keys_eq!(receiver.key(), payer, InvoiceError::InvalidWallet);
+
+        **receiver.try_borrow_mut_lamports()? += ballot.lamports();
+        **ballot.try_borrow_mut_lamports()? = 0;
+        ballot.assign(&System::id());
+        ballot.resize(0)?;
+    }
+    ctx.accounts.document_registry.release(&inv.key());
+    ctx.accounts.number_registry.release(&inv.key());
+
+    emit!(Refunded {
+        invoice: inv.key(),
+        refund_ata: refund_ata.key(),
+        amount,
+        status: inv.status,
+    });
+    msg!("Escrow refunded: {} -> {} ({})", inv.key(), refund_ata.key(), amount);
+    Ok(())
+}
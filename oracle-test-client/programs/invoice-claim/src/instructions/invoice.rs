@@ -1,5 +1,6 @@
 use anchor_lang::prelude::*;
 use crate::state::*;
+use crate::events::*;
 use anchor_lang::prelude::Pubkey;
 
 #[derive(Accounts)]
@@ -9,6 +10,13 @@ pub struct RequestExtraction<'info> {
     #[account(mut)]
     pub authority: Signer<'info>,
 
+    // Org the invoice is submitted to; only that org's oracle picks it up
+    #[account(
+        seeds = [b"org_config", org_config.creator.as_ref()],
+        bump = org_config.bump
+    )]
+    pub org_config: Account<'info, OrgConfig>,
+
     #[account(
         init,
         payer = authority,
@@ -29,6 +37,7 @@ pub fn request_invoice_extraction(
 ) -> Result<()> {
     require!(!ipfs_hash.is_empty(), InvoiceError::InvalidIPFSHash);
     require!(amount > 0, InvoiceError::InvalidAmount);
+    require!(!ctx.accounts.org_config.paused, InvoiceError::OrgPaused);
 
     ctx.accounts.invoice_request.set_inner(InvoiceRequest{
         authority: ctx.accounts.authority.key(),
@@ -37,6 +46,16 @@ pub fn request_invoice_extraction(
         timestamp: Clock::get()?.unix_timestamp,
         amount,
         nonce,
+        org: ctx.accounts.org_config.key(),
+    });
+
+    emit!(RequestCreated {
+        request: ctx.accounts.invoice_request.key(),
+        authority: ctx.accounts.authority.key(),
+        org: ctx.accounts.org_config.key(),
+        ipfs_hash: ipfs_hash.clone(),
+        amount,
+        nonce,
     });
 
     msg!("Invoice extraction requested for IPFS: {}", ipfs_hash);
@@ -63,14 +82,15 @@ pub fn debug_request_pda(
 }
 
 #[derive(Accounts)]
-#[instruction(vendor_name: String)]  //needed for vendor PDA derivation
+#[instruction(vendor_name: String, amount: u64, due_date: i64, invoice_number: String)]  //needed for vendor/registry PDA derivation
 pub struct ProcessResult<'info> {
+    // Must be the org's oracle signer (or one of its extra oracle keys)
     #[account(mut)]
     pub payer: Signer<'info>,
     // OrgConfig for oracle authorization and invoice counter
     #[account(
         mut,
-        seeds = [b"org_config", org_config.authority.as_ref()],
+        seeds = [b"org_config", org_config.creator.as_ref()],
         bump
     )]
     pub org_config: Account<'info, OrgConfig>,
@@ -102,6 +122,30 @@ pub struct ProcessResult<'info> {
     )]
     pub invoice_account: Account<'info, InvoiceAccount>,
 
+    // Same document already processed under another request?
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + InvoiceRegistry::INIT_SPACE,
+        seeds = [b"invoice_doc", org_config.key().as_ref(), &document_key(&invoice_request.ipfs_hash)],
+        bump
+    )]
+    pub document_registry: Account<'info, InvoiceRegistry>,
+
+    // Same vendor invoice number already processed (e.g. a re-scan of the PDF)?
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + InvoiceRegistry::INIT_SPACE,
+        seeds = [
+            b"invoice_number",
+            vendor_account.key().as_ref(),
+            &invoice_number_key(&invoice_number, &invoice_request.ipfs_hash)
+        ],
+        bump
+    )]
+    pub number_registry: Account<'info, InvoiceRegistry>,
+
     pub system_program: Program<'info, System>,
 }
 
@@ -110,17 +154,22 @@ pub fn process_extraction_result(
     vendor_name: String,
     amount: u64,
     due_date: i64,
+    invoice_number: String,
 ) -> Result<()> {
 
     let org_config = &mut ctx.accounts.org_config;
 
-
+    // Only the org's oracle may submit OCR results
+    require!(org_config.is_oracle(&ctx.accounts.payer.key()), InvoiceError::Unauthorized);
+    // The request can only be processed against the org it was submitted to
+    require_keys_eq!(ctx.accounts.invoice_request.org, org_config.key(), InvoiceError::WrongOrg);
 
     // Validate extracted data
     require!(amount > 0, InvoiceError::InvalidAmount);
     require!(amount <= org_config.per_invoice_cap, InvoiceError::CapExceeded);
     require!(!vendor_name.is_empty(), InvoiceError::InvalidVendor);
     require!(vendor_name.len() <= 50, InvoiceError::InvalidVendor);
+    require!(invoice_number.len() <= MAX_INVOICE_NUMBER_LEN, InvoiceError::InvalidInvoiceNumber);
 
     let current_time = Clock::get()?.unix_timestamp;
     require!(due_date > current_time, InvoiceError::InvalidDueDate);
@@ -132,23 +181,71 @@ pub fn process_extraction_result(
     let invoice = &mut ctx.accounts.invoice_account;
     let request = &mut ctx.accounts.invoice_request;
 
+    // A registry that already names an invoice means this one is a duplicate
+    let doc_registry = &mut ctx.accounts.document_registry;
+    require_keys_eq!(doc_registry.invoice, Pubkey::default(), InvoiceError::DuplicateDocument);
+    let number_registry = &mut ctx.accounts.number_registry;
+    require_keys_eq!(number_registry.invoice, Pubkey::default(), InvoiceError::DuplicateInvoiceNumber);
+
+    for (registry, bump) in [
+        (doc_registry, ctx.bumps.document_registry),
+        (number_registry, ctx.bumps.number_registry),
+    ] {
+        registry.set_inner(InvoiceRegistry {
+            org: org_config.key(),
+            invoice: invoice.key(),
+            created_at: current_time,
+            bump,
+        });
+    }
+
+    // Hold invoices whose OCR amount disagrees with what the submitter declared
+    let amount_ok = org_config.amount_within_tolerance(request.amount, amount);
+
     invoice.set_inner(InvoiceAccount{
         authority: request.authority,
         vendor_name,
         amount,
         due_date,
         ipfs_hash: request.ipfs_hash.clone(),
-        status: InvoiceStatus::Validated,
+        status: if amount_ok { InvoiceStatus::Validated } else { InvoiceStatus::NeedsReview },
         timestamp: Clock::get()?.unix_timestamp,
         vendor: ctx.accounts.vendor_account.key(),
         nonce: request.nonce,
+        invoice_number,
+        escrowed_at: 0,
     });
 
     request.status = RequestStatus::Completed;
+
+    if !amount_ok {
+        emit!(AmountMismatch {
+            invoice: invoice.key(),
+            request: request.key(),
+            org: org_config.key(),
+            declared_amount: request.amount,
+            extracted_amount: amount,
+            status: invoice.status,
+        });
+        msg!("Amount mismatch: declared {}, extracted {} - needs review", request.amount, amount);
+        return Ok(());
+    }
+
+    emit!(InvoiceValidated {
+        invoice: invoice.key(),
+        request: request.key(),
+        org: org_config.key(),
+        vendor: invoice.vendor,
+        amount: invoice.amount,
+        due_date: invoice.due_date,
+        status: invoice.status,
+    });
+
     msg!("Invoice processed: {} - ${}", invoice.vendor_name, invoice.amount);
     Ok(())
 }
-// Manual review decision after VRF selects the invoice for audit
+// One reviewer's vote on an invoice the VRF selected for audit. The invoice
+// moves on once approvals or rejections reach the org's audit quorum.
 #[derive(Accounts)]
 pub struct AuditDecide<'info> {
     #[account(mut)]
@@ -156,7 +253,7 @@ pub struct AuditDecide<'info> {
 
     #[account(
         mut,
-        seeds = [b"org_config", org_config.authority.as_ref()],
+        seeds = [b"org_config", org_config.creator.as_ref()],
         bump
     )]
     pub org_config: Account<'info, OrgConfig>,
@@ -168,47 +265,176 @@ pub struct AuditDecide<'info> {
     )]
     pub invoice_account: Account<'info, InvoiceAccount>,
 
+    // Ties the invoice to this org, so only its auditor set and quorum apply
     #[account(
-        mut,
-        seeds = [b"payment_queue", org_config.key().as_ref()],
+        seeds = [b"vendor", org_config.key().as_ref(), vendor_account.vendor_name.as_bytes()],
+        bump,
+        constraint = vendor_account.key() == invoice_account.vendor @ InvoiceError::InvalidVendor,
+    )]
+    pub vendor_account: Account<'info, VendorAccount>,
+
+    // Opened by the first vote, closed when the quorum is reached
+    #[account(
+        init_if_needed,
+        payer = reviewer,
+        space = 8 + AuditBallot::INIT_SPACE,
+        seeds = [b"audit_ballot", invoice_account.key().as_ref()],
         bump
     )]
-    pub payment_queue: Account<'info, PaymentQueue>,
+    pub audit_ballot: Account<'info, AuditBallot>,
+
+    /// CHECK: must be the ballot's rent payer (the first reviewer); only used when the ballot closes
+    #[account(mut)]
+    pub rent_receiver: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
 }
 
 pub fn audit_decide(ctx: Context<AuditDecide>, approve: bool) -> Result<()> {
     let org = &ctx.accounts.org_config;
-    // Only org authority or oracle signer can decide
-    require!(
-        ctx.accounts.reviewer.key() == org.authority || ctx.accounts.reviewer.key() == org.oracle_signer,
-        InvoiceError::Unauthorized
-    );
+    let reviewer = ctx.accounts.reviewer.key();
+    // Only the org's auditors can vote
+    require!(org.is_auditor(&reviewer), InvoiceError::Unauthorized);
 
     let invoice = &mut ctx.accounts.invoice_account;
     require!(invoice.status == InvoiceStatus::InEscrowAuditPending, InvoiceError::InvalidStatus);
 
+    // The first vote opens the ballot with the org's current quorum
+    let ballot = &mut ctx.accounts.audit_ballot;
+    if ballot.invoice == Pubkey::default() {
+        ballot.invoice = invoice.key();
+        ballot.org = org.key();
+        ballot.threshold = org.audit_quorum();
+        ballot.voters = org.audit_voters();
+        ballot.payer = reviewer;
+        ballot.bump = ctx.bumps.audit_ballot;
+    }
+    require!(
+        !ballot.approvals.contains(&reviewer) && !ballot.rejections.contains(&reviewer),
+        InvoiceError::AlreadyVoted
+    );
     if approve {
-        invoice.status = InvoiceStatus::InEscrowReadyToSettle;
+        ballot.approvals.push(reviewer);
+    } else {
+        ballot.rejections.push(reviewer);
+    }
 
-        // Add to PaymentQueue when audit is approved
-        let queue = &mut ctx.accounts.payment_queue;
-        let already = queue.pending_invoices.iter().any(|p| p.invoice_account == invoice.key());
-        require!(!already, InvoiceError::InvalidStatus);
-
-        let entry = PendingPayment {
-            invoice_account: invoice.key(),
-            vendor: invoice.vendor,
-            due_date: invoice.due_date,
-            amount: invoice.amount,
-        };
-
-        queue.pending_invoices.push(entry);
-        queue.count = queue.pending_invoices.len() as u64;
-        queue.last_updated = Clock::get()?.unix_timestamp;
-        msg!("✓ Added to PaymentQueue after audit approval: {}", invoice.key());
+    emit!(AuditVoteCast {
+        invoice: invoice.key(),
+        reviewer,
+        approve,
+        approvals: ballot.approvals.len() as u8,
+        rejections: ballot.rejections.len() as u8,
+        threshold: ballot.threshold,
+    });
+
+    // Reject as soon as too few auditors are left for approval to reach the
+    // quorum, so a split vote can't hold the escrow until it expires
+    let threshold = ballot.threshold as usize;
+    let rejections = ballot.rejections.len();
+    let approved = if ballot.approvals.len() >= threshold {
+        true
+    } else if rejections >= threshold || rejections > (ballot.voters as usize).saturating_sub(threshold) {
+        false
+    } else {
+        msg!(
+            "Audit vote recorded: {} approve, {} reject, {} needed",
+            ballot.approvals.len(),
+            ballot.rejections.len(),
+            threshold
+        );
+        return Ok(());
+    };
+
+    if approved {
+        // Ready for add_to_payment_queue
+        invoice.status = InvoiceStatus::InEscrowReadyToSettle;
     } else {
         invoice.status = InvoiceStatus::Refunded;
     }
 
+    // Quorum reached: refund the ballot's rent to whoever opened it
+    require_keys_eq!(ctx.accounts.rent_receiver.key(), ballot.payer, InvoiceError::InvalidWallet);
+    ballot.close(ctx.accounts.rent_receiver.to_account_info())?;
+
+    emit!(AuditDecided {
+        invoice: invoice.key(),
+        reviewer,
+        approved,
+        status: invoice.status,
+    });
+
+    Ok(())
+}
+
+// Org authority resolves an invoice held for an amount mismatch: approve
+// accepts the OCR amount, reject takes it out of the payment flow.
+#[derive(Accounts)]
+pub struct ReviewInvoiceAmount<'info> {
+    pub reviewer: Signer<'info>,
+
+    #[account(
+        seeds = [b"org_config", org_config.creator.as_ref()],
+        bump
+    )]
+    pub org_config: Account<'info, OrgConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"invoice", invoice_account.authority.as_ref(), &invoice_account.nonce.to_le_bytes()],
+        bump
+    )]
+    pub invoice_account: Account<'info, InvoiceAccount>,
+
+    // Ties the invoice to this org, so only its authority can review it
+    #[account(
+        seeds = [b"vendor", org_config.key().as_ref(), vendor_account.vendor_name.as_bytes()],
+        bump,
+        constraint = vendor_account.key() == invoice_account.vendor @ InvoiceError::InvalidVendor,
+    )]
+    pub vendor_account: Account<'info, VendorAccount>,
+    // Released when the invoice is rejected, so the document can be resubmitted
+    #[account(
+        mut,
+        seeds = [b"invoice_doc", org_config.key().as_ref(), &document_key(&invoice_account.ipfs_hash)],
+        bump = document_registry.bump
+    )]
+    pub document_registry: Account<'info, InvoiceRegistry>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"invoice_number",
+            vendor_account.key().as_ref(),
+            &invoice_number_key(&invoice_account.invoice_number, &invoice_account.ipfs_hash)
+        ],
+        bump = number_registry.bump
+    )]
+    pub number_registry: Account<'info, InvoiceRegistry>,
+}
+
+pub fn review_invoice_amount(ctx: Context<ReviewInvoiceAmount>, approve: bool) -> Result<()> {
+    require_keys_eq!(
+        ctx.accounts.reviewer.key(),
+        ctx.accounts.org_config.authority,
+        InvoiceError::Unauthorized
+    );
+
+    let invoice = &mut ctx.accounts.invoice_account;
+    require!(invoice.status == InvoiceStatus::NeedsReview, InvoiceError::InvalidStatus);
+
+    invoice.status = if approve { InvoiceStatus::Validated } else { InvoiceStatus::ReviewRejected };
+    if !approve {
+        ctx.accounts.document_registry.release(&invoice.key());
+        ctx.accounts.number_registry.release(&invoice.key());
+    }
+
+    emit!(AmountReviewed {
+        invoice: invoice.key(),
+        reviewer: ctx.accounts.reviewer.key(),
+        approved: approve,
+        status: invoice.status,
+    });
+
     Ok(())
 }
@@ -1,5 +1,6 @@
 use anchor_lang::prelude::*;
 use crate::state::*;
+use crate::events::*;
 
 #[derive(Accounts)]
 pub struct OrgInit<'info> {
@@ -30,21 +31,32 @@ pub fn org_init(
     require!(daily_cap >= per_invoice_cap, InvoiceError::CapExceeded);
     require!(audit_rate_bps <= 10_000, InvoiceError::InvalidAuditRate); // Max is 100%
 
+    let authority = ctx.accounts.authority.key();
     let cfg = &mut ctx.accounts.org_config;
     cfg.set_inner(OrgConfig{
-        authority: ctx.accounts.authority.key(),
-        oracle_signer: ctx.accounts.authority.key(),
+        authority,
+        oracle_signer: authority,
         treasury_vault,
         mint,
         per_invoice_cap,
         daily_cap,
         daily_spent: 0,
-        last_reset_day: Clock::get()?.unix_timestamp / 86400,
+        last_reset_day: Clock::get()?.unix_timestamp / SECONDS_PER_DAY,
         audit_rate_bps,
         paused: false,
         invoice_counter: 0,
         version: 1,
-        bump: ctx.bumps.org_config
+        bump: ctx.bumps.org_config,
+        extra_oracle_signers: Vec::new(),
+        amount_tolerance: 0,
+        amount_tolerance_bps: 0,
+        creator: authority,
+        pending_authority: None,
+        treasurer: authority,
+        auditor: authority,
+        vendor_manager: authority,
+        auditors: Vec::new(),
+        audit_threshold: 1,
     });
 
     msg!("Organization initialized - authority: {}", cfg.authority);
@@ -87,20 +99,186 @@ pub fn update_org_config(
         msg!("Oracle signer updated to: {}", oracle_signer);
     }
 
+    if let Some(extra) = args.extra_oracle_signers {
+        require!(extra.len() <= MAX_EXTRA_ORACLE_SIGNERS, InvoiceError::TooManyOracleSigners);
+        require!(!extra.contains(&Pubkey::default()), InvoiceError::InvalidWallet);
+        msg!("Extra oracle signers updated: {}", extra.len());
+        cfg.extra_oracle_signers = extra;
+    }
+
     if let Some(mint) = args.mint {
         require!(mint != Pubkey::default(), InvoiceError::WrongMint);
         cfg.mint = mint;
         msg!("Mint updated to: {}", mint);
     }
 
+    if let Some(tolerance) = args.amount_tolerance {
+        cfg.amount_tolerance = tolerance;
+        msg!("Amount tolerance updated: {}", tolerance);
+    }
+
+    if let Some(bps) = args.amount_tolerance_bps {
+        require!(bps <= 10_000, InvoiceError::InvalidTolerance);
+        cfg.amount_tolerance_bps = bps;
+        msg!("Amount tolerance updated: {} bps", bps);
+    }
+
+    let roles: &mut OrgConfig = cfg;
+    for (role, key, name) in [
+        (&mut roles.treasurer, args.treasurer, "Treasurer"),
+        (&mut roles.auditor, args.auditor, "Auditor"),
+        (&mut roles.vendor_manager, args.vendor_manager, "Vendor manager"),
+    ] {
+        if let Some(key) = key {
+            require!(key != Pubkey::default(), InvoiceError::InvalidWallet);
+            *role = key;
+            msg!("{} updated to: {}", name, key);
+        }
+    }
+
+    if let Some(auditors) = args.auditors {
+        require!(auditors.len() <= MAX_AUDITORS, InvoiceError::TooManyAuditors);
+        require!(!auditors.contains(&Pubkey::default()), InvoiceError::InvalidWallet);
+        require!(
+            auditors.iter().enumerate().all(|(i, a)| !auditors[..i].contains(a)),
+            InvoiceError::InvalidWallet
+        );
+        msg!("Auditor set updated: {}", auditors.len());
+        cfg.auditors = auditors;
+    }
+
+    if let Some(threshold) = args.audit_threshold {
+        cfg.audit_threshold = threshold;
+        msg!("Audit threshold updated: {}", threshold);
+    }
+
+    // The quorum must be reachable by the configured auditor set
+    require!(
+        cfg.audit_threshold >= 1
+            && (cfg.auditors.is_empty() || cfg.audit_threshold as usize <= cfg.auditors.len()),
+        InvoiceError::InvalidAuditThreshold
+    );
+
+    emit!(OrgConfigUpdated {
+        org: cfg.key(),
+        per_invoice_cap: cfg.per_invoice_cap,
+        daily_cap: cfg.daily_cap,
+        paused: cfg.paused,
+        oracle_signer: cfg.oracle_signer,
+        extra_oracle_signers: cfg.extra_oracle_signers.clone(),
+        mint: cfg.mint,
+        amount_tolerance: cfg.amount_tolerance,
+        amount_tolerance_bps: cfg.amount_tolerance_bps,
+        treasurer: cfg.treasurer,
+        auditor: cfg.auditor,
+        vendor_manager: cfg.vendor_manager,
+        auditors: cfg.auditors.clone(),
+        audit_threshold: cfg.audit_threshold,
+    });
+
     Ok(())
 }
 
+// Step one of an authority transfer: the current authority names its successor
+// (or cancels a pending proposal with None). Nothing changes until accepted.
+#[derive(Accounts)]
+pub struct ProposeOrgAuthority<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"org_config", org_config.creator.as_ref()],
+        bump = org_config.bump,
+        has_one = authority @ InvoiceError::Unauthorized,
+    )]
+    pub org_config: Account<'info, OrgConfig>,
+}
+
+pub fn propose_org_authority(
+    ctx: Context<ProposeOrgAuthority>,
+    new_authority: Option<Pubkey>,
+) -> Result<()> {
+    let cfg = &mut ctx.accounts.org_config;
+    if let Some(key) = new_authority {
+        require!(key != Pubkey::default(), InvoiceError::InvalidWallet);
+        require!(key != cfg.authority, InvoiceError::InvalidWallet);
+    }
+    cfg.pending_authority = new_authority;
+
+    emit!(OrgAuthorityProposed {
+        org: cfg.key(),
+        authority: cfg.authority,
+        pending_authority: new_authority,
+    });
+    Ok(())
+}
+
+// Step two: the proposed key signs to take over. The PDA keeps its creator seed,
+// so vendors, invoices and queues stay attached to the org.
+#[derive(Accounts)]
+pub struct AcceptOrgAuthority<'info> {
+    pub new_authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"org_config", org_config.creator.as_ref()],
+        bump = org_config.bump,
+        constraint = org_config.pending_authority == Some(new_authority.key()) @ InvoiceError::Unauthorized,
+    )]
+    pub org_config: Account<'info, OrgConfig>,
+}
+
+pub fn accept_org_authority(ctx: Context<AcceptOrgAuthority>) -> Result<()> {
+    let cfg = &mut ctx.accounts.org_config;
+    let previous_authority = cfg.authority;
+    let new_authority = ctx.accounts.new_authority.key();
+    cfg.authority = new_authority;
+    cfg.pending_authority = None;
+
+    // Roles default to the authority at init; any still held by the outgoing key
+    // move with the transfer so it keeps no oracle, treasury, audit or vendor powers.
+    // Explicitly configured auditor and extra oracle sets are left as they are.
+    let roles: &mut OrgConfig = cfg;
+    for role in [
+        &mut roles.oracle_signer,
+        &mut roles.treasurer,
+        &mut roles.auditor,
+        &mut roles.vendor_manager,
+    ] {
+        if *role == previous_authority {
+            *role = new_authority;
+        }
+    }
+
+    emit!(OrgAuthorityTransferred {
+        org: cfg.key(),
+        previous_authority,
+        new_authority: cfg.authority,
+    });
+    msg!("Organization authority transferred to: {}", cfg.authority);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct ViewOrgBudget<'info> {
+    #[account(
+        seeds = [b"org_config", org_config.creator.as_ref()],
+        bump = org_config.bump
+    )]
+    pub org_config: Account<'info, OrgConfig>,
+}
+
+// Read-only: how much of daily_cap can still be escrowed today (use via simulate / .view())
+pub fn get_remaining_daily_budget(ctx: Context<ViewOrgBudget>) -> Result<u64> {
+    let cfg = &ctx.accounts.org_config;
+    let remaining = cfg.remaining_daily_budget(Clock::get()?.unix_timestamp);
+    msg!("Remaining daily budget: {} of {}", remaining, cfg.daily_cap);
+    Ok(remaining)
+}
+
 #[derive(Accounts)]
 pub struct CloseOrg<'info> {
     #[account(
         mut,
-        seeds = [b"org_config", org_config.authority.as_ref()],
+        seeds = [b"org_config", org_config.creator.as_ref()],
         bump = org_config.bump,
         has_one = authority @ InvoiceError::Unauthorized,
         close = authority  // Closes account and sends rent to authority
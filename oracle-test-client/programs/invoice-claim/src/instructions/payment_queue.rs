@@ -1,5 +1,6 @@
 use anchor_lang::prelude::*;
 use crate::state::*;
+use crate::events::*;
 
 #[derive(Accounts)]
 pub struct InitPaymentQueue<'info> {
@@ -7,7 +8,7 @@ pub struct InitPaymentQueue<'info> {
     pub authority: Signer<'info>,
 
     #[account(
-        seeds = [b"org_config", org_config.authority.as_ref()],
+        seeds = [b"org_config", org_config.creator.as_ref()],
         bump = org_config.bump
     )]
     pub org_config: Account<'info, OrgConfig>,
@@ -27,7 +28,8 @@ pub struct InitPaymentQueue<'info> {
 pub fn init_payment_queue(ctx: Context<InitPaymentQueue>) -> Result<()> {
     let queue = &mut ctx.accounts.payment_queue;
     queue.org = ctx.accounts.org_config.key();
-    queue.pending_invoices = Vec::new();
+    queue.head = None;
+    queue.tail = None;
     queue.count = 0;
     queue.last_updated = Clock::get()?.unix_timestamp;
     queue.bump = ctx.bumps.payment_queue;
@@ -36,13 +38,18 @@ pub fn init_payment_queue(ctx: Context<InitPaymentQueue>) -> Result<()> {
     Ok(())
 }
 
-// Called when invoice reaches InEscrowReadyToSettle status
+// Called when invoice reaches InEscrowReadyToSettle status. Creates the
+// invoice's QueueEntry PDA and links it at its due-date position.
+// Clients find the position off-chain and pass the neighbours as hints.
 #[derive(Accounts)]
 pub struct AddToPaymentQueue<'info> {
+    // Org authority or oracle signer; pays rent for the entry
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
     #[account(
-        mut,
-        seeds = [b"org_config", org_config.authority.as_ref()],
-        bump
+        seeds = [b"org_config", org_config.creator.as_ref()],
+        bump = org_config.bump
     )]
     pub org_config: Account<'info, OrgConfig>,
 
@@ -52,63 +59,121 @@ pub struct AddToPaymentQueue<'info> {
     )]
     pub invoice_account: Account<'info, InvoiceAccount>,
 
+    // Ties the invoice to this org, so it can only enter its own org's queue
+    #[account(
+        seeds = [b"vendor", org_config.key().as_ref(), vendor_account.vendor_name.as_bytes()],
+        bump,
+        constraint = vendor_account.key() == invoice_account.vendor @ InvoiceError::InvalidVendor,
+    )]
+    pub vendor_account: Account<'info, VendorAccount>,
+
     #[account(
         mut,
         seeds = [b"payment_queue", org_config.key().as_ref()],
-        bump
+        bump = payment_queue.bump
     )]
     pub payment_queue: Account<'info, PaymentQueue>,
 
+    #[account(
+        init_if_needed,
+        payer = signer,
+        space = 8 + QueueEntry::INIT_SPACE,
+        seeds = [b"queue_entry", payment_queue.key().as_ref(), invoice_account.key().as_ref()],
+        bump
+    )]
+    pub queue_entry: Account<'info, QueueEntry>,
+
+    // Last entry due on or before this invoice (None to insert at the head)
+    #[account(mut)]
+    pub prev_entry: Option<Account<'info, QueueEntry>>,
+
+    // First entry due after this invoice (None to insert at the tail)
+    #[account(mut)]
+    pub next_entry: Option<Account<'info, QueueEntry>>,
+
     pub system_program: Program<'info, System>,
 }
 
 pub fn add_to_payment_queue(ctx: Context<AddToPaymentQueue>) -> Result<()> {
+    let cfg = &ctx.accounts.org_config;
+    let s = ctx.accounts.signer.key();
+    require!(s == cfg.authority || cfg.is_oracle(&s), InvoiceError::Unauthorized);
+
     let invoice = &ctx.accounts.invoice_account;
-    let queue = &mut ctx.accounts.payment_queue;
 
     // Only add invoices ready for settlement
-    // require!(
-    //     invoice.status == InvoiceStatus::InEscrowReadyToSettle,
-    //     InvoiceError::InvalidStatus
-    // );
-
-    // Prevent duplicates
     require!(
-        !queue.pending_invoices.iter().any(|p| p.invoice_account == invoice.key()),
-        InvoiceError::InvalidStatus // or create custom error
+        invoice.status == InvoiceStatus::InEscrowReadyToSettle,
+        InvoiceError::InvalidStatus
     );
 
-    let payment = PendingPayment {
-        invoice_account: invoice.key(),
-        vendor: invoice.vendor,
-        due_date: invoice.due_date,
-        amount: invoice.amount,
-    };
+    // A live entry already exists for this invoice
+    let entry = &mut ctx.accounts.queue_entry;
+    require!(entry.invoice_account == Pubkey::default(), InvoiceError::AlreadyQueued);
 
-    queue.pending_invoices.push(payment);
-    queue.count += 1;
-    queue.last_updated = Clock::get()?.unix_timestamp;
+    let queue = &mut ctx.accounts.payment_queue;
+    entry.queue = queue.key();
+    entry.invoice_account = invoice.key();
+    entry.vendor = invoice.vendor;
+    entry.due_date = invoice.due_date;
+    entry.amount = invoice.amount;
+    entry.payer = s;
+    entry.bump = ctx.bumps.queue_entry;
+
+    let entry_key = entry.key();
+    let prev = ctx.accounts.prev_entry.as_mut().map(|p| (p.key(), &mut **p));
+    let next = ctx.accounts.next_entry.as_mut().map(|n| (n.key(), &mut **n));
+    queue.link_sorted(entry_key, entry, prev, next, Clock::get()?.unix_timestamp)?;
+
+    emit!(QueuedForPayment {
+        invoice: invoice.key(),
+        queue: queue.key(),
+        entry: entry_key,
+        due_date: entry.due_date,
+        amount: entry.amount,
+    });
 
     msg!("Added invoice to payment queue: {}", invoice.key());
     Ok(())
 }
 
 #[derive(Accounts)]
+#[instruction(invoice_key: Pubkey)]
 pub struct RemoveFromPaymentQueue<'info> {
+    // Org authority or oracle signer
+    pub signer: Signer<'info>,
+
     #[account(
-        mut,
-        seeds = [b"org_config", org_config.authority.as_ref()],
-        bump
+        seeds = [b"org_config", org_config.creator.as_ref()],
+        bump = org_config.bump
     )]
     pub org_config: Account<'info, OrgConfig>,
 
     #[account(
         mut,
         seeds = [b"payment_queue", org_config.key().as_ref()],
-        bump
+        bump = payment_queue.bump
     )]
     pub payment_queue: Account<'info, PaymentQueue>,
 
+    /// CHECK: QueueEntry PDA for `invoice_key`; may be empty when not queued
+    #[account(
+        mut,
+        seeds = [b"queue_entry", payment_queue.key().as_ref(), invoice_key.as_ref()],
+        bump
+    )]
+    pub queue_entry: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub prev_entry: Option<Account<'info, QueueEntry>>,
+
+    #[account(mut)]
+    pub next_entry: Option<Account<'info, QueueEntry>>,
+
+    /// CHECK: must be the entry's rent payer; checked in close_queue_entry
+    #[account(mut)]
+    pub rent_receiver: UncheckedAccount<'info>,
+
     pub system_program: Program<'info, System>,
 }
 
@@ -116,19 +181,59 @@ pub fn remove_from_payment_queue(
     ctx: Context<RemoveFromPaymentQueue>,
     invoice_key: Pubkey,
 ) -> Result<()> {
-    let queue = &mut ctx.accounts.payment_queue;
-
-    let initial_len = queue.pending_invoices.len();
-    queue.pending_invoices.retain(|p| p.invoice_account != invoice_key);
-
-    require!(
-        queue.pending_invoices.len() < initial_len,
-        InvoiceError::InvalidStatus // Invoice not found in queue
-    );
-
-    queue.count = queue.pending_invoices.len() as u64;
-    queue.last_updated = Clock::get()?.unix_timestamp;
+    let cfg = &ctx.accounts.org_config;
+    let s = ctx.accounts.signer.key();
+    require!(s == cfg.authority || cfg.is_oracle(&s), InvoiceError::Unauthorized);
+
+    let a = &mut *ctx.accounts;
+    let removed = close_queue_entry(
+        &mut a.payment_queue,
+        &a.queue_entry,
+        a.prev_entry.as_mut(),
+        a.next_entry.as_mut(),
+        &a.rent_receiver,
+        Clock::get()?.unix_timestamp,
+    )?;
+    require!(removed, InvoiceError::NotInQueue);
 
     msg!("Removed invoice from payment queue: {}", invoice_key);
     Ok(())
 }
+
+// Unlink the invoice's QueueEntry (if it holds one) and return its rent to the
+// original payer. Returns false when the invoice was not queued.
+pub(crate) fn close_queue_entry<'info>(
+    queue: &mut Account<'info, PaymentQueue>,
+    entry_info: &AccountInfo<'info>,
+    prev: Option<&mut Account<'info, QueueEntry>>,
+    next: Option<&mut Account<'info, QueueEntry>>,
+    rent_receiver: &AccountInfo<'info>,
+    now: i64,
+) -> Result<bool> {
+    if entry_info.data_is_empty() {
+        return Ok(false);
+    }
+    require_keys_eq!(*entry_info.owner, crate::ID, InvoiceError::QueueLinkMismatch);
+
+    let entry = {
+        let data = entry_info.try_borrow_data()?;
+        QueueEntry::try_deserialize(&mut &data[..])?
+    };
+    require_keys_eq!(entry.queue, queue.key(), InvoiceError::QueueLinkMismatch);
+    require_keys_eq!(rent_receiver.key(), entry.payer, InvoiceError::InvalidWallet);
+
+    let prev = prev.map(|p| (p.key(), &mut **p));
+    let next = next.map(|n| (n.key(), &mut **n));
+    queue.unlink(entry_info.key(), &entry, prev, next, now)?;
+
+    // Close: move rent to the payer and hand the account back to the system program
+    let lamports = entry_info.lamports();
+    **rent_receiver.try_borrow_mut_lamports()? = rent_receiver
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(InvoiceError::Overflow)?;
+    **entry_info.try_borrow_mut_lamports()? = 0;
+    entry_info.assign(&System::id());
+    entry_info.resize(0)?;
+    Ok(true)
+}
@@ -32,24 +32,3 @@ pub fn process_invoice_payment(ctx: Context<ProcessPayment>) -> Result<()> {
     msg!("Invoice logically moved to escrow (status-only)");
     Ok(())
 }
-
-#[derive(Accounts)]
-pub struct CompletePayment<'info> {
-    #[account(
-        mut,
-        seeds = [b"invoice", authority.key().as_ref(), &invoice_account.nonce.to_le_bytes()],
-        bump,
-        has_one = authority
-    )]
-    pub invoice_account: Account<'info, InvoiceAccount>,
-
-    pub authority: Signer<'info>,
-}
-
-pub fn complete_payment(ctx: Context<CompletePayment>) -> Result<()> {
-    let invoice = &mut ctx.accounts.invoice_account;
-    require!(invoice.status == InvoiceStatus::InEscrowReadyToSettle, InvoiceError::InvalidStatus);
-    invoice.status = InvoiceStatus::Paid;
-    msg!("Payment completed for vendor: {}", invoice.vendor_name);
-    Ok(())
-}
@@ -1,5 +1,6 @@
 use anchor_lang::prelude::*;
 use crate::state::*;
+use crate::events::*;
 
 
 #[derive(Accounts)]
@@ -15,10 +16,11 @@ pub struct RegisterVendor<'info> {
     pub vendor_account: Account<'info, VendorAccount>,
 
     #[account(
-        has_one = authority
+        constraint = org_config.is_vendor_manager(&authority.key()) @ InvoiceError::Unauthorized
     )]
     pub org_config: Account<'info, OrgConfig>,
 
+    // Org authority or vendor manager
     #[account(mut)]
     pub authority: Signer<'info>,
     pub system_program: Program<'info, System>,
@@ -43,6 +45,13 @@ pub fn register_vendor(
         currency_preference: ctx.accounts.org_config.mint,
     });
 
+    emit!(VendorRegistered {
+        vendor: ctx.accounts.vendor_account.key(),
+        org: ctx.accounts.org_config.key(),
+        vendor_name: vendor_name.clone(),
+        wallet,
+    });
+
     msg!("Vendor registered: {}", vendor_name);
     Ok(())
 }
@@ -57,10 +66,11 @@ pub struct ManageVendor<'info> {
     pub vendor_account: Account<'info, VendorAccount>,
 
     #[account(
-        has_one = authority @ InvoiceError::Unauthorized
+        constraint = org_config.is_vendor_manager(&authority.key()) @ InvoiceError::Unauthorized
     )]
     pub org_config: Account<'info, OrgConfig>,
 
+    // Org authority or vendor manager
     pub authority: Signer<'info>,
 }
 
@@ -69,6 +79,7 @@ pub fn deactivate_vendor(ctx: Context<ManageVendor>) -> Result<()> {
     require!(vendor.is_active, InvoiceError::VendorInactive);
 
     vendor.is_active = false;
+    emit!(VendorDeactivated { vendor: vendor.key(), org: vendor.org });
     msg!("Vendor deactivated: {}", vendor.vendor_name);
     Ok(())
 }
@@ -78,12 +89,32 @@ pub fn activate_vendor(ctx: Context<ManageVendor>) -> Result<()> {
     require!(!vendor.is_active, InvoiceError::VendorInactive);
 
     vendor.is_active = true;
+    emit!(VendorActivated { vendor: vendor.key(), org: vendor.org });
     msg!("Vendor activated: {}", vendor.vendor_name);
     Ok(())
 }
 
+// Repointing payouts is reserved to the org authority; the vendor manager
+// role only administers the vendor list.
+#[derive(Accounts)]
+pub struct UpdateVendorWallet<'info> {
+    #[account(
+        mut,
+        seeds = [b"vendor", org_config.key().as_ref(), vendor_account.vendor_name.as_bytes()],
+        bump
+    )]
+    pub vendor_account: Account<'info, VendorAccount>,
+
+    #[account(
+        has_one = authority @ InvoiceError::Unauthorized
+    )]
+    pub org_config: Account<'info, OrgConfig>,
+
+    pub authority: Signer<'info>,
+}
+
 pub fn update_vendor_wallet(
-    ctx: Context<ManageVendor>,
+    ctx: Context<UpdateVendorWallet>,
     new_wallet: Pubkey,
 ) -> Result<()> {
     require!(new_wallet != Pubkey::default(), InvoiceError::InvalidWallet);
@@ -4,10 +4,16 @@ use ephemeral_vrf_sdk::instructions::{create_request_randomness_ix, RequestRando
 use ephemeral_vrf_sdk::types::SerializableAccountMeta;
 use crate::CALLBACK_VRF_DISCRIMINATOR;
 use crate::state::*;
+use crate::events::*;
 
 /// STEP 1: Request randomness to decide if invoice should be audited
 pub fn request_invoice_audit_vrf(ctx: Context<RequestInvoiceAuditVrf>, client_seed: u8) -> Result<()> {
     let invoice = &ctx.accounts.invoice_account;
+    let org = &ctx.accounts.org_config;
+
+    // The callback reads this org's audit rate, so only it may ask
+    let payer = ctx.accounts.payer.key();
+    require!(payer == org.authority || org.is_oracle(&payer), InvoiceError::Unauthorized);
 
     require!(
         invoice.status == InvoiceStatus::InEscrowAwaitingVRF,
@@ -38,9 +44,9 @@ pub fn request_invoice_audit_vrf(ctx: Context<RequestInvoiceAuditVrf>, client_se
                 is_writable: false,
             },
             SerializableAccountMeta {
-                pubkey: ctx.accounts.payment_queue.key(),
+                pubkey: ctx.accounts.vendor_account.key(),
                 is_signer: false,
-                is_writable: true,
+                is_writable: false,
             },
         ]),
         ..Default::default()
@@ -49,6 +55,12 @@ pub fn request_invoice_audit_vrf(ctx: Context<RequestInvoiceAuditVrf>, client_se
     // Dispatch request to oracle
     ctx.accounts.invoke_signed_vrf(&ctx.accounts.payer.to_account_info(), &ix)?;
 
+    emit!(VrfRequested {
+        invoice: ctx.accounts.invoice_account.key(),
+        org: ctx.accounts.org_config.key(),
+        client_seed,
+    });
+
     msg!("VRF randomness request sent.");
     Ok(())
 }
@@ -59,7 +71,11 @@ pub fn callback_invoice_vrf(ctx: Context<CallbackInvoiceVrf>, randomness: [u8; 3
 
     let invoice = &mut ctx.accounts.invoice_account;
     let org_config = &ctx.accounts.org_config;
-    let payment_queue = &mut ctx.accounts.payment_queue;
+
+    // Apply only the audit rate of the org the request was bound to
+    let vendor = &ctx.accounts.vendor_account;
+    require_keys_eq!(vendor.key(), invoice.vendor, InvoiceError::InvalidVendor);
+    require_keys_eq!(vendor.org, org_config.key(), InvoiceError::WrongOrg);
 
     // Only apply VRF outcome immediately after validation.
     // Prevents late/duplicate callbacks from overriding post-VRF states.
@@ -83,29 +99,16 @@ pub fn callback_invoice_vrf(ctx: Context<CallbackInvoiceVrf>, randomness: [u8; 3
         InvoiceStatus::InEscrowReadyToSettle
     };
 
-    // AUTO-ADD to PaymentQueue when invoice is approved for settlement
-    if !audit_selected {
-        // Check if already in queue to prevent duplicates
-        let already_queued = payment_queue
-            .pending_invoices
-            .iter()
-            .any(|p| p.invoice_account == invoice.key());
-
-        require!(!already_queued, InvoiceError::InvalidStatus);
-
-        let pending_payment = PendingPayment {
-            invoice_account: invoice.key(),
-            vendor: invoice.vendor,
-            due_date: invoice.due_date,
-            amount: invoice.amount,
-        };
-
-        payment_queue.pending_invoices.push(pending_payment);
-        payment_queue.count += 1;
-        payment_queue.last_updated = Clock::get()?.unix_timestamp;
+    // The callback has no rent payer, so queueing is left to the org
+    // authority / oracle via add_to_payment_queue.
 
-        msg!("Invoice added to PaymentQueue: {}", invoice.key());
-    }
+    emit!(AuditSelected {
+        invoice: invoice.key(),
+        selected: audit_selected,
+        random_value,
+        audit_rate_bps: org_config.audit_rate_bps,
+        status: invoice.status,
+    });
 
     msg!(
         "Invoice {} | Audit Selected: {} | Random Value: {} | Threshold: {} bps",
@@ -125,7 +128,7 @@ pub struct RequestInvoiceAuditVrf<'info> {
 
     #[account(
         mut,
-        seeds = [b"org_config", org_config.authority.as_ref()],
+        seeds = [b"org_config", org_config.creator.as_ref()],
         bump
     )]
     pub org_config: Account<'info, OrgConfig>,
@@ -137,12 +140,13 @@ pub struct RequestInvoiceAuditVrf<'info> {
     )]
     pub invoice_account: Account<'info, InvoiceAccount>,
 
+    // Ties the invoice to this org, whose audit rate the callback applies
     #[account(
-        mut,
-        seeds = [b"payment_queue", org_config.key().as_ref()],
-        bump
+        seeds = [b"vendor", org_config.key().as_ref(), vendor_account.vendor_name.as_bytes()],
+        bump,
+        constraint = vendor_account.key() == invoice_account.vendor @ InvoiceError::InvalidVendor,
     )]
-    pub payment_queue: Account<'info, PaymentQueue>,
+    pub vendor_account: Account<'info, VendorAccount>,
 
     /// CHECK: Oracle queue reference
     #[account(mut, address = ephemeral_vrf_sdk::consts::DEFAULT_QUEUE)]
@@ -164,11 +168,6 @@ pub struct CallbackInvoiceVrf<'info> {
     // 2) org_config (readonly)
     pub org_config: Account<'info, OrgConfig>,
 
-    // 3) payment_queue (writable) - NEW
-    #[account(
-        mut,
-        seeds = [b"payment_queue", org_config.key().as_ref()],
-        bump
-    )]
-    pub payment_queue: Account<'info, PaymentQueue>,
+    // 3) vendor_account (readonly), checked against both in the handler
+    pub vendor_account: Account<'info, VendorAccount>,
 }
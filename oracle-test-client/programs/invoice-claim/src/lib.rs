@@ -6,9 +6,11 @@ declare_id!("HQ5y6ZMwNHSrRvma4bDHtay4UDW5qBM63A5mvyGi4MkH");
 
 pub const CALLBACK_VRF_DISCRIMINATOR: [u8; 7] = *b"clbrand"; 
 mod state;
+mod events;
 mod instructions;
 
 pub use crate::state::*;
+pub use crate::events::*;
 use crate::instructions::*;
 
 
@@ -32,8 +34,9 @@ pub mod invoice_claim {
         vendor_name: String,
         amount: u64,
         due_date: i64,
+        invoice_number: String,
     ) -> Result<()> {
-        instructions::invoice::process_extraction_result(ctx, vendor_name, amount, due_date)
+        instructions::invoice::process_extraction_result(ctx, vendor_name, amount, due_date, invoice_number)
     }
 
     pub fn request_invoice_audit_vrf(ctx: Context<RequestInvoiceAuditVrf>, client_seed: u8) -> Result<()> {
@@ -50,10 +53,6 @@ pub mod invoice_claim {
         instructions::payments::process_invoice_payment(ctx)
     }
 
-    pub fn complete_payment(ctx: Context<CompletePayment>) -> Result<()> {
-        instructions::payments::complete_payment(ctx)
-    }
-
     // Close accounts
     pub fn close_invoice(ctx: Context<CloseInvoice>) -> Result<()> {
         instructions::close::close_invoice(ctx)
@@ -63,11 +62,16 @@ pub mod invoice_claim {
         instructions::close::close_request(ctx)
     }
 
-    // Manual review decision after VRF selects invoice for audit
+    // Manual review vote after VRF selects invoice for audit (M-of-N auditors)
     pub fn audit_decide(ctx: Context<AuditDecide>, approve: bool) -> Result<()> {
         instructions::invoice::audit_decide(ctx, approve)
     }
 
+    // Resolve an invoice held for an amount mismatch
+    pub fn review_invoice_amount(ctx: Context<ReviewInvoiceAmount>, approve: bool) -> Result<()> {
+        instructions::invoice::review_invoice_amount(ctx, approve)
+    }
+
     // Org config
     pub fn org_init(
         ctx: Context<OrgInit>,
@@ -85,6 +89,19 @@ pub mod invoice_claim {
         instructions::org::update_org_config(ctx, update_args)
     }
 
+    // Two-step authority transfer
+    pub fn propose_org_authority(ctx: Context<ProposeOrgAuthority>, new_authority: Option<Pubkey>) -> Result<()> {
+        instructions::org::propose_org_authority(ctx, new_authority)
+    }
+
+    pub fn accept_org_authority(ctx: Context<AcceptOrgAuthority>) -> Result<()> {
+        instructions::org::accept_org_authority(ctx)
+    }
+
+    pub fn get_remaining_daily_budget(ctx: Context<ViewOrgBudget>) -> Result<u64> {
+        instructions::org::get_remaining_daily_budget(ctx)
+    }
+
     pub fn close_org(ctx: Context<CloseOrg>) -> Result<()> {
         instructions::org::close_org(ctx)
     }
@@ -98,6 +115,10 @@ pub mod invoice_claim {
         instructions::escrow::settle_to_vendor(ctx)
     }
 
+    pub fn refund_escrow(ctx: Context<RefundEscrow>) -> Result<()> {
+        instructions::escrow::refund_escrow(ctx)
+    }
+
     //Vendor Management
     pub fn register_vendor(
         ctx: Context<RegisterVendor>,
@@ -119,7 +140,7 @@ pub mod invoice_claim {
         instructions::vendor::close_vendor(ctx)
     }
 
-    pub fn update_vendor_wallet(ctx: Context<ManageVendor>, new_wallet: Pubkey) -> Result<()> {
+    pub fn update_vendor_wallet(ctx: Context<UpdateVendorWallet>, new_wallet: Pubkey) -> Result<()> {
         instructions::vendor::update_vendor_wallet(ctx, new_wallet)
     }
     // Debug: print the PDA derived by the program for (authority, nonce)
@@ -143,7 +164,20 @@ pub mod invoice_claim {
         extracted_amount: u64,
         extracted_vendor: Pubkey,
     ) -> Result<()> {
+        // Same gate as process_extraction_result: only the org's oracle may write results
+        require!(
+            ctx.accounts.org_config.is_oracle(&ctx.accounts.oracle.key()),
+            InvoiceError::Unauthorized
+        );
+        // ...and only for requests submitted to that org
+        require_keys_eq!(
+            ctx.accounts.invoice_request.org,
+            ctx.accounts.org_config.key(),
+            InvoiceError::WrongOrg
+        );
+
         let invoice = &mut ctx.accounts.invoice_request;
+        require!(invoice.status == RequestStatus::Pending, InvoiceError::InvalidStatus);
         invoice.amount = extracted_amount;
         invoice.authority = extracted_vendor;
         invoice.status = RequestStatus::Completed;
@@ -190,5 +224,13 @@ pub struct CommitInvoice<'info> {
     #[account(mut)]
     pub invoice_request: Account<'info, InvoiceRequest>,
 
+    #[account(
+        seeds = [b"org_config", org_config.creator.as_ref()],
+        bump = org_config.bump
+    )]
+    pub org_config: Account<'info, OrgConfig>,
+
+    pub oracle: Signer<'info>,
+
     pub system_program: Program<'info, System>,
 }
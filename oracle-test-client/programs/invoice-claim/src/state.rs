@@ -1,23 +1,122 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hash;
 
-
+// Header of the payment queue. Entries live in their own QueueEntry PDAs and
+// form a doubly linked list ordered by due_date, so the queue has no fixed
+// capacity and the head is always the next payment to fall due.
 #[account]
 #[derive(InitSpace)]
 pub struct PaymentQueue {
     pub org: Pubkey,                    // Links to OrgConfig
-    #[max_len(100)]
-    pub pending_invoices: Vec<PendingPayment>,
+    pub head: Option<Pubkey>,           // First QueueEntry
+    pub tail: Option<Pubkey>,           // Last QueueEntry
     pub count: u64,                     // Total entries
     pub last_updated: i64,              // Last time queue was modified
     pub bump: u8,
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
-pub struct PendingPayment {
+// One queued invoice. Seeds: [b"queue_entry", payment_queue, invoice_account]
+#[account]
+#[derive(InitSpace)]
+pub struct QueueEntry {
+    pub queue: Pubkey,                  // Owning PaymentQueue
     pub invoice_account: Pubkey,        // Reference to InvoiceAccount
     pub vendor: Pubkey,                 // Vendor pubkey (redundant but speeds up cranker)
     pub due_date: i64,                  // Due date timestamp
     pub amount: u64,                    // Invoice amount
+    pub prev: Option<Pubkey>,
+    pub next: Option<Pubkey>,
+    pub payer: Pubkey,                  // Paid the rent; refunded when the entry is closed
+    pub bump: u8,
+}
+
+impl PaymentQueue {
+    // Insert `entry` between `prev` and `next`, keeping the list sorted by
+    // due_date (ties go after existing entries). The client supplies the
+    // neighbours; they must be adjacent in this queue and bracket the due date.
+    pub fn link_sorted(
+        &mut self,
+        key: Pubkey,
+        entry: &mut QueueEntry,
+        prev: Option<(Pubkey, &mut QueueEntry)>,
+        next: Option<(Pubkey, &mut QueueEntry)>,
+        now: i64,
+    ) -> Result<()> {
+        let prev_key = prev.as_ref().map(|(k, _)| *k);
+        let next_key = next.as_ref().map(|(k, _)| *k);
+
+        // Neighbours must be adjacent in this queue
+        let after_prev = match &prev {
+            Some((_, p)) => {
+                require_keys_eq!(p.queue, entry.queue, InvoiceError::QueueLinkMismatch);
+                p.next
+            }
+            None => self.head,
+        };
+        let before_next = match &next {
+            Some((_, n)) => {
+                require_keys_eq!(n.queue, entry.queue, InvoiceError::QueueLinkMismatch);
+                n.prev
+            }
+            None => self.tail,
+        };
+        require!(
+            after_prev == next_key && before_next == prev_key,
+            InvoiceError::QueueLinkMismatch
+        );
+
+        // ...and bracket the new due date
+        if let Some((_, p)) = &prev {
+            require!(p.due_date <= entry.due_date, InvoiceError::QueueOrderViolation);
+        }
+        if let Some((_, n)) = &next {
+            require!(entry.due_date < n.due_date, InvoiceError::QueueOrderViolation);
+        }
+
+        match prev {
+            Some((_, p)) => p.next = Some(key),
+            None => self.head = Some(key),
+        }
+        match next {
+            Some((_, n)) => n.prev = Some(key),
+            None => self.tail = Some(key),
+        }
+        entry.prev = prev_key;
+        entry.next = next_key;
+        self.count = self.count.checked_add(1).ok_or(InvoiceError::Overflow)?;
+        self.last_updated = now;
+        Ok(())
+    }
+
+    // Detach `entry` from the list. `prev`/`next` must be its current neighbours.
+    pub fn unlink(
+        &mut self,
+        key: Pubkey,
+        entry: &QueueEntry,
+        prev: Option<(Pubkey, &mut QueueEntry)>,
+        next: Option<(Pubkey, &mut QueueEntry)>,
+        now: i64,
+    ) -> Result<()> {
+        match (entry.prev, prev) {
+            (None, None) => {
+                require!(self.head == Some(key), InvoiceError::QueueLinkMismatch);
+                self.head = entry.next;
+            }
+            (Some(p), Some((prev_key, prev))) if p == prev_key => prev.next = entry.next,
+            _ => return err!(InvoiceError::QueueLinkMismatch),
+        }
+        match (entry.next, next) {
+            (None, None) => {
+                require!(self.tail == Some(key), InvoiceError::QueueLinkMismatch);
+                self.tail = entry.prev;
+            }
+            (Some(n), Some((next_key, next))) if n == next_key => next.prev = entry.prev,
+            _ => return err!(InvoiceError::QueueLinkMismatch),
+        }
+        self.count = self.count.saturating_sub(1);
+        self.last_updated = now;
+        Ok(())
+    }
 }
 
 #[account]
@@ -30,6 +129,7 @@ pub struct InvoiceRequest {
     pub timestamp: i64,
     pub amount: u64,
     pub nonce: u64,
+    pub org: Pubkey,                    // OrgConfig the invoice was submitted to
 }
 
 #[account]
@@ -46,6 +146,54 @@ pub struct InvoiceAccount {
     pub status: InvoiceStatus,
     pub timestamp: i64,
     pub nonce: u64,
+    #[max_len(32)]
+    pub invoice_number: String,         // Vendor's own invoice number, as extracted
+    pub escrowed_at: i64,               // When fund_escrow charged the daily cap (0 before)
+}
+
+// Claims an invoice identity so it can only be processed once, whatever nonce
+// it is resubmitted under. Two registries exist per invoice:
+//   [b"invoice_doc", org_config, document_key(ipfs_hash)]
+//   [b"invoice_number", vendor_account, invoice_number_key(invoice_number, ipfs_hash)]
+#[account]
+#[derive(InitSpace)]
+pub struct InvoiceRegistry {
+    pub org: Pubkey,
+    pub invoice: Pubkey,                // First InvoiceAccount to claim this key
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+impl InvoiceRegistry {
+    /// Free the key for a later submission, if `invoice` still holds it.
+    /// Called when the invoice ends without being paid, or is closed.
+    pub fn release(&mut self, invoice: &Pubkey) {
+        if self.invoice == *invoice {
+            self.invoice = Pubkey::default();
+        }
+    }
+}
+
+pub const MAX_INVOICE_NUMBER_LEN: usize = 32;
+
+// Registry key for a document: sha256 of its IPFS hash (seeds are capped at 32 bytes)
+pub fn document_key(ipfs_hash: &str) -> [u8; 32] {
+    hash(ipfs_hash.as_bytes()).to_bytes()
+}
+
+// Registry key for a vendor invoice number. Only ASCII letters and digits count,
+// case-insensitively, so "INV-0042" and "inv 0042" collide. Invoices without a
+// number fall back to the document key.
+pub fn invoice_number_key(invoice_number: &str, ipfs_hash: &str) -> [u8; 32] {
+    let normalized: Vec<u8> = invoice_number
+        .bytes()
+        .filter(u8::is_ascii_alphanumeric)
+        .map(|b| b.to_ascii_uppercase())
+        .collect();
+    if normalized.is_empty() {
+        return document_key(ipfs_hash);
+    }
+    hash(&normalized).to_bytes()
 }
 
 //A singleton state that manages the full protocol
@@ -59,12 +207,134 @@ pub struct OrgConfig {
     pub per_invoice_cap: u64,
     pub daily_cap: u64,
     pub daily_spent: u64,               // Track daily spending
-    pub last_reset_day: i64,            // Last day caps were reset
+    pub last_reset_day: i64,            // Last UTC day index (unix / 86400) caps were reset
     pub audit_rate_bps: u16,            // Basis points (e.g., 500 = 5%)
     pub paused: bool,
     pub invoice_counter: u64,
     pub version: u8,
-    pub bump: u8
+    pub bump: u8,
+    #[max_len(4)]
+    pub extra_oracle_signers: Vec<Pubkey>, // Additional keys allowed to act as oracle_signer
+    pub amount_tolerance: u64,          // Allowed |extracted - declared| in base units
+    pub amount_tolerance_bps: u16,      // ...or as basis points of the declared amount
+    pub creator: Pubkey,                // PDA seed; stays fixed when authority is transferred
+    pub pending_authority: Option<Pubkey>, // Proposed authority, set until accept_org_authority
+    pub treasurer: Pubkey,              // May fund escrow
+    pub auditor: Pubkey,                // May decide VRF-selected audits
+    pub vendor_manager: Pubkey,         // May register and (de)activate vendors
+    #[max_len(8)]
+    pub auditors: Vec<Pubkey>,          // Audit reviewer set; empty leaves audits to the auditor role
+    pub audit_threshold: u8,            // Votes from `auditors` needed to approve or reject an audit
+}
+
+pub const MAX_AUDITORS: usize = 8;
+
+// Votes on one audit-selected invoice. Seeds: [b"audit_ballot", invoice_account]
+// Opened by the first audit_decide vote and closed once either side reaches the threshold.
+#[account]
+#[derive(InitSpace)]
+pub struct AuditBallot {
+    pub invoice: Pubkey,
+    pub org: Pubkey,
+    pub threshold: u8,                  // Org's quorum when the ballot was opened
+    pub voters: u8,                     // Eligible auditors when the ballot was opened
+    #[max_len(8)]
+    pub approvals: Vec<Pubkey>,
+    #[max_len(8)]
+    pub rejections: Vec<Pubkey>,
+    pub payer: Pubkey,                  // Paid the rent; refunded when the ballot closes
+    pub bump: u8,
+}
+
+pub const MAX_EXTRA_ORACLE_SIGNERS: usize = 4;
+
+pub const SECONDS_PER_DAY: i64 = 86_400;
+// Escrow still waiting on VRF/audit this long after due_date can be refunded
+pub const ESCROW_EXPIRY_SECS: i64 = 30 * SECONDS_PER_DAY;
+
+impl OrgConfig {
+    // Primary oracle signer or one of the extra authorized oracle keys
+    pub fn is_oracle(&self, key: &Pubkey) -> bool {
+        *key == self.oracle_signer || self.extra_oracle_signers.contains(key)
+    }
+
+    // Role checks; the org authority can act as treasurer and vendor manager
+    pub fn is_treasurer(&self, key: &Pubkey) -> bool {
+        *key == self.authority || *key == self.treasurer
+    }
+
+    // With an auditor set configured only its members may vote on audits;
+    // otherwise the auditor role (or the authority) decides alone
+    pub fn is_auditor(&self, key: &Pubkey) -> bool {
+        if self.auditors.is_empty() {
+            *key == self.authority || *key == self.auditor
+        } else {
+            self.auditors.contains(key)
+        }
+    }
+
+    // Votes needed on either side to settle an audit
+    pub fn audit_quorum(&self) -> u8 {
+        if self.auditors.is_empty() { 1 } else { self.audit_threshold }
+    }
+
+    // Keys that may vote on an audit
+    pub fn audit_voters(&self) -> u8 {
+        if !self.auditors.is_empty() {
+            self.auditors.len() as u8
+        } else if self.auditor == self.authority {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn is_vendor_manager(&self, key: &Pubkey) -> bool {
+        *key == self.authority || *key == self.vendor_manager
+    }
+
+    // OCR amount agrees with the submitter's declared amount, within either the
+    // absolute or the relative tolerance (both zero means an exact match)
+    pub fn amount_within_tolerance(&self, declared: u64, extracted: u64) -> bool {
+        let diff = declared.abs_diff(extracted) as u128;
+        diff <= self.amount_tolerance as u128
+            || diff * 10_000 <= declared as u128 * self.amount_tolerance_bps as u128
+    }
+
+    // Reset daily_spent when the UTC day has changed since the last reset
+    pub fn roll_daily_window(&mut self, now: i64) {
+        let today = now / SECONDS_PER_DAY;
+        if today != self.last_reset_day {
+            self.daily_spent = 0;
+            self.last_reset_day = today;
+        }
+    }
+
+    // Budget left for the current UTC day (a stale window counts as fully reset)
+    pub fn remaining_daily_budget(&self, now: i64) -> u64 {
+        if now / SECONDS_PER_DAY != self.last_reset_day {
+            return self.daily_cap;
+        }
+        self.daily_cap.saturating_sub(self.daily_spent)
+    }
+
+    // Charge `amount` against today's cap, rolling the window first
+    pub fn record_spend(&mut self, amount: u64, now: i64) -> Result<()> {
+        self.roll_daily_window(now);
+        let spent = self.daily_spent.checked_add(amount).ok_or(InvoiceError::Overflow)?;
+        require!(spent <= self.daily_cap, InvoiceError::DailyCapExceeded);
+        self.daily_spent = spent;
+        Ok(())
+    }
+
+    // Give back a spend recorded at `spent_at` while its day's window is still
+    // open; once the window has rolled it no longer counts against the cap
+    pub fn release_spend(&mut self, amount: u64, spent_at: i64, now: i64) {
+        let day = spent_at / SECONDS_PER_DAY;
+        if day == self.last_reset_day && now / SECONDS_PER_DAY == day {
+            self.daily_spent = self.daily_spent.saturating_sub(amount);
+        }
+    }
 }
 
 
@@ -94,7 +364,10 @@ pub enum InvoiceStatus {
     InEscrowAuditPending,
     InEscrowReadyToSettle,
     Paid,
-    Refunded,
+    Refunded,                           // Rejected; escrowed funds awaiting refund_escrow
+    RefundCompleted,                    // Escrow returned to the org
+    NeedsReview,                        // OCR amount outside tolerance of the declared amount
+    ReviewRejected,                     // Amount mismatch rejected by the org authority
 }
 
 // Update Org Config Args
@@ -105,6 +378,14 @@ pub struct UpdateOrgConfigArgs {
     pub paused: Option<bool>,
     pub oracle_signer: Option<Pubkey>,
     pub mint: Option<Pubkey>,
+    pub extra_oracle_signers: Option<Vec<Pubkey>>,
+    pub amount_tolerance: Option<u64>,
+    pub amount_tolerance_bps: Option<u16>,
+    pub treasurer: Option<Pubkey>,
+    pub auditor: Option<Pubkey>,
+    pub vendor_manager: Option<Pubkey>,
+    pub auditors: Option<Vec<Pubkey>>,
+    pub audit_threshold: Option<u8>,
 }
 
 #[error_code]
@@ -141,4 +422,30 @@ pub enum InvoiceError {
     InvalidWallet,
     #[msg("Invalid IPFS hash")]
     InvalidIPFSHash,
+    #[msg("Daily spending cap exceeded")]
+    DailyCapExceeded,
+    #[msg("Too many oracle signers")]
+    TooManyOracleSigners,
+    #[msg("Invoice is already in the payment queue")]
+    AlreadyQueued,
+    #[msg("Invoice not found in the payment queue")]
+    NotInQueue,
+    #[msg("Queue neighbour accounts do not match the queue links")]
+    QueueLinkMismatch,
+    #[msg("Queue position would break due date ordering")]
+    QueueOrderViolation,
+    #[msg("Invalid amount tolerance (bps must be 0-10000)")]
+    InvalidTolerance,
+    #[msg("Invoice number is too long")]
+    InvalidInvoiceNumber,
+    #[msg("This document has already been processed")]
+    DuplicateDocument,
+    #[msg("This vendor invoice number has already been processed")]
+    DuplicateInvoiceNumber,
+    #[msg("Too many auditors")]
+    TooManyAuditors,
+    #[msg("Invalid audit threshold (must be 1 to the number of auditors)")]
+    InvalidAuditThreshold,
+    #[msg("Reviewer has already voted on this audit")]
+    AlreadyVoted,
 }
@@ -24,7 +24,12 @@ async function main() {
 
   const tx = await program.methods
     .auditDecide(true)
-    .accounts({ reviewer: wallet.publicKey, orgConfig: orgConfigPda, invoiceAccount: invoicePda })
+    .accounts({
+      reviewer: wallet.publicKey,
+      orgConfig: orgConfigPda,
+      invoiceAccount: invoicePda,
+      vendorAccount: (inv as any).vendor,
+    })
     .rpc();
 
   console.log("Audit approved. Tx:", tx);
@@ -98,6 +98,7 @@ async function main() {
     .accounts({
       orgConfig: orgConfigPda,
       invoiceAccount: invoicePda,
+      vendorAccount: (invoice as any).vendor,
       escrowAuthority: escrowAuthPda,
       payer: wallet.publicKey,
       authority: wallet.publicKey,
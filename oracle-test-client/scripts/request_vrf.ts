@@ -47,6 +47,7 @@ async function main() {
         payer: wallet.publicKey,
         orgConfig: orgConfigPda,
         invoiceAccount: invPubkey,
+        vendorAccount: new anchor.web3.PublicKey(myInvoices[0].account.data.slice(40, 72)),
         oracleQueue: queuePk,
       })
       .rpc();
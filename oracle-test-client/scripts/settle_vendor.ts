@@ -33,13 +33,13 @@ async function main() {
   const vendorPk = (invoice as any).vendor as anchor.web3.PublicKey | undefined;
   // If vendor pubkey is not stored, fetch vendor account PDA via invoice.vendor_name
   let vendorWallet: anchor.web3.PublicKey | null = null;
+  // Fetch vendor account PDA via name
+  const vendorName: string = (invoice as any).vendorName;
+  const [vendorPda] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("vendor"), orgConfigPda.toBuffer(), Buffer.from(vendorName)],
+    program.programId
+  );
   try {
-    // Fetch vendor account PDA via name
-    const vendorName: string = (invoice as any).vendorName;
-    const [vendorPda] = anchor.web3.PublicKey.findProgramAddressSync(
-      [Buffer.from("vendor"), orgConfigPda.toBuffer(), Buffer.from(vendorName)],
-      program.programId
-    );
     const vendorAcc = await program.account.vendorAccount.fetch(vendorPda);
     vendorWallet = (vendorAcc as any).wallet;
   } catch (e) {
@@ -55,6 +55,10 @@ async function main() {
     .accounts({
       orgConfig: orgConfigPda,
       invoiceAccount: invoicePda,
+      vendorAccount: vendorPda,
+      prevEntry: null,
+      nextEntry: null,
+      rentReceiver: wallet.publicKey,
       escrowAuthority: escrowAuthPda,
       vendorAta,
       escrowAta,
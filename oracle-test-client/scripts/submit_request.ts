@@ -9,6 +9,13 @@ async function main() {
   const ipfsHash = process.env.IPFS_HASH || "bafkreibjntqp7vaggmvtlgs2sptrjhiwywmrqwlcdbdoi2ub2medwdqomm";
   const amount = new anchor.BN(parseInt(process.env.REQUEST_AMOUNT || "100", 10));
   const nonce = new anchor.BN(Date.now());
+  // OrgConfig the invoice is submitted to; defaults to the wallet's own org
+  const orgConfig = process.env.ORG_CONFIG
+    ? new anchor.web3.PublicKey(process.env.ORG_CONFIG)
+    : anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("org_config"), wallet.publicKey.toBuffer()],
+        program.programId
+      )[0];
 
   const nonceLe = Buffer.from(nonce.toArray("le", 8));
   const [requestPda] = anchor.web3.PublicKey.findProgramAddressSync(
@@ -21,6 +28,7 @@ async function main() {
   console.log("Authority:", wallet.publicKey.toBase58());
   console.log("Nonce (u64):", nonce.toString());
   console.log("Request PDA:", requestPda.toBase58());
+  console.log("Org config:", orgConfig.toBase58());
   console.log("IPFS Hash:", ipfsHash);
   console.log("Amount:", amount.toString());
 
@@ -35,6 +43,7 @@ async function main() {
     .requestInvoiceExtraction(ipfsHash, amount, nonce)
     .accountsStrict({
       authority: wallet.publicKey,
+      orgConfig,
       invoiceRequest: requestPda,
       systemProgram: anchor.web3.SystemProgram.programId,
     })
@@ -1,6 +1,7 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { InvoiceClaim } from "../target/types/invoice_claim";
+import { createHash } from "crypto";
 import {
   createMint,
   getAssociatedTokenAddress,
@@ -95,9 +96,21 @@ describe("Invoice E2E (live VRF)", function () {
 
   it("resets prior state (idempotent)", async () => {
     try {
+      // Closing releases the invoice's document and number registries
+      const { ipfsHash, invoiceNumber, vendor } = await program.account.invoiceAccount.fetch(invoicePda);
+      const sha256 = (data: string) => createHash("sha256").update(data).digest();
+      const numberKey = invoiceNumber.replace(/[^a-z0-9]/gi, "").toUpperCase();
+      const [documentRegistry] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("invoice_doc"), orgConfigPda.toBuffer(), sha256(ipfsHash)],
+        program.programId
+      );
+      const [numberRegistry] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("invoice_number"), vendor.toBuffer(), sha256(numberKey || ipfsHash)],
+        program.programId
+      );
       await program.methods
         .closeInvoice()
-        .accounts({ invoiceAccount: invoicePda, authority })
+        .accounts({ invoiceAccount: invoicePda, documentRegistry, numberRegistry, authority })
         .rpc();
       await new Promise((r) => setTimeout(r, 500));
     } catch (_) {}
@@ -197,7 +210,13 @@ describe("Invoice E2E (live VRF)", function () {
     // Request live VRF
     await program.methods
       .requestInvoiceAuditVrf(42)
-      .accounts({ payer: authority, orgConfig: orgConfigPda, invoiceAccount: invoicePda, oracleQueue: VRF_QUEUE })
+      .accounts({
+        payer: authority,
+        orgConfig: orgConfigPda,
+        invoiceAccount: invoicePda,
+        vendorAccount: (await program.account.invoiceAccount.fetch(invoicePda)).vendor,
+        oracleQueue: VRF_QUEUE,
+      })
       .rpc();
 
     // Poll for VRF callback to update status
@@ -249,6 +268,10 @@ describe("Invoice E2E (live VRF)", function () {
       .accounts({
         orgConfig: orgConfigPda,
         invoiceAccount: invoicePda,
+        vendorAccount: vendorPda,
+        prevEntry: null,
+        nextEntry: null,
+        rentReceiver: authority,
         escrowAuthority: escrowAuthPda,
         vendorAta,
         escrowAta,
@@ -266,6 +289,11 @@ describe("Invoice E2E (live VRF)", function () {
     const escrowAfter = await provider.connection.getTokenAccountBalance(escrowAta).then(b => Number(b.value.amount));
     if (!(vendorAfter - vendorBefore >= invAmt)) throw new Error("Vendor did not receive expected amount");
     if (!(escrowBefore - escrowAfter >= 0)) throw new Error("Escrow balance did not decrease");
+
+    // Vendor payment stats are stamped on settlement
+    const vendorFinal: any = await program.account.vendorAccount.fetch(vendorPda);
+    if (!(Number(vendorFinal.totalPaid) - Number((vendorAcc as any).totalPaid) >= invAmt)) throw new Error("VendorAccount.total_paid not updated");
+    if (!(Number(vendorFinal.lastPayment) > 0)) throw new Error("VendorAccount.last_payment not stamped");
   });
 });
 
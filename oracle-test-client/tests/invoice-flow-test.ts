@@ -1,6 +1,7 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { InvoiceClaim } from "../target/types/invoice_claim";
+import { createHash } from "crypto";
 import {
   createMint,
   getAssociatedTokenAddress,
@@ -221,6 +222,10 @@ describe("Invoice lifecycle (escrow happy path)", () => {
         .accounts({
           orgConfig: orgConfigPda,
           invoiceAccount: invoicePda,
+          vendorAccount: vendorPda,
+          prevEntry: null,
+          nextEntry: null,
+          rentReceiver: authority,
           escrowAuthority: escrowAuthPda,
           vendorAta,
           escrowAta,
@@ -252,9 +257,21 @@ describe("Invoice lifecycle (escrow happy path)", () => {
     }
     // Reset any prior state: close invoice/request if present (idempotent)
     try {
+      // Closing releases the invoice's document and number registries
+      const { ipfsHash, invoiceNumber, vendor } = await program.account.invoiceAccount.fetch(invoicePda);
+      const sha256 = (data: string) => createHash("sha256").update(data).digest();
+      const numberKey = invoiceNumber.replace(/[^a-z0-9]/gi, "").toUpperCase();
+      const [documentRegistry] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("invoice_doc"), orgConfigPda.toBuffer(), sha256(ipfsHash)],
+        program.programId
+      );
+      const [numberRegistry] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("invoice_number"), vendor.toBuffer(), sha256(numberKey || ipfsHash)],
+        program.programId
+      );
       await program.methods
         .closeInvoice()
-        .accounts({ invoiceAccount: invoicePda, authority })
+        .accounts({ invoiceAccount: invoicePda, documentRegistry, numberRegistry, authority })
         .rpc();
       // tiny delay for close to land
       await new Promise((r) => setTimeout(r, 500));
@@ -340,6 +357,7 @@ describe("Invoice lifecycle (escrow happy path)", () => {
         vrfProgramIdentity: vrfIdentity,
         invoiceAccount: invoicePda,
         orgConfig: orgConfigPda,
+        vendorAccount: (await program.account.invoiceAccount.fetch(invoicePda)).vendor,
       })
       .rpc();
 
@@ -370,6 +388,10 @@ describe("Invoice lifecycle (escrow happy path)", () => {
       .accounts({
         orgConfig: orgConfigPda,
         invoiceAccount: invoicePda,
+        vendorAccount: vendorPda,
+        prevEntry: null,
+        nextEntry: null,
+        rentReceiver: authority,
         escrowAuthority: escrowAuthPda,
         vendorAta,
         escrowAta,